@@ -0,0 +1,150 @@
+use std::time::{Duration, Instant};
+
+/*
+*   conditions are timed effects on a creature
+*       poison, burning, electrified    deal damage every tick until the ticks run out
+*       haste, paralyze                 change the speed while active, one replaces the other
+*       light                           overrides the light of the creature while active
+*       invisible                       hides the outfit from other players
+*       drunk                           makes steps go in a random direction now and then
+*
+*   a creature has at most one condition of each type, adding one again replaces it
+*/
+
+// bits of the player icons packet
+pub const ICON_POISON: u8 = 1 << 0;
+pub const ICON_BURNING: u8 = 1 << 1;
+pub const ICON_ELECTRIFIED: u8 = 1 << 2;
+pub const ICON_DRUNK: u8 = 1 << 3;
+pub const ICON_PARALYZE: u8 = 1 << 5;
+pub const ICON_HASTE: u8 = 1 << 6;
+
+// chance in percent that a drunk creature staggers
+pub const DRUNK_STAGGER_CHANCE: u32 = 25;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ConditionType {
+    Poison,
+    Burning,
+    Electrified,
+    Haste,
+    Paralyze,
+    Light,
+    Invisible,
+    Drunk,
+}
+
+impl ConditionType {
+    pub fn from_name(name: &str) -> Option<ConditionType> {
+        return match name {
+            "poison" => Some(ConditionType::Poison),
+            "burning" => Some(ConditionType::Burning),
+            "electrified" => Some(ConditionType::Electrified),
+            "haste" => Some(ConditionType::Haste),
+            "paralyze" => Some(ConditionType::Paralyze),
+            "light" => Some(ConditionType::Light),
+            "invisible" => Some(ConditionType::Invisible),
+            "drunk" => Some(ConditionType::Drunk),
+            _ => None,
+        };
+    }
+
+    pub fn icon(&self) -> u8 {
+        return match self {
+            ConditionType::Poison => ICON_POISON,
+            ConditionType::Burning => ICON_BURNING,
+            ConditionType::Electrified => ICON_ELECTRIFIED,
+            ConditionType::Drunk => ICON_DRUNK,
+            ConditionType::Paralyze => ICON_PARALYZE,
+            ConditionType::Haste => ICON_HASTE,
+            ConditionType::Light | ConditionType::Invisible => 0,
+        };
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        return match self {
+            ConditionType::Poison => Duration::from_secs(2),
+            ConditionType::Burning => Duration::from_secs(4),
+            ConditionType::Electrified => Duration::from_secs(3),
+            _ => Duration::ZERO,
+        };
+    }
+
+    // 0 based magic effect shown when a damage tick hits
+    pub fn magic_effect(&self) -> u8 {
+        return match self {
+            ConditionType::Burning => 15,
+            ConditionType::Electrified => 11,
+            _ => 16,
+        };
+    }
+}
+
+#[derive(Clone)]
+pub struct Condition {
+    pub condition_type: ConditionType,
+    pub ends_at: Instant,
+    pub next_tick_at: Instant,
+    pub damage: u16,
+    pub speed_change: i32,
+    pub light_level: u8,
+    pub light_color: u8,
+    // credited with kills made by damage ticks
+    pub attacker_id: Option<u32>,
+}
+
+impl Condition {
+    pub fn timed(condition_type: ConditionType, duration: Duration) -> Condition {
+        let now = Instant::now();
+        return Condition {
+            next_tick_at: now + condition_type.tick_interval(),
+            condition_type,
+            ends_at: now + duration,
+            damage: 0,
+            speed_change: 0,
+            light_level: 0,
+            light_color: 0,
+            attacker_id: None,
+        };
+    }
+
+    pub fn damage(
+        condition_type: ConditionType,
+        damage: u16,
+        ticks: u32,
+        attacker_id: Option<u32>,
+    ) -> Condition {
+        let duration = condition_type.tick_interval() * ticks;
+        let mut condition = Condition::timed(condition_type, duration);
+        condition.damage = damage;
+        condition.attacker_id = attacker_id;
+        return condition;
+    }
+
+    pub fn speed(
+        condition_type: ConditionType,
+        speed_change: i32,
+        duration: Duration,
+    ) -> Condition {
+        let mut condition = Condition::timed(condition_type, duration);
+        condition.speed_change = speed_change;
+        return condition;
+    }
+
+    pub fn light(light_level: u8, light_color: u8, duration: Duration) -> Condition {
+        let mut condition = Condition::timed(ConditionType::Light, duration);
+        condition.light_level = light_level;
+        condition.light_color = light_color;
+        return condition;
+    }
+}
+
+// the parts of a creature that conditions change and other players can see
+#[derive(Clone, Copy, PartialEq)]
+pub struct ConditionView {
+    pub speed: u16,
+    pub light_level: u8,
+    pub light_color: u8,
+    pub icons: u8,
+    pub invisible: bool,
+}
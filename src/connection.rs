@@ -1,16 +1,32 @@
 use crate::{
     StaticData,
-    creature::Character,
+    creature::{Character, EquipModifiers, FIST_ATTACK, SKILL_FIST, SKILL_TITLES},
     event_handler::{Command, ServerEvent},
-    item::Item,
-    map::{Direction, Tile},
+    house::unix_time,
+    item::{Item, container_capacity, describe_item},
+    map::{Direction, Tile, VIEWPORT_X, VIEWPORT_Y, is_sight_clear},
+    message::{Cancel, cancel_payload, message_payload},
+    otb_io::item_loader::{FLAG_BLOCK_SOLID, FLAG_MOVEABLE, ItemData},
     payload::{
-        MagicEffect, SpeechType, add_item_to_container_payload, add_item_to_inventory_payload,
-        add_thing_payload, close_container_payload, container_payload, creature_added_payload,
-        creature_turn_payload, enter_game_payload, login_payload, magic_effect_payload,
-        map_direction_payload, remove_item_from_container_payload,
-        remove_item_from_inventory_payload, remove_thing_payload, speech_payload,
-        thing_moved_payload, thing_transformed_payload,
+        MagicEffect, MessageType, SpeechType, add_item_to_container_payload,
+        add_item_to_inventory_payload, add_thing_payload, cancel_target_payload,
+        cancel_walk_payload, close_container_payload, container_payload, creature_added_payload,
+        creature_health_payload, creature_light_payload, creature_outfit_payload,
+        creature_speed_payload, creature_turn_payload, distance_effect_payload, enter_game_payload,
+        full_map_payload, icons_payload, login_payload, magic_effect_payload,
+        map_direction_payload, player_skills_payload, player_stats_payload, quest_line_payload,
+        quest_log_payload, remove_item_from_container_payload, remove_item_from_inventory_payload,
+        remove_thing_payload, speech_payload, text_message_payload, text_window_payload,
+        thing_moved_payload, thing_transformed_payload, update_container_item_payload,
+    },
+    script::{creature_arg, item_arg},
+    xml_io::{
+        action_loader::DoorGate,
+        house_loader::House,
+        movement_loader::{MoveAction, MoveEventType},
+        player_loader::{PLAYERS_DIRECTORY, read_player, save_player},
+        spell_loader::{Spell, SpellEffect, find_spell},
+        vocation_loader::vocation_of,
     },
 };
 use std::{
@@ -19,14 +35,48 @@ use std::{
         Arc, Mutex,
         mpsc::{Receiver, Sender, TryRecvError},
     },
-    thread::sleep,
-    time::Duration,
+    time::{Duration, Instant},
     vec,
 };
 use tokio::net::TcpStream;
 
+// ids below this are reserved for player connections
+pub const FIRST_MONSTER_ID: u32 = 0x40000000;
+// used for tiles whose ground has no speed
+pub const DEFAULT_GROUND_SPEED: u16 = 150;
+
 pub struct State {
     pub map: HashMap<(u16, u16, u8), Tile>,
+    pub next_creature_id: u32,
+    // the next instance id given to a ground container that is opened or an item that decays
+    pub next_instance_id: u32,
+    pub houses: HashMap<u32, House>,
+}
+
+// where the items of a container window are
+#[derive(Clone, PartialEq)]
+pub enum ContainerRef {
+    // on the tile at pos or in a container on it, the world gave it the instance id when it
+    // was first opened
+    Ground {
+        pos: (u16, u16, u8),
+        instance_id: u32,
+    },
+    // in an inventory slot at pos or the locker of a town, reached through the slots of the
+    // containers inside it on the path, pos is the depot on the map for lockers
+    Held {
+        pos: (u16, u16, u8),
+        locker: Option<u32>,
+        path: Vec<u8>,
+    },
+}
+
+impl ContainerRef {
+    pub fn pos(&self) -> (u16, u16, u8) {
+        return match self {
+            ContainerRef::Ground { pos, .. } | ContainerRef::Held { pos, .. } => *pos,
+        };
+    }
 }
 
 #[derive(Clone)]
@@ -34,11 +84,21 @@ pub struct Container {
     pub container_id: u8,
     pub parent_id: Option<u8>,
     pub client_id: u16,
-    pub items: Vec<Item>,
-    pub pos: (u16, u16, u8),
-    pub stack_pos: u8,
+    pub location: ContainerRef,
     pub name: String,
     pub capacity: u8,
+    // inside the inventory of the player, so its items weigh on the capacity
+    pub carried: bool,
+}
+
+// the writeable item whose text window the player has open
+#[derive(Clone)]
+pub struct TextEdit {
+    pub window_id: u32,
+    pub pos: (u16, u16, u8),
+    pub stack_pos: u8,
+    pub item_id: u16,
+    pub max_length: u16,
 }
 
 pub struct Connection {
@@ -54,6 +114,20 @@ pub struct Connection {
     pub character: Option<Character>,
     pub data: Arc<StaticData>,
     pub open_containers: HashMap<u8, Container>,
+    pub health_regen_at: Instant,
+    pub mana_regen_at: Instant,
+    pub spell_cooldowns: HashMap<String, Instant>,
+    pub next_step_at: Instant,
+    pub text_edit: Option<TextEdit>,
+    pub next_window_id: u32,
+    // the creature the player attacks, 0 for none
+    pub attack_target: u32,
+    pub next_attack_at: Instant,
+    pub next_save_at: Instant,
+}
+
+fn save_interval(data: &StaticData) -> Duration {
+    return Duration::from_secs(data.config.players.save_minutes.max(1) as u64 * 60);
 }
 
 impl Connection {
@@ -75,14 +149,27 @@ impl Connection {
             state,
             event_handler_in,
             character: None,
-            data,
             event_receiver,
             open_containers: HashMap::new(),
+            health_regen_at: Instant::now(),
+            mana_regen_at: Instant::now(),
+            spell_cooldowns: HashMap::new(),
+            next_step_at: Instant::now(),
+            text_edit: None,
+            next_window_id: 1,
+            attack_target: 0,
+            next_attack_at: Instant::now(),
+            next_save_at: Instant::now() + save_interval(&data),
+            data,
         };
     }
 
     pub async fn handle_events(&mut self) {
-        let mut payload: Vec<u8> = Vec::new();
+        let mut payload: Vec<u8> = self.regenerate();
+        self.attack();
+        self.save_periodically();
+        let carried_weight = self.carried_weight();
+        let position = self.character.as_ref().map(|character| character.position);
         loop {
             match self.event_receiver.try_recv() {
                 Ok(event) => match event {
@@ -100,15 +187,25 @@ impl Connection {
                         creature_id,
                         direction,
                     } => {
-                        payload.extend_from_slice(&thing_transformed_payload(&from, stack_pos, None));
-                        payload.extend_from_slice(&creature_turn_payload(direction.clone(), creature_id));
+                        payload.extend_from_slice(&thing_transformed_payload(
+                            &from,
+                            stack_pos,
+                            None,
+                            &self.data.item_data,
+                        ));
+                        payload.extend_from_slice(&creature_turn_payload(
+                            direction.clone(),
+                            creature_id,
+                        ));
                         payload.extend_from_slice(&thing_moved_payload(&from, stack_pos, &to));
                         if creature_id == self.id {
                             self.character.as_mut().unwrap().position = to;
+                            self.character.as_mut().unwrap().look_dir = direction.clone();
                             payload.extend_from_slice(&map_direction_payload(
                                 self.state.clone(),
                                 direction,
                                 to,
+                                &self.data.item_data,
                             ));
                         }
                     }
@@ -117,7 +214,10 @@ impl Connection {
                             self.state.clone(),
                             &self.character.as_ref().unwrap().position,
                             self.id,
+                            &self.data.item_data,
                         ));
+                        payload.extend_from_slice(&self.player_stats());
+                        payload.extend_from_slice(&self.update_equipment());
                     }
                     ServerEvent::CreatureRemoved { pos, stack_pos } => {
                         payload.extend_from_slice(&remove_thing_payload(&pos, stack_pos));
@@ -126,47 +226,21 @@ impl Connection {
                             MagicEffect::Puff as u8,
                         ));
                     }
-                    ServerEvent::ItemMoved {
-                        from,
-                        to,
-                        stack_pos,
-                        item_id,
-                    } => {
-                        let mut container_id: Option<u8> = None;
-                        for (id, container) in self.open_containers.iter() {
-                            if from == container.pos && container.stack_pos == stack_pos {
-                                container_id = Some(*id);
-                            }
-                        }
-                        if let Some(c_id) = container_id {
-                            payload.extend_from_slice(&close_container_payload(c_id));
-                            self.open_containers.remove(&c_id);
-                        }
-
-                        if from.0 < 0xFFFF {
-                            payload.extend_from_slice(&remove_thing_payload(&from, stack_pos));
-                        }
-                        if to.0 < 0xFFFF {
-                            payload.extend_from_slice(&add_thing_payload(&to, &item_id));
-                        }
-                    }
                     ServerEvent::OpenContainer {
+                        container,
                         index,
                         item,
                         name,
                         parent_id,
                         capacity,
-                        pos,
-                        stack_pos,
                     } => {
                         let mut has_parent = 0;
                         let mut container = Container {
                             container_id: index,
                             parent_id: None,
                             client_id: item.client_id,
-                            items: item.items,
-                            pos: pos,
-                            stack_pos,
+                            carried: self.is_carried(container.pos()),
+                            location: container,
                             name,
                             capacity,
                         };
@@ -181,73 +255,95 @@ impl Connection {
                                 container.parent_id = parent_id;
                             }
                         }
+                        // the window shows the new container instead of the one it had
+                        self.forget_window(index);
                         self.open_containers.insert(index, container.clone());
                         payload.extend_from_slice(&container_payload(
                             &container,
+                            &self.container_items(&container),
                             &container.name,
                             capacity,
                             has_parent,
+                            &self.data.item_data,
                         ));
                     }
-                    ServerEvent::AddedToContainer {
-                        pos,
-                        stack_pos,
-                        item,
-                    } => {
-                        for (container_id, container) in self.open_containers.iter_mut() {
-                            if pos == container.pos && container.stack_pos == stack_pos {
-                                if pos.0 == 0xFFFF && pos.1 & 0x40 != 0x40 {
-                                    let char = self.character.as_mut().unwrap();
-                                    let inventory_item =
-                                        char.inventory.clone().get_from_slot(pos.1);
-                                    if let Some(mut it) = inventory_item {
-                                        it.add_item(item.clone());
-                                        char.inventory.equip(pos.1, it);
-                                    }
-                                }
-                                let mut items = vec![item.clone()];
-                                items.append(&mut container.items);
-                                container.items = items;
-                                payload.extend_from_slice(&add_item_to_container_payload(
-                                    &item.client_id,
-                                    *container_id,
-                                ));
+                    ServerEvent::AddedToContainer { container, item } => {
+                        if let ContainerRef::Held { pos, locker, path } = &container {
+                            let added = self.change_held_container(*pos, *locker, path, |items| {
+                                items.insert(0, item.clone());
+                            });
+                            // the container left the inventory meanwhile, the item falls
+                            // to the feet of the player instead of getting lost
+                            if !added {
+                                let position = self.character.as_ref().unwrap().position;
+                                let _ = self.event_handler_in.send(Command::MoveItem {
+                                    from: (0xFFFF, 0, 0),
+                                    to: position,
+                                    stack_pos: 0,
+                                    count: item.count,
+                                    item,
+                                });
+                                continue;
                             }
                         }
+                        payload.extend_from_slice(&self.item_added_to_container(&container, &item));
                     }
-                    ServerEvent::RemovedFromContainer {
-                        pos,
-                        stack_pos,
+                    ServerEvent::RemovedFromContainer { container, slot } => {
+                        if let ContainerRef::Held { pos, locker, path } = &container {
+                            self.change_held_container(*pos, *locker, path, |items| {
+                                if (slot as usize) < items.len() {
+                                    items.remove(slot as usize);
+                                }
+                            });
+                        }
+                        payload
+                            .extend_from_slice(&self.item_removed_from_container(&container, slot));
+                    }
+                    ServerEvent::ContainerItemUpdated {
+                        container,
                         slot,
+                        item,
                     } => {
-                        for (container_id, container) in self.open_containers.iter_mut() {
-                            if pos == container.pos && container.stack_pos == stack_pos {
-                                if pos.0 == 0xFFFF && pos.1 & 0x40 != 0x40 {
-                                    let char = self.character.as_mut().unwrap();
-                                    let inventory_item =
-                                        char.inventory.clone().get_from_slot(pos.1);
-                                    if let Some(mut it) = inventory_item {
-                                        it.items.remove(slot as usize);
-                                        char.inventory.equip(pos.1, it);
-                                    }
+                        if let ContainerRef::Held { pos, locker, path } = &container {
+                            self.change_held_container(*pos, *locker, path, |items| {
+                                if let Some(it) = items.get_mut(slot as usize) {
+                                    *it = item.clone();
                                 }
-                                container.items.remove(slot as usize);
-                                payload.extend_from_slice(&remove_item_from_container_payload(
-                                    *container_id,
-                                    slot,
-                                ));
-                            }
+                            });
+                        }
+                        for container_id in self.windows_of(&container) {
+                            payload.extend_from_slice(&update_container_item_payload(
+                                container_id,
+                                slot,
+                                &item,
+                                &self.data.item_data,
+                            ));
+                        }
+                    }
+                    ServerEvent::ContainerClosed { instance_id } => {
+                        let closed: Vec<u8> = self
+                            .open_containers
+                            .iter()
+                            .filter(|(_, container)| {
+                                matches!(container.location, ContainerRef::Ground { instance_id: id, .. } if id == instance_id)
+                            })
+                            .map(|(container_id, _)| *container_id)
+                            .collect();
+                        for container_id in closed {
+                            payload.extend_from_slice(&close_container_payload(container_id));
+                            self.open_containers.remove(&container_id);
                         }
                     }
                     ServerEvent::ThingTransformed {
                         pos,
                         stack_pos,
-                        to_item_id,
+                        item,
                     } => {
                         payload.extend_from_slice(&thing_transformed_payload(
                             &pos,
                             stack_pos,
-                            Some(to_item_id),
+                            Some(&item),
+                            &self.data.item_data,
                         ));
                     }
                     ServerEvent::CreatureSpoke {
@@ -258,7 +354,7 @@ impl Connection {
                     } => payload.extend_from_slice(&speech_payload(
                         &text,
                         &creature_name,
-                        SpeechType::Say,
+                        SpeechType::from_u8(speech_type),
                         &pos,
                     )),
                     ServerEvent::CreatureTurned {
@@ -267,20 +363,728 @@ impl Connection {
                         direction,
                         creature_id,
                     } => {
-                        payload
-                            .extend_from_slice(&thing_transformed_payload(&pos, stack_pos, None));
+                        if creature_id == self.id {
+                            self.character.as_mut().unwrap().look_dir = direction.clone();
+                        }
+                        payload.extend_from_slice(&thing_transformed_payload(
+                            &pos,
+                            stack_pos,
+                            None,
+                            &self.data.item_data,
+                        ));
                         payload.extend_from_slice(&creature_turn_payload(direction, creature_id));
                     }
+                    ServerEvent::CreatureHealthChanged {
+                        creature_id,
+                        health,
+                        max_health,
+                    } => {
+                        let percent = (health as u32 * 100 / max_health.max(1) as u32) as u8;
+                        payload.extend_from_slice(&creature_health_payload(creature_id, percent));
+                        if creature_id == self.id {
+                            let character = self.character.as_mut().unwrap();
+                            character.health = health;
+                            character.max_health = max_health;
+                            payload.extend_from_slice(&self.player_stats());
+                        }
+                    }
+                    ServerEvent::ItemAdded { pos, item } => {
+                        payload.extend_from_slice(&add_thing_payload(
+                            &pos,
+                            &item,
+                            &self.data.item_data,
+                        ));
+                    }
+                    ServerEvent::PlayerDied { pos } => {
+                        payload.extend_from_slice(&self.handle_death(pos));
+                    }
+                    ServerEvent::ExperienceGained { experience } => {
+                        let character = self.character.as_mut().unwrap();
+                        let vocation = vocation_of(&self.data.vocations, character.vocation);
+                        let old_level = character.level;
+                        if character.add_experience(experience, vocation) {
+                            payload.extend_from_slice(&message_payload(
+                                MessageType::EventAdvance,
+                                &format!(
+                                    "You advanced from Level {} to Level {}.",
+                                    old_level, character.level
+                                ),
+                            ));
+                            let _ = self.event_handler_in.send(Command::SetMaxHealth {
+                                pos: character.position,
+                                creature_id: self.id,
+                                max_health: character.max_health,
+                            });
+                            let _ = self.event_handler_in.send(Command::SetPlayerLevel {
+                                creature_id: self.id,
+                                level: character.level,
+                                vocation: character.vocation,
+                            });
+                        }
+                        payload.extend_from_slice(&self.player_stats());
+                    }
+                    ServerEvent::MagicEffect { pos, effect } => {
+                        payload.extend_from_slice(&magic_effect_payload(&pos, effect));
+                    }
+                    ServerEvent::CreatureSpeedChanged { creature_id, speed } => {
+                        if creature_id == self.id {
+                            self.character.as_mut().unwrap().speed = speed;
+                        }
+                        payload.extend_from_slice(&creature_speed_payload(creature_id, speed));
+                    }
+                    ServerEvent::CreatureLightChanged {
+                        creature_id,
+                        light_level,
+                        light_color,
+                    } => {
+                        payload.extend_from_slice(&creature_light_payload(
+                            creature_id,
+                            light_level,
+                            light_color,
+                        ));
+                    }
+                    ServerEvent::IconsChanged { icons } => {
+                        payload.extend_from_slice(&icons_payload(icons));
+                    }
+                    ServerEvent::CreatureOutfitChanged {
+                        creature_id,
+                        outfit,
+                    } => {
+                        payload.extend_from_slice(&creature_outfit_payload(creature_id, &outfit));
+                    }
+                    ServerEvent::TextWindow {
+                        pos,
+                        stack_pos,
+                        item_id,
+                        text,
+                        max_length,
+                        writeable,
+                    } => {
+                        // the client sends the window id back with the edited text
+                        let window_id = self.next_window_id;
+                        self.next_window_id = self.next_window_id.wrapping_add(1);
+                        self.text_edit = writeable.then_some(TextEdit {
+                            window_id,
+                            pos,
+                            stack_pos,
+                            item_id,
+                            max_length,
+                        });
+                        payload.extend_from_slice(&text_window_payload(
+                            window_id, item_id, max_length, &text,
+                        ));
+                    }
+                    ServerEvent::Promoted => {
+                        let character = self.character.as_mut().unwrap();
+                        if character.promote(&self.data.vocations) {
+                            let _ = self.event_handler_in.send(Command::SetPlayerLevel {
+                                creature_id: self.id,
+                                level: character.level,
+                                vocation: character.vocation,
+                            });
+                        }
+                    }
+                    ServerEvent::LockerOpened {
+                        pos,
+                        town_id,
+                        index,
+                    } => {
+                        payload.extend_from_slice(&self.open_locker(pos, town_id, index));
+                    }
+                    ServerEvent::ChestOpened {
+                        reward_id,
+                        storage_key,
+                    } => {
+                        payload.extend_from_slice(&self.take_chest_reward(reward_id, storage_key));
+                    }
+                    ServerEvent::GatedDoorUsed {
+                        pos,
+                        stack_pos,
+                        door,
+                        open_id,
+                        gate,
+                    } => {
+                        payload.extend_from_slice(&self.pass_gated_door(
+                            (pos, stack_pos),
+                            &door,
+                            open_id,
+                            gate,
+                        ));
+                    }
+                    ServerEvent::RentDue {
+                        house_id,
+                        town_id,
+                        rent,
+                    } => {
+                        let character = self.character.as_mut().unwrap();
+                        let paid = match character.depots.get_mut(&town_id) {
+                            Some(locker) => locker.take_money(rent),
+                            None => false,
+                        };
+                        let _ = self
+                            .event_handler_in
+                            .send(Command::RentPaid { house_id, paid });
+                        payload.extend_from_slice(&if paid {
+                            message_payload(
+                                MessageType::StatusConsole,
+                                &format!("The rent of {} gold for your house was paid.", rent),
+                            )
+                        } else {
+                            message_payload(
+                                MessageType::Warning,
+                                "You could not pay the rent and lost your house.",
+                            )
+                        });
+                    }
+                    ServerEvent::StorageChanged { key, value } => {
+                        let character = self.character.as_mut().unwrap();
+                        character.storage.insert(key, value);
+                    }
+                    ServerEvent::WalkCancelled { direction } => {
+                        payload.extend_from_slice(&cancel_walk_payload(direction));
+                    }
+                    ServerEvent::TextMessage { message_type, text } => {
+                        payload.extend_from_slice(&text_message_payload(message_type, &text));
+                    }
+                    ServerEvent::DistanceEffect { from, to, effect } => {
+                        payload.extend_from_slice(&distance_effect_payload(&from, &to, effect));
+                    }
+                    ServerEvent::ItemSubtypeChanged { pos, subtype } => {
+                        payload.extend_from_slice(&self.set_item_subtype(pos, subtype));
+                    }
+                    ServerEvent::ManaGained { amount } => {
+                        let character = self.character.as_mut().unwrap();
+                        character.mana = (character.mana + amount).min(character.max_mana);
+                        payload.extend_from_slice(&self.player_stats());
+                    }
+                    ServerEvent::ItemChargeUsed {
+                        pos,
+                        default_charges,
+                    } => {
+                        payload.extend_from_slice(&self.use_item_charge(pos, default_charges));
+                    }
+                    ServerEvent::ItemRemoved { pos, stack_pos } => {
+                        payload.extend_from_slice(&remove_thing_payload(&pos, stack_pos));
+                    }
+                    ServerEvent::SkillTried { skill } => {
+                        payload.extend_from_slice(&self.add_skill_try(skill));
+                    }
+                    ServerEvent::TargetLost => {
+                        self.attack_target = 0;
+                        payload.extend_from_slice(&cancel_target_payload());
+                    }
+                    ServerEvent::Teleported { pos } => {
+                        self.character.as_mut().unwrap().position = pos;
+                        payload.extend_from_slice(&full_map_payload(
+                            self.state.clone(),
+                            &pos,
+                            &self.data.item_data,
+                        ));
+                    }
                 },
                 Err(TryRecvError::Empty) => break,
                 Err(_) => panic!("Event handler for connection closed"),
             }
         }
+        if self.carried_weight() != carried_weight {
+            payload.extend_from_slice(&self.player_stats());
+        }
+        if self.character.as_ref().map(|character| character.position) != position {
+            payload.extend_from_slice(&self.close_far_lockers());
+        }
         if payload.len() > 0 {
             self.send_packet(&payload).await;
         }
     }
 
+    // the open container itself, taken from the map for ground containers and from the
+    // character for the held ones, so that every window shows the one real copy
+    fn container_item(&self, container: &Container) -> Option<Item> {
+        let character = self.character.as_ref().unwrap();
+        return match &container.location {
+            ContainerRef::Ground { pos, instance_id } => {
+                let state_handle = self.state.lock().unwrap();
+                state_handle
+                    .map
+                    .get(pos)
+                    .and_then(|tile| tile.find_container(*instance_id))
+                    .cloned()
+            }
+            ContainerRef::Held {
+                locker: Some(town_id),
+                path,
+                ..
+            } => character
+                .depots
+                .get(town_id)
+                .and_then(|locker| locker.nested(path))
+                .cloned(),
+            ContainerRef::Held { pos, path, .. } => character
+                .inventory
+                .clone()
+                .get_from_slot(pos.1)
+                .and_then(|item| item.nested(path).cloned()),
+        };
+    }
+
+    fn container_items(&self, container: &Container) -> Vec<Item> {
+        return self
+            .container_item(container)
+            .map_or(vec![], |container_item| container_item.items);
+    }
+
+    // the open container a position in a container window points into, with its items
+    fn window_contents(&self, pos: (u16, u16, u8)) -> Option<(Container, Vec<Item>)> {
+        if pos.0 != 0xFFFF || pos.1 & 0x40 != 0x40 {
+            return None;
+        }
+        let container = self.open_containers.get(&((pos.1 & 0x0F) as u8))?;
+        return Some((container.clone(), self.container_items(container)));
+    }
+
+    fn windows_of(&self, location: &ContainerRef) -> Vec<u8> {
+        return self
+            .open_containers
+            .iter()
+            .filter(|(_, container)| container.location == *location)
+            .map(|(container_id, _)| *container_id)
+            .collect();
+    }
+
+    // the world stops sending changes of a ground container once none of its windows is left
+    fn forget_window(&mut self, container_id: u8) -> Option<Container> {
+        let container = self.open_containers.remove(&container_id)?;
+        match container.location {
+            ContainerRef::Ground { instance_id, .. }
+                if self.windows_of(&container.location).is_empty() =>
+            {
+                let _ = self.event_handler_in.send(Command::CloseContainer {
+                    sender_id: self.id,
+                    instance_id,
+                });
+            }
+            _ => {}
+        }
+        return Some(container);
+    }
+
+    // containers in the inventory and the lockers are changed here, the world keeps the rest,
+    // false when the container is not held anymore
+    fn change_held_container(
+        &mut self,
+        pos: (u16, u16, u8),
+        locker: Option<u32>,
+        path: &[u8],
+        change: impl FnOnce(&mut Vec<Item>),
+    ) -> bool {
+        let character = self.character.as_mut().unwrap();
+        if let Some(town_id) = locker {
+            let Some(container) = character.locker(town_id).nested_mut(path) else {
+                return false;
+            };
+            change(&mut container.items);
+            return true;
+        }
+        let Some(mut item) = character.inventory.clone().get_from_slot(pos.1) else {
+            return false;
+        };
+        let Some(container) = item.nested_mut(path) else {
+            return false;
+        };
+        change(&mut container.items);
+        character.inventory.equip(pos.1, item);
+        return true;
+    }
+
+    // the windows of containers in an inventory slot close once the slot is left
+    fn close_slot_windows(&mut self, slot: u16) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        let closed: Vec<u8> = self
+            .open_containers
+            .iter()
+            .filter(|(_, container)| {
+                matches!(
+                    container.location,
+                    ContainerRef::Held { pos, locker: None, .. } if pos.1 == slot
+                )
+            })
+            .map(|(container_id, _)| *container_id)
+            .collect();
+        for container_id in closed {
+            self.open_containers.remove(&container_id);
+            payload.extend_from_slice(&close_container_payload(container_id));
+        }
+        return payload;
+    }
+
+    // new items go in front, so held containers inside move one slot further
+    fn item_added_to_container(&mut self, location: &ContainerRef, item: &Item) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        for container_id in self.windows_of(location) {
+            payload.extend_from_slice(&add_item_to_container_payload(
+                item,
+                container_id,
+                &self.data.item_data,
+            ));
+        }
+        for container in self.open_containers.values_mut() {
+            if let Some(nested_slot) = nested_slot_mut(container, location) {
+                *nested_slot += 1;
+            }
+        }
+        return payload;
+    }
+
+    // held containers after the slot move one slot back, the windows of the removed one close,
+    // the world closes the windows of ground containers itself
+    fn item_removed_from_container(&mut self, location: &ContainerRef, slot: u8) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        for container_id in self.windows_of(location) {
+            payload.extend_from_slice(&remove_item_from_container_payload(container_id, slot));
+        }
+        let mut closed: Vec<u8> = Vec::new();
+        for (container_id, container) in self.open_containers.iter_mut() {
+            match nested_slot_mut(container, location) {
+                Some(nested_slot) if *nested_slot == slot => closed.push(*container_id),
+                Some(nested_slot) if *nested_slot > slot => *nested_slot -= 1,
+                _ => {}
+            }
+        }
+        for container_id in closed {
+            self.open_containers.remove(&container_id);
+            payload.extend_from_slice(&close_container_payload(container_id));
+        }
+        return payload;
+    }
+
+    // lockers close once the player is not next to their depot anymore
+    fn close_far_lockers(&mut self) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        let position = self.character.as_ref().unwrap().position;
+        let closed: Vec<u8> = self
+            .open_containers
+            .iter()
+            .filter(|(_, container)| match container.location {
+                ContainerRef::Held {
+                    pos,
+                    locker: Some(_),
+                    ..
+                } => {
+                    pos.2 != position.2
+                        || pos.0.abs_diff(position.0) > 1
+                        || pos.1.abs_diff(position.1) > 1
+                }
+                _ => false,
+            })
+            .map(|(container_id, _)| *container_id)
+            .collect();
+        for container_id in closed {
+            self.open_containers.remove(&container_id);
+            payload.extend_from_slice(&close_container_payload(container_id));
+        }
+        return payload;
+    }
+
+    // lockers belong to the character so no other player sees the same container
+    fn open_locker(&mut self, depot_pos: (u16, u16, u8), town_id: u32, index: u8) -> Vec<u8> {
+        let locker = self.character.as_mut().unwrap().locker(town_id).clone();
+        let name = match self.data.item_data.get(&locker.client_id) {
+            Some(item_data) => item_data.item_name.clone(),
+            None => "locker".to_string(),
+        };
+        let container = Container {
+            container_id: index,
+            parent_id: None,
+            client_id: locker.client_id,
+            location: ContainerRef::Held {
+                pos: depot_pos,
+                locker: Some(town_id),
+                path: vec![],
+            },
+            name,
+            capacity: container_capacity(locker.client_id, &self.data.item_data),
+            carried: false,
+        };
+        self.open_containers.insert(index, container.clone());
+        return container_payload(
+            &container,
+            &locker.items,
+            &container.name,
+            container.capacity,
+            0,
+            &self.data.item_data,
+        );
+    }
+
+    // quest chests give their reward once, the storage remembers it was taken
+    fn take_chest_reward(&mut self, reward_id: u16, storage_key: u32) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        let character = self.character.as_mut().unwrap();
+        if character.storage.contains_key(&storage_key) {
+            payload.extend_from_slice(&text_message_payload(
+                MessageType::InfoDescription as u8,
+                "It is empty.",
+            ));
+            return payload;
+        }
+        let reward = Item::new(reward_id);
+        let name = match self.data.item_data.get(&reward_id) {
+            Some(item_data) => item_data.item_name.clone(),
+            None => "item".to_string(),
+        };
+        let weight = reward.weight(&self.data.item_data);
+        if weight > character.free_capacity(&self.data.item_data) {
+            let text = format!(
+                "You have found a {} weighing {}.{:02} oz. It is too heavy.",
+                name,
+                weight / 100,
+                weight % 100
+            );
+            payload.extend_from_slice(&text_message_payload(
+                MessageType::InfoDescription as u8,
+                &text,
+            ));
+            return payload;
+        }
+        let backpack_has_room = character
+            .inventory
+            .backpack
+            .as_ref()
+            .is_some_and(|backpack| !backpack.is_full(&self.data.item_data));
+        if backpack_has_room {
+            character
+                .inventory
+                .backpack
+                .as_mut()
+                .unwrap()
+                .add_item(reward.clone());
+            // the backpack may be open, its window has to show the reward as well
+            payload.extend_from_slice(&self.item_added_to_container(
+                &ContainerRef::Held {
+                    pos: (0xFFFF, 3, 0),
+                    locker: None,
+                    path: vec![],
+                },
+                &reward,
+            ));
+        } else if let Some(slot) = [6, 5]
+            .into_iter()
+            .find(|slot| character.inventory.clone().get_from_slot(*slot).is_none())
+        {
+            payload.extend_from_slice(&add_item_to_inventory_payload(
+                &reward,
+                slot as u8,
+                &self.data.item_data,
+            ));
+            character.inventory.equip(slot, reward);
+        } else {
+            let text = format!(
+                "You have found a {}, but you have no room to take it.",
+                name
+            );
+            payload.extend_from_slice(&text_message_payload(
+                MessageType::InfoDescription as u8,
+                &text,
+            ));
+            return payload;
+        }
+        let character = self.character.as_mut().unwrap();
+        character.storage.insert(storage_key, 1);
+        let _ = self.event_handler_in.send(Command::SetStorage {
+            creature_id: self.id,
+            key: storage_key,
+            value: 1,
+        });
+        payload.extend_from_slice(&text_message_payload(
+            MessageType::InfoDescription as u8,
+            &format!("You have found a {}.", name),
+        ));
+        return payload;
+    }
+
+    // level and quest doors open for the players they let through, who then step in
+    fn pass_gated_door(
+        &mut self,
+        (pos, stack_pos): ((u16, u16, u8), u8),
+        door: &Item,
+        open_id: u16,
+        gate: DoorGate,
+    ) -> Vec<u8> {
+        let character = self.character.as_ref().unwrap();
+        let allowed = match gate {
+            DoorGate::Level => character.level as i32 >= door.action_id as i32 - 1000,
+            DoorGate::Quest => character.storage.contains_key(&(door.action_id as u32)),
+        };
+        if !allowed {
+            let text = match gate {
+                DoorGate::Level => "Only the worthy may pass.",
+                DoorGate::Quest => "The door seems to be sealed against unwanted intruders.",
+            };
+            return text_message_payload(MessageType::InfoDescription as u8, text);
+        }
+        // the player steps into the door, which it can only do from a tile next to it
+        let from = character.position;
+        let Some(direction) = Direction::towards(from, pos) else {
+            return cancel_payload(Cancel::NotPossible);
+        };
+        let _ = self.event_handler_in.send(Command::TransformThing {
+            pos,
+            stack_pos,
+            from_item_id: door.client_id,
+            to_item_id: open_id,
+        });
+        self.next_step_at = Instant::now() + self.step_duration();
+        let _ = self.event_handler_in.send(Command::MoveCreature {
+            from,
+            to: pos,
+            direction,
+            creature_id: self.id,
+        });
+        return vec![];
+    }
+
+    fn handle_death(&mut self, pos: (u16, u16, u8)) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        self.attack_target = 0;
+        for container_id in self.open_containers.keys() {
+            payload.extend_from_slice(&close_container_payload(*container_id));
+        }
+        self.open_containers.clear();
+        payload.extend_from_slice(&message_payload(MessageType::EventDefault, "You are dead."));
+        let temple_pos = self.temple_position();
+        let death_config = &self.data.config.death;
+        let character = self.character.as_mut().unwrap();
+        let vocation = vocation_of(&self.data.vocations, character.vocation);
+        character.apply_death_penalty(death_config, vocation);
+        // conditions die with the creature
+        character.speed = character.base_speed;
+        payload.extend_from_slice(&icons_payload(0));
+        let mut corpse_items: Vec<Item> = Vec::new();
+        for (slot, item) in character.drop_items_on_death(death_config) {
+            payload.extend_from_slice(&remove_item_from_inventory_payload(slot as u8));
+            corpse_items.push(item);
+        }
+        character.position = temple_pos;
+        let _ = self.event_handler_in.send(Command::PlaceCorpse {
+            pos,
+            corpse_id: death_config.player_corpse_id,
+            items: corpse_items,
+        });
+        let _ = self.event_handler_in.send(Command::EnterGame {
+            character_creature: character.as_creature(),
+            pos: temple_pos,
+        });
+        return payload;
+    }
+
+    fn regenerate(&mut self) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        let Some(character) = self.character.as_mut() else {
+            return payload;
+        };
+        let vocation = vocation_of(&self.data.vocations, character.vocation);
+        let now = Instant::now();
+        if now >= self.health_regen_at {
+            self.health_regen_at = now + Duration::from_secs(vocation.gain_health_ticks as u64);
+            if character.health < character.max_health {
+                let _ = self.event_handler_in.send(Command::ChangeHealth {
+                    pos: character.position,
+                    creature_id: self.id,
+                    amount: vocation.gain_health_amount as i32,
+                    attacker_id: None,
+                });
+            }
+        }
+        if now >= self.mana_regen_at {
+            self.mana_regen_at = now + Duration::from_secs(vocation.gain_mana_ticks as u64);
+            if character.mana < character.max_mana {
+                character.mana =
+                    (character.mana + vocation.gain_mana_amount).min(character.max_mana);
+                payload.extend_from_slice(&player_stats_payload(
+                    character,
+                    vocation,
+                    &self.data.item_data,
+                ));
+            }
+        }
+        return payload;
+    }
+
+    fn player_stats(&self) -> Vec<u8> {
+        let character = self.character.as_ref().unwrap();
+        let vocation = vocation_of(&self.data.vocations, character.vocation);
+        return player_stats_payload(character, vocation, &self.data.item_data);
+    }
+
+    // the skills are sent again once one of them advances
+    fn add_skill_try(&mut self, skill: usize) -> Vec<u8> {
+        let character = self.character.as_mut().unwrap();
+        let vocation = vocation_of(&self.data.vocations, character.vocation);
+        if !character.add_skill_tries(skill, 1, vocation) {
+            return vec![];
+        }
+        let mut payload = message_payload(
+            MessageType::EventAdvance,
+            &format!("You advanced in {}.", SKILL_TITLES[skill]),
+        );
+        payload.extend_from_slice(&self.player_skills());
+        return payload;
+    }
+
+    // the world is asked to hit the target whenever the attack speed of the vocation allows
+    fn attack(&mut self) {
+        let Some(character) = self.character.as_ref() else {
+            return;
+        };
+        let now = Instant::now();
+        if self.attack_target == 0 || now < self.next_attack_at {
+            return;
+        }
+        let vocation = vocation_of(&self.data.vocations, character.vocation);
+        self.next_attack_at = now + Duration::from_millis(vocation.attack_speed as u64);
+        let weapon = character.inventory.weapon(&self.data.item_data);
+        let skill = weapon
+            .and_then(|data| data.weapon_skill)
+            .unwrap_or(SKILL_FIST);
+        let boosts = character.inventory.modifiers(&self.data.item_data).skills;
+        let _ = self.event_handler_in.send(Command::Attack {
+            attacker_id: self.id,
+            target_id: self.attack_target,
+            skill,
+            skill_level: character.skills[skill].level + boosts[skill],
+            attack: weapon.map_or(FIST_ATTACK, |data| data.attack),
+            shoot_effect: weapon.and_then(|data| data.shoot_effect),
+        });
+    }
+
+    // 0 stops attacking
+    pub fn handle_attack_packet(&mut self) {
+        self.attack_target = self.read_u32_le();
+        if self.attack_target == self.id {
+            self.attack_target = 0;
+        }
+    }
+
+    fn player_skills(&self) -> Vec<u8> {
+        let character = self.character.as_ref().unwrap();
+        let vocation = vocation_of(&self.data.vocations, character.vocation);
+        return player_skills_payload(character, vocation, &self.data.item_data);
+    }
+
+    fn temple_position(&self) -> (u16, u16, u8) {
+        let character = self.character.as_ref().unwrap();
+        if let Some(town) = self.data.towns.get(&character.town_id) {
+            return town.temple_pos;
+        }
+        // maps without towns respawn players where they were created
+        for static_character in self.data.characters.iter() {
+            if static_character.name == character.name {
+                return static_character.position;
+            }
+        }
+        return character.position;
+    }
+
     pub async fn login(&mut self) {
         self.send_packet(&login_payload(&self.data.characters))
             .await;
@@ -352,16 +1156,40 @@ impl Connection {
         let _password = self.read_str();
         for character in self.data.characters.iter() {
             if character.name == name {
-                self.character = Some(character.clone());
-                self.character.as_mut().unwrap().id = self.id;
+                let mut character = character.clone();
+                character.id = self.id;
+                read_player(PLAYERS_DIRECTORY, &mut character, &self.data.vocations);
                 let _ = self.event_handler_in.send(Command::EnterGame {
-                    character_creature: self.character.as_ref().unwrap().as_creature(),
+                    character_creature: character.as_creature(),
                     pos: character.position,
                 });
+                self.character = Some(character);
             }
         }
     }
 
+    // saves the player now and then so a crash loses little of its progress
+    fn save_periodically(&mut self) {
+        let now = Instant::now();
+        if now < self.next_save_at {
+            return;
+        }
+        self.next_save_at = now + save_interval(&self.data);
+        if let Some(character) = self.character.as_ref() {
+            save_player(PLAYERS_DIRECTORY, character);
+        }
+    }
+
+    // saves the player and takes its creature out of the world
+    pub fn logout(&mut self) {
+        if let Some(character) = self.character.as_ref() {
+            save_player(PLAYERS_DIRECTORY, character);
+        }
+        let _ = self
+            .event_handler_in
+            .send(Command::Logout { sender_id: self.id });
+    }
+
     pub fn _parse_login_payload(&mut self) {
         let _ = self.read_u16_le();
         let _client_version = self.read_u16_le();
@@ -370,10 +1198,12 @@ impl Connection {
         let _password = self.read_str();
     }
 
-    pub fn handle_move_character_packets(&mut self, direction: Direction) {
-        // let sleep_time = GROUND_SPEED * 1000 / self.character.as_ref().unwrap().speed as u32;
-        let sleep_time = 58;
-        sleep(Duration::from_millis(sleep_time as u64));
+    pub async fn handle_move_character_packets(&mut self, direction: Direction) {
+        let now = Instant::now();
+        if self.next_step_at > now {
+            tokio::time::sleep(self.next_step_at - now).await;
+        }
+        self.next_step_at = Instant::now() + self.step_duration();
         let from = self.character.as_ref().unwrap().position;
         let to = direction.move_in_dir(from);
         let _ = self.event_handler_in.send(Command::MoveCreature {
@@ -384,6 +1214,22 @@ impl Connection {
         });
     }
 
+    // time a step from the current tile takes at the current speed
+    fn step_duration(&self) -> Duration {
+        let character = self.character.as_ref().unwrap();
+        let ground_speed = {
+            let state_handle = self.state.lock().unwrap();
+            state_handle
+                .map
+                .get(&character.position)
+                .and_then(|tile| self.data.item_data.get(&tile.floor_item_client_id))
+                .map(|item_data| item_data.speed)
+                .filter(|speed| *speed > 0)
+                .unwrap_or(DEFAULT_GROUND_SPEED)
+        };
+        return Duration::from_millis(ground_speed as u64 * 1000 / character.speed.max(1) as u64);
+    }
+
     pub fn handle_creature_turn_packets(&mut self, direction: Direction) {
         let character = self.character.as_ref().unwrap();
         let pos = character.position;
@@ -398,51 +1244,112 @@ impl Connection {
     pub async fn handle_move_item(&mut self) {
         // TODO: handle drag and drop onto container
         let from = self.read_position();
-        let item_id = self.read_u16_le();
+        let _item_id = self.read_u16_le();
         let stack_pos = self.read_u8();
         let to = self.read_position();
         let count = self.read_u8();
+        let failure = if !self.can_move_items_at(from) || !self.can_move_items_at(to) {
+            Some(Cancel::NotPossible)
+        } else {
+            self.move_failure(from, stack_pos, to, count)
+        };
+        if let Some(cancel) = failure {
+            self.send_packet(&cancel_payload(cancel)).await;
+            return;
+        }
         let mut payload: Vec<u8> = Vec::new();
+        let carried_weight = self.carried_weight();
+        let equipment = self.equipment();
+        let from_window = self.window_contents(from);
+        let to_window = self.window_contents(to);
         let mut item: Option<Item> = None;
+        let mut vacated_slot: Option<u16> = None;
+        let item_data = &self.data.item_data;
         let character = self.character.as_mut().unwrap();
         let mut commands: Vec<Command> = Vec::new();
         if from.0 == 0xFFFF {
             // from container
-            if from.1 & 0x40 == 0x40 {
-                let from_container_id = (from.1 & 0x0F) as u8;
-                let container = self.open_containers.get(&from_container_id).unwrap();
-                item = container.items.get(from.2 as usize).cloned();
-                commands.push(Command::RemoveItemFromContainer {
-                    pos: container.pos,
-                    stack_pos: container.stack_pos,
-                    slot: from.2,
-                    sender_id: self.id,
-                });
+            if let Some((container, items)) = from_window {
+                if let Some(mut source) = items.get(from.2 as usize).cloned() {
+                    // part of a stack leaves the rest in its slot
+                    match source.split(count, item_data) {
+                        Some(taken) => {
+                            commands.push(Command::UpdateContainerItem {
+                                container: container.location.clone(),
+                                slot: from.2,
+                                item: source,
+                                sender_id: self.id,
+                            });
+                            item = Some(taken);
+                        }
+                        None => {
+                            commands.push(Command::RemoveItemFromContainer {
+                                container: container.location,
+                                slot: from.2,
+                                sender_id: self.id,
+                            });
+                            item = Some(source);
+                        }
+                    }
+                }
             } else {
                 // from inventory
-                item = character.inventory.remove_from_slot(from.1);
-                payload.extend_from_slice(&remove_item_from_inventory_payload(from.1 as u8));
+                let mut source = character.inventory.clone().get_from_slot(from.1);
+                if let Some(taken) = source.as_mut().and_then(|it| it.split(count, item_data)) {
+                    let rest = source.unwrap();
+                    payload.extend_from_slice(&add_item_to_inventory_payload(
+                        &rest,
+                        from.1 as u8,
+                        item_data,
+                    ));
+                    character.inventory.equip(from.1, rest);
+                    item = Some(taken);
+                } else {
+                    item = character.inventory.remove_from_slot(from.1);
+                    vacated_slot = Some(from.1);
+                    payload.extend_from_slice(&remove_item_from_inventory_payload(from.1 as u8));
+                    if let Some(it) = item.as_mut() {
+                        payload.extend_from_slice(&run_equip_actions(
+                            &self.data,
+                            (self.id, character.position),
+                            MoveEventType::DeEquip,
+                            from.1,
+                            it,
+                            &mut commands,
+                        ));
+                    }
+                }
             }
-            if let Some(it) = item {
+            if let Some(mut it) = item {
                 if to.0 == 0xFFFF {
                     // to container
-                    if to.1 & 0x40 == 0x40 {
-                        // TODO: consider handling the addition here
-                        let to_container_id = (to.1 & 0x0F) as u8;
-                        let container = self.open_containers.get_mut(&to_container_id).unwrap();
-                        commands.push(Command::AddToContainer {
-                            item: it.clone(),
-                            sender_id: self.id,
-                            slot: to.2,
-                            container: container.clone(),
-                        });
+                    if let Some(window) = to_window.as_ref() {
+                        commands.push(drop_into_container(window, to.2, it, self.id, item_data));
                     } else {
                         // to inventory
-                        character.inventory.equip(to.1, it.clone());
-                        payload.extend_from_slice(&add_item_to_inventory_payload(
-                            it.client_id,
-                            to.1 as u8,
-                        ));
+                        match character.inventory.clone().get_from_slot(to.1) {
+                            Some(mut stack) if stack.can_merge(&it, item_data) => {
+                                stack.count += it.count;
+                                payload.extend_from_slice(&add_item_to_inventory_payload(
+                                    &stack, to.1 as u8, item_data,
+                                ));
+                                character.inventory.equip(to.1, stack);
+                            }
+                            _ => {
+                                payload.extend_from_slice(&run_equip_actions(
+                                    &self.data,
+                                    (self.id, character.position),
+                                    MoveEventType::Equip,
+                                    to.1,
+                                    &mut it,
+                                    &mut commands,
+                                ));
+                                character.inventory.equip(to.1, it.clone());
+                                payload.extend_from_slice(&add_item_to_inventory_payload(
+                                    &it, to.1 as u8, item_data,
+                                ));
+                            }
+                        }
                     }
                 } else {
                     // to ground
@@ -461,7 +1368,7 @@ impl Connection {
             let state_handle = self.state.lock().unwrap();
             let tile = state_handle.map.get(&from).unwrap();
             item = tile.get_item_at_stack_pos(stack_pos);
-            if let Some(it) = item {
+            if let Some(mut it) = item {
                 commands.push(Command::MoveItem {
                     from,
                     to,
@@ -470,24 +1377,36 @@ impl Connection {
                     count,
                 });
                 if to.0 == 0xFFFF {
-                    if to.1 & 0x40 == 0x40 {
-                        // TODO: consider handling the addition here
-                        let to_container_id = (to.1 & 0x0F) as u8;
-                        let container = self.open_containers.get(&to_container_id).unwrap();
-                        commands.push(Command::AddToContainer {
-                            item: it,
-                            sender_id: self.id,
-                            slot: to.2,
-                            container: container.clone(),
-                        });
+                    // the world takes the same part of the stack off the ground
+                    if let Some(taken) = it.split(count, item_data) {
+                        it = taken;
+                    }
+                    if let Some(window) = to_window.as_ref() {
+                        commands.push(drop_into_container(window, to.2, it, self.id, item_data));
                     } else {
-                        self.character
-                            .as_mut()
-                            .unwrap()
-                            .inventory
-                            .equip(to.1, it.clone());
-                        payload
-                            .extend_from_slice(&add_item_to_inventory_payload(item_id, to.1 as u8));
+                        match character.inventory.clone().get_from_slot(to.1) {
+                            Some(mut stack) if stack.can_merge(&it, item_data) => {
+                                stack.count += it.count;
+                                payload.extend_from_slice(&add_item_to_inventory_payload(
+                                    &stack, to.1 as u8, item_data,
+                                ));
+                                character.inventory.equip(to.1, stack);
+                            }
+                            _ => {
+                                payload.extend_from_slice(&run_equip_actions(
+                                    &self.data,
+                                    (self.id, character.position),
+                                    MoveEventType::Equip,
+                                    to.1,
+                                    &mut it,
+                                    &mut commands,
+                                ));
+                                character.inventory.equip(to.1, it.clone());
+                                payload.extend_from_slice(&add_item_to_inventory_payload(
+                                    &it, to.1 as u8, item_data,
+                                ));
+                            }
+                        }
                     }
                 }
             }
@@ -495,58 +1414,324 @@ impl Connection {
         for command in commands.iter() {
             let _ = self.event_handler_in.send(command.clone());
         }
+        if let Some(slot) = vacated_slot {
+            payload.extend_from_slice(&self.close_slot_windows(slot));
+        }
+        if self.carried_weight() != carried_weight {
+            payload.extend_from_slice(&self.player_stats());
+        }
+        if self.equipment() != equipment {
+            payload.extend_from_slice(&self.update_equipment());
+        }
         if payload.len() > 0 {
             self.send_packet(&payload).await;
         }
     }
 
+    fn equipment(&self) -> EquipModifiers {
+        let character = self.character.as_ref().unwrap();
+        return character.inventory.modifiers(&self.data.item_data);
+    }
+
+    // the world applies speed, light and armor of the worn items, the skills are shown here
+    fn update_equipment(&self) -> Vec<u8> {
+        let _ = self.event_handler_in.send(Command::SetEquipment {
+            creature_id: self.id,
+            equipment: self.equipment(),
+        });
+        return self.player_skills();
+    }
+
+    fn carried_weight(&self) -> u32 {
+        return self.character.as_ref().map_or(0, |character| {
+            character.inventory.weight(&self.data.item_data)
+        });
+    }
+
+    // inventory slots and the containers opened from them, lockers are on the map
+    fn is_carried(&self, pos: (u16, u16, u8)) -> bool {
+        if pos.0 != 0xFFFF {
+            return false;
+        }
+        if pos.1 & 0x40 == 0x40 {
+            return self
+                .open_containers
+                .get(&((pos.1 & 0x0F) as u8))
+                .is_some_and(|container| container.carried);
+        }
+        return true;
+    }
+
+    // why the item at from cannot be moved to, checked before it is taken from anywhere
+    fn move_failure(
+        &self,
+        from: (u16, u16, u8),
+        stack_pos: u8,
+        to: (u16, u16, u8),
+        count: u8,
+    ) -> Option<Cancel> {
+        let is_open = |pos: (u16, u16, u8)| {
+            pos.0 != 0xFFFF
+                || pos.1 & 0x40 != 0x40
+                || self.open_containers.contains_key(&((pos.1 & 0x0F) as u8))
+        };
+        // a window whose container is not there anymore takes and gives nothing
+        let is_gone = |pos: (u16, u16, u8)| {
+            pos.0 == 0xFFFF
+                && pos.1 & 0x40 == 0x40
+                && self
+                    .open_containers
+                    .get(&((pos.1 & 0x0F) as u8))
+                    .and_then(|container| self.container_item(container))
+                    .is_none()
+        };
+        if !is_open(from) || !is_open(to) || is_gone(from) || is_gone(to) {
+            return Some(Cancel::NotPossible);
+        }
+        let item_data = &self.data.item_data;
+        let has_flag = |client_id: u16, flag: u32| {
+            item_data
+                .get(&client_id)
+                .is_some_and(|data| data.has_flag(flag))
+        };
+        let character = self.character.as_ref().unwrap();
+        let player_pos = character.position;
+        let mut moved = self.resolve_item(from, 0);
+        // where a held item is, found the same way as the held containers of the windows
+        let held_at = match self
+            .window_contents(from)
+            .map(|(container, _)| container.location)
+        {
+            Some(ContainerRef::Held { pos, locker, path }) => Some(ContainerRef::Held {
+                pos,
+                locker,
+                path: [&path[..], &[from.2]].concat(),
+            }),
+            Some(ContainerRef::Ground { .. }) => None,
+            None if from.0 == 0xFFFF => Some(ContainerRef::Held {
+                pos: (0xFFFF, from.1, 0),
+                locker: None,
+                path: vec![],
+            }),
+            None => None,
+        };
+        let target_window = self.window_contents(to);
+        let state_handle = self.state.lock().unwrap();
+        if from.0 != 0xFFFF {
+            let Some(item) = state_handle
+                .map
+                .get(&from)
+                .and_then(|tile| tile.get_item_at_stack_pos(stack_pos))
+            else {
+                return Some(Cancel::NotPossible);
+            };
+            if !has_flag(item.client_id, FLAG_MOVEABLE) {
+                return Some(Cancel::NotMoveable);
+            }
+            if player_pos.2 != from.2
+                || player_pos.0.abs_diff(from.0) > 1
+                || player_pos.1.abs_diff(from.1) > 1
+            {
+                return Some(Cancel::TooFarAway);
+            }
+            moved = item;
+        }
+        if to.0 == 0xFFFF && to.1 & 0x40 != 0x40 {
+            // the slot the item comes from is free for it
+            let mut inventory = character.inventory.clone();
+            if from.0 == 0xFFFF && from.1 & 0x40 != 0x40 {
+                inventory.remove_from_slot(from.1);
+            }
+            if let Some(cancel) = inventory.equip_failure(to.1, &moved, item_data) {
+                return Some(cancel);
+            }
+        }
+        if let Some((target, _)) = target_window.as_ref() {
+            // a container cannot go into itself or any container inside it
+            let into_itself = match (&target.location, held_at) {
+                (ContainerRef::Ground { instance_id, .. }, _) => {
+                    moved.find_instance(*instance_id).is_some()
+                }
+                (
+                    ContainerRef::Held { pos, locker, path },
+                    Some(ContainerRef::Held {
+                        pos: from_pos,
+                        locker: from_locker,
+                        path: from_path,
+                    }),
+                ) => *pos == from_pos && *locker == from_locker && path.starts_with(&from_path),
+                _ => false,
+            };
+            if into_itself {
+                return Some(Cancel::ThisIsImpossible);
+            }
+        }
+        let taken = moved.clone().split(count, item_data).unwrap_or(moved);
+        if let Some((target, items)) = target_window {
+            let merges = items
+                .get(to.2 as usize)
+                .is_some_and(|stack| stack.can_merge(&taken, item_data));
+            if !merges && items.len() >= target.capacity as usize {
+                return Some(Cancel::ContainerFull);
+            }
+        }
+        if self.is_carried(to)
+            && !self.is_carried(from)
+            && taken.weight(item_data) > character.free_capacity(item_data)
+        {
+            return Some(Cancel::TooHeavy);
+        }
+        if to.0 != 0xFFFF {
+            let Some(tile) = state_handle.map.get(&to) else {
+                return Some(Cancel::CannotThrowThere);
+            };
+            if player_pos.2 != to.2
+                || player_pos.0.abs_diff(to.0) > VIEWPORT_X
+                || player_pos.1.abs_diff(to.1) > VIEWPORT_Y
+                || !is_sight_clear(&state_handle.map, item_data, player_pos, to)
+            {
+                return Some(Cancel::CannotThrowThere);
+            }
+            // walls and other fixed solid things leave no room, solid furniture can be stacked on
+            let no_room = std::iter::once(tile.floor_item_client_id)
+                .chain(
+                    tile.top_items
+                        .iter()
+                        .chain(tile.bot_items.iter())
+                        .map(|item| item.client_id),
+                )
+                .any(|client_id| {
+                    has_flag(client_id, FLAG_BLOCK_SOLID) && !has_flag(client_id, FLAG_MOVEABLE)
+                });
+            if no_room {
+                return Some(Cancel::NotEnoughRoom);
+            }
+        }
+        return None;
+    }
+
+    // items in houses are only moved by the players the owner allowed to
+    fn can_move_items_at(&self, pos: (u16, u16, u8)) -> bool {
+        let ground_pos = if pos.0 != 0xFFFF {
+            pos
+        } else if pos.1 & 0x40 == 0x40 {
+            match self.open_containers.get(&((pos.1 & 0x0F) as u8)) {
+                Some(container) if container.location.pos().0 != 0xFFFF => container.location.pos(),
+                _ => return true,
+            }
+        } else {
+            return true;
+        };
+        let name = &self.character.as_ref().unwrap().name;
+        let state_handle = self.state.lock().unwrap();
+        let Some(tile) = state_handle.map.get(&ground_pos) else {
+            return true;
+        };
+        return match state_handle.houses.get(&tile.house_id) {
+            Some(house) => house.can_move_items(name),
+            None => true,
+        };
+    }
+
     pub fn handle_use_item(&mut self) {
         let from = self.read_position();
         let item_id = self.read_u16_le();
         let stack_pos = self.read_u8();
         let index = self.read_u8();
-        let item: Item;
-        if from.0 == 0xFFFF {
-            if from.1 & 0x40 == 0x40 {
-                let container_id = (from.1 & 0x0F) as u8;
-                let container = &self.open_containers.get(&container_id).unwrap();
-                item = container.items[from.2 as usize].clone();
-            } else {
-                if let Some(it) = self
-                    .character
-                    .as_ref()
-                    .unwrap()
-                    .inventory
-                    .clone()
-                    .get_from_slot(from.1)
-                {
-                    item = it;
-                } else {
-                    item = Item {
-                        client_id: item_id,
-                        items: vec![],
-                    };
-                }
-            }
-        } else {
-            item = Item {
-                client_id: item_id,
-                items: vec![],
-            };
-        }
+        let item = self.resolve_item(from, item_id);
         let _ = self.event_handler_in.send(Command::UseItem {
             sender_id: self.id,
             pos: from,
             stack_pos,
             item,
             index,
+            window: self
+                .window_contents(from)
+                .map(|(container, _)| container.location),
+        });
+    }
+
+    pub async fn handle_look_at(&mut self) {
+        let pos = self.read_position();
+        let item_id = self.read_u16_le();
+        let stack_pos = self.read_u8();
+        if pos.0 != 0xFFFF {
+            let _ = self.event_handler_in.send(Command::LookAt {
+                sender_id: self.id,
+                pos,
+                stack_pos,
+            });
+            return;
+        }
+        let item = self.resolve_item(pos, item_id);
+        let text = describe_item(&item, &self.data.item_data, true);
+        self.send_packet(&text_message_payload(
+            MessageType::InfoDescription as u8,
+            &text,
+        ))
+        .await;
+    }
+
+    pub fn handle_edit_text(&mut self) {
+        let window_id = self.read_u32_le();
+        let text = self.read_str();
+        let Some(edit) = self.text_edit.take_if(|edit| edit.window_id == window_id) else {
+            return;
+        };
+        if text.len() > edit.max_length as usize {
+            return;
+        }
+        let character = self.character.as_mut().unwrap();
+        let writer = character.name.clone();
+        if edit.pos.0 != 0xFFFF {
+            let _ = self.event_handler_in.send(Command::WriteText {
+                pos: edit.pos,
+                stack_pos: edit.stack_pos,
+                item_id: edit.item_id,
+                text,
+                writer,
+            });
+            return;
+        }
+        if edit.pos.1 & 0x40 != 0x40 {
+            if let Some(mut item) = character
+                .inventory
+                .clone()
+                .get_from_slot(edit.pos.1)
+                .filter(|item| item.client_id == edit.item_id)
+            {
+                item.text = text;
+                item.writer = writer;
+                item.written_at = unix_time();
+                character.inventory.equip(edit.pos.1, item);
+            }
+            return;
+        }
+        let Some((container, items)) = self.window_contents(edit.pos) else {
+            return;
+        };
+        let Some(mut item) = items
+            .get(edit.pos.2 as usize)
+            .filter(|item| item.client_id == edit.item_id)
+            .cloned()
+        else {
+            return;
+        };
+        item.text = text;
+        item.writer = writer;
+        item.written_at = unix_time();
+        let _ = self.event_handler_in.send(Command::UpdateContainerItem {
+            container: container.location,
+            slot: edit.pos.2,
+            item,
+            sender_id: self.id,
         });
     }
 
     pub async fn handle_close_container(&mut self) {
         let mut payload: Vec<u8> = Vec::new();
         let container_id = self.read_u8();
-        self.open_containers.remove(&container_id);
+        self.forget_window(container_id);
         payload.push(0x6F);
         payload.push(container_id);
         self.send_packet(&payload).await;
@@ -555,29 +1740,44 @@ impl Connection {
     pub async fn handle_container_up(&mut self) {
         let mut payload: Vec<u8> = Vec::new();
         let container_id = self.read_u8();
-        let container = self.open_containers.remove(&container_id).unwrap();
-        let parent_container = self
+        let Some(parent_id) = self
             .open_containers
-            .remove(&(container.parent_id.unwrap() as u8))
-            .unwrap();
-        self.open_containers.insert(
-            0xFF - &(container.parent_id.unwrap()),
-            parent_container.clone(),
-        );
+            .get(&container_id)
+            .and_then(|container| container.parent_id)
+        else {
+            return;
+        };
+        let Some(parent_container) = self.open_containers.remove(&parent_id) else {
+            return;
+        };
+        self.forget_window(container_id);
+        self.open_containers
+            .insert(0xFF - parent_id, parent_container.clone());
         payload.extend_from_slice(&container_payload(
             &parent_container,
+            &self.container_items(&parent_container),
             &parent_container.name.clone(),
             parent_container.capacity,
             0,
+            &self.data.item_data,
         ));
         self.send_packet(&payload).await;
     }
-    pub fn handle_say_packet(&mut self) {
+    pub async fn handle_say_packet(&mut self) {
         let speech_type = self.read_u8();
         let speech_text = self.read_str();
+        let data = self.data.clone();
+        if let Some((spell, param)) = find_spell(&data.spells, &speech_text) {
+            let payload = self.cast_spell(spell, param, &speech_text, speech_type);
+            if payload.len() > 0 {
+                self.send_packet(&payload).await;
+            }
+            return;
+        }
         let char_pos = self.character.as_ref().unwrap().position;
         let char_name = self.character.as_ref().unwrap().name.clone();
         let _ = self.event_handler_in.send(Command::CreatureSpeech {
+            sender_id: self.id,
             pos: char_pos,
             text: speech_text.to_string(),
             creature_name: char_name,
@@ -585,15 +1785,380 @@ impl Connection {
         });
     }
 
-    pub fn handle_use_item_on_target_packet(&mut self) {
-        let pos = self.read_position();
+    fn cast_spell(
+        &mut self,
+        spell: &Spell,
+        param: String,
+        words: &str,
+        speech_type: u8,
+    ) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        let character = self.character.as_mut().unwrap();
+        let vocation = vocation_of(&self.data.vocations, character.vocation);
+        let on_cooldown = match self.spell_cooldowns.get(&spell.name) {
+            Some(ready_at) => *ready_at > Instant::now(),
+            None => false,
+        };
+        let failure =
+            if !spell.vocations.contains(&character.vocation) && !vocation.can_cast(&spell.name) {
+                Some("Your vocation cannot use this spell.")
+            } else if character.level < spell.level {
+                Some("You do not have enough level.")
+            } else if character.magic_level < spell.magic_level {
+                Some("You do not have enough magic level.")
+            } else if character.mana < spell.mana {
+                Some("You do not have enough mana.")
+            } else if on_cooldown {
+                Some("You are exhausted.")
+            } else if matches!(spell.effect, SpellEffect::Summon) && param.is_empty() {
+                Some("You need to name a creature to summon.")
+            } else {
+                None
+            };
+        if let Some(text) = failure {
+            payload.extend_from_slice(&text_message_payload(MessageType::StatusSmall as u8, text));
+            payload.extend_from_slice(&magic_effect_payload(
+                &character.position,
+                MagicEffect::Puff as u8,
+            ));
+            return payload;
+        }
+        character.mana -= spell.mana;
+        if character.add_mana_spent(spell.mana as u32, vocation) {
+            payload.extend_from_slice(&message_payload(
+                MessageType::EventAdvance,
+                &format!("You advanced to magic level {}.", character.magic_level),
+            ));
+        }
+        payload.extend_from_slice(&player_stats_payload(
+            character,
+            vocation,
+            &self.data.item_data,
+        ));
+        self.spell_cooldowns.insert(
+            spell.name.clone(),
+            Instant::now() + Duration::from_millis(spell.cooldown as u64),
+        );
+        let _ = self.event_handler_in.send(Command::CreatureSpeech {
+            sender_id: self.id,
+            pos: character.position,
+            text: words.to_string(),
+            creature_name: character.name.clone(),
+            speech_type,
+        });
+        let _ = self.event_handler_in.send(Command::CastSpell {
+            caster_id: self.id,
+            spell_name: spell.name.clone(),
+            param,
+        });
+        return payload;
+    }
+
+    // the item at an inventory or open container position, ground items are looked up by the world
+    fn resolve_item(&self, pos: (u16, u16, u8), item_id: u16) -> Item {
+        if pos.0 == 0xFFFF {
+            if pos.1 & 0x40 == 0x40 {
+                if let Some(item) = self
+                    .window_contents(pos)
+                    .and_then(|(_, items)| items.get(pos.2 as usize).cloned())
+                {
+                    return item;
+                }
+            } else if let Some(item) = self
+                .character
+                .as_ref()
+                .unwrap()
+                .inventory
+                .clone()
+                .get_from_slot(pos.1)
+            {
+                return item;
+            }
+        }
+        return Item::new(item_id);
+    }
+
+    // level requirements are checked here, everything about the target is up to the world
+    fn check_rune(&self, item_id: u16) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        let Some(rune) = self.data.runes.get(&item_id) else {
+            return payload;
+        };
+        let character = self.character.as_ref().unwrap();
+        let failure = if character.level < rune.level {
+            Some("You do not have enough level.")
+        } else if character.magic_level < rune.magic_level {
+            Some("You do not have enough magic level.")
+        } else {
+            None
+        };
+        if let Some(text) = failure {
+            payload.extend_from_slice(&text_message_payload(MessageType::StatusSmall as u8, text));
+            payload.extend_from_slice(&magic_effect_payload(
+                &character.position,
+                MagicEffect::Puff as u8,
+            ));
+        }
+        return payload;
+    }
+
+    pub async fn handle_use_item_with_packet(&mut self) {
+        let from = self.read_position();
         let item_id = self.read_u16_le();
         let stack_pos = self.read_u8();
-        let target_pos = self.read_u16_le();
+        let target_pos = self.read_position();
+        let _target_item_id = self.read_u16_le();
         let target_stack_pos = self.read_u8();
+        let failure = self.check_rune(item_id);
+        if failure.len() > 0 {
+            self.send_packet(&failure).await;
+            return;
+        }
+        let _ = self.event_handler_in.send(Command::UseItemOnTarget {
+            sender_id: self.id,
+            pos: from,
+            stack_pos,
+            item: self.resolve_item(from, item_id),
+            target_pos,
+            target_stack_pos,
+        });
+    }
+
+    pub async fn handle_use_item_on_creature_packet(&mut self) {
+        let from = self.read_position();
+        let item_id = self.read_u16_le();
+        let stack_pos = self.read_u8();
+        let creature_id = self.read_u32_le();
+        let failure = self.check_rune(item_id);
+        if failure.len() > 0 {
+            self.send_packet(&failure).await;
+            return;
+        }
+        let _ = self.event_handler_in.send(Command::UseItemOnCreature {
+            sender_id: self.id,
+            pos: from,
+            stack_pos,
+            item: self.resolve_item(from, item_id),
+            creature_id,
+        });
+    }
+
+    // a charge of an item held by the player was used up, the world keeps ground items itself
+    fn set_item_subtype(&mut self, pos: (u16, u16, u8), subtype: u8) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        if pos.1 & 0x40 != 0x40 {
+            let character = self.character.as_mut().unwrap();
+            if let Some(mut item) = character.inventory.clone().get_from_slot(pos.1) {
+                item.subtype = subtype;
+                payload.extend_from_slice(&add_item_to_inventory_payload(
+                    &item,
+                    pos.1 as u8,
+                    &self.data.item_data,
+                ));
+                character.inventory.equip(pos.1, item);
+            }
+            return payload;
+        }
+        let Some((container, items)) = self.window_contents(pos) else {
+            return payload;
+        };
+        let Some(mut item) = items.get(pos.2 as usize).cloned() else {
+            return payload;
+        };
+        item.subtype = subtype;
+        // the world sends the change back to everyone seeing the container
+        let _ = self.event_handler_in.send(Command::UpdateContainerItem {
+            container: container.location,
+            slot: pos.2,
+            item,
+            sender_id: self.id,
+        });
+        return payload;
+    }
+
+    fn use_item_charge(&mut self, pos: (u16, u16, u8), default_charges: u8) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        if pos.1 & 0x40 != 0x40 {
+            let character = self.character.as_mut().unwrap();
+            if let Some(mut item) = character.inventory.clone().get_from_slot(pos.1) {
+                if item.use_charge(default_charges) {
+                    character.inventory.remove_from_slot(pos.1);
+                    payload.extend_from_slice(&remove_item_from_inventory_payload(pos.1 as u8));
+                } else {
+                    payload.extend_from_slice(&add_item_to_inventory_payload(
+                        &item,
+                        pos.1 as u8,
+                        &self.data.item_data,
+                    ));
+                    character.inventory.equip(pos.1, item);
+                }
+            }
+            return payload;
+        }
+        let slot = pos.2;
+        let Some((container, items)) = self.window_contents(pos) else {
+            return payload;
+        };
+        let Some(mut item) = items.get(slot as usize).cloned() else {
+            return payload;
+        };
+        // the change comes back like any other, for held containers from the world as well
+        let command = if item.use_charge(default_charges) {
+            Command::RemoveItemFromContainer {
+                container: container.location,
+                slot,
+                sender_id: self.id,
+            }
+        } else {
+            Command::UpdateContainerItem {
+                container: container.location,
+                slot,
+                item,
+                sender_id: self.id,
+            }
+        };
+        let _ = self.event_handler_in.send(command);
+        return payload;
+    }
+
+    pub async fn handle_quest_log(&mut self) {
+        let storage = &self.character.as_ref().unwrap().storage;
+        let quests: Vec<(u16, String, bool)> = self
+            .data
+            .quests
+            .iter()
+            .filter(|quest| quest.is_started(storage))
+            .map(|quest| (quest.id, quest.name.clone(), quest.is_completed(storage)))
+            .collect();
+        self.send_packet(&quest_log_payload(&quests)).await;
+    }
+
+    pub async fn handle_quest_line(&mut self) {
+        let quest_id = self.read_u16_le();
+        let storage = &self.character.as_ref().unwrap().storage;
+        let Some(quest) = self
+            .data
+            .quests
+            .iter()
+            .find(|quest| quest.id == quest_id && quest.is_started(storage))
+        else {
+            return;
+        };
+        let missions: Vec<(String, String)> = quest
+            .missions
+            .iter()
+            .filter(|mission| mission.is_started(storage))
+            .map(|mission| (mission.name.clone(), mission.description(storage)))
+            .collect();
+        self.send_packet(&quest_line_payload(quest_id, &missions))
+            .await;
     }
 
     pub async fn handle_ping(&mut self) {
         self.send_packet(&vec![0x1E]).await;
     }
 }
+
+// the slot in the changed held container that leads to a held container inside it, None for
+// the changed container itself and for the ones elsewhere
+fn nested_slot_mut<'a>(container: &'a mut Container, changed: &ContainerRef) -> Option<&'a mut u8> {
+    let (
+        ContainerRef::Held { pos, locker, path },
+        ContainerRef::Held {
+            pos: changed_pos,
+            locker: changed_locker,
+            path: changed_path,
+        },
+    ) = (&mut container.location, changed)
+    else {
+        return None;
+    };
+    if pos != changed_pos || locker != changed_locker || !path.starts_with(changed_path) {
+        return None;
+    }
+    return path.get_mut(changed_path.len());
+}
+
+// stacks dropped onto the same item in a container join it
+fn drop_into_container(
+    (container, items): &(Container, Vec<Item>),
+    slot: u8,
+    item: Item,
+    sender_id: u32,
+    item_data: &HashMap<u16, ItemData>,
+) -> Command {
+    return match items.get(slot as usize) {
+        Some(target) if target.can_merge(&item, item_data) => {
+            let mut stack = target.clone();
+            stack.count += item.count;
+            Command::UpdateContainerItem {
+                container: container.location.clone(),
+                slot,
+                item: stack,
+                sender_id,
+            }
+        }
+        _ => Command::AddToContainer {
+            container: container.location.clone(),
+            item,
+            sender_id,
+        },
+    };
+}
+
+// runs the equip or de-equip actions of an item going into or out of an inventory slot,
+// transforms change the item itself so that it is placed in its new form
+fn run_equip_actions(
+    data: &StaticData,
+    (player_id, player_pos): (u32, (u16, u16, u8)),
+    event_type: MoveEventType,
+    slot: u16,
+    item: &mut Item,
+    commands: &mut Vec<Command>,
+) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    for action in data.movements.item_actions(event_type, item).to_vec() {
+        match action {
+            MoveAction::Transform { to_item_id } => {
+                item.client_id = to_item_id;
+            }
+            MoveAction::Teleport { destination } => {
+                commands.push(Command::TeleportCreature {
+                    creature_id: player_id,
+                    to: destination,
+                });
+            }
+            MoveAction::Message { text } => {
+                payload.extend_from_slice(&text_message_payload(
+                    MessageType::InfoDescription as u8,
+                    &text,
+                ));
+            }
+            MoveAction::AddCondition(spec) => {
+                commands.push(Command::AddCondition {
+                    creature_id: player_id,
+                    condition: spec.create(),
+                });
+            }
+            MoveAction::RemoveCondition { condition_type } => {
+                commands.push(Command::RemoveCondition {
+                    creature_id: player_id,
+                    condition_type,
+                });
+            }
+            MoveAction::Script { script } => {
+                commands.push(Command::RunScript {
+                    script,
+                    function: event_type.script_function().to_string(),
+                    args: vec![
+                        creature_arg(player_id, player_pos),
+                        item_arg(item, (0xFFFF, slot, 0), 0),
+                    ],
+                });
+            }
+            // only tiles can block and hold fields
+            MoveAction::Block { .. } | MoveAction::Field => {}
+        }
+    }
+    return payload;
+}
@@ -1,8 +1,32 @@
-use crate::{item::Item, map::Direction};
+use std::{collections::HashMap, time::Instant};
+
+use crate::{
+    condition::{Condition, ConditionType, ConditionView},
+    item::{Item, LOCKER, indefinite_article},
+    map::Direction,
+    message::Cancel,
+    otb_io::item_loader::{ItemData, SlotType},
+    random::random_range,
+    xml_io::{
+        config_loader::DeathConfig,
+        monster_loader::{MAX_LOOT_CHANCE, MonsterType},
+        vocation_loader::{Vocation, promoted_vocation},
+    },
+};
 
 const KNOWN_CREATURE_ID: u16 = 0x62;
 const UNKNOWN_CREATURE_ID: u16 = 0x61;
 
+// what the worn items add up to
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct EquipModifiers {
+    pub armor: u16,
+    pub speed: i32,
+    pub light_level: u8,
+    pub light_color: u8,
+    pub skills: [u16; SKILL_COUNT],
+}
+
 #[derive(Clone)]
 pub struct Inventory {
     pub head: Option<Item>,
@@ -33,6 +57,118 @@ impl Inventory {
         };
     }
 
+    pub fn weight(&self, item_data: &HashMap<u16, ItemData>) -> u32 {
+        let slots = [
+            &self.head,
+            &self.neck,
+            &self.backpack,
+            &self.armor,
+            &self.right,
+            &self.left,
+            &self.legs,
+            &self.feet,
+            &self.ring,
+            &self.ammo,
+        ];
+        return slots
+            .iter()
+            .filter_map(|slot| slot.as_ref())
+            .map(|item| item.weight(item_data))
+            .sum();
+    }
+
+    // only items in the slot they are made for give their modifiers, weapons and shields in hands
+    fn is_worn_in(slot: u16, slot_type: Option<SlotType>) -> bool {
+        let Some(slot_type) = slot_type else {
+            return matches!(slot, 5 | 6 | 10);
+        };
+        return match slot {
+            1 => slot_type == SlotType::Head,
+            2 => slot_type == SlotType::Necklace,
+            3 => slot_type == SlotType::Backpack,
+            4 => slot_type == SlotType::Body,
+            5 | 6 => matches!(slot_type, SlotType::Hand | SlotType::TwoHanded),
+            7 => slot_type == SlotType::Legs,
+            8 => slot_type == SlotType::Feet,
+            9 => slot_type == SlotType::Ring,
+            10 => slot_type == SlotType::Ammo,
+            _ => false,
+        };
+    }
+
+    // the weapon in either hand, None fights with the fists
+    pub fn weapon<'a>(&self, item_data: &'a HashMap<u16, ItemData>) -> Option<&'a ItemData> {
+        return [&self.right, &self.left]
+            .into_iter()
+            .filter_map(|hand| hand.as_ref())
+            .filter_map(|item| item_data.get(&item.client_id))
+            .find(|data| data.weapon_skill.is_some());
+    }
+
+    // why the item cannot be put into the slot
+    pub fn equip_failure(
+        &self,
+        slot: u16,
+        item: &Item,
+        item_data: &HashMap<u16, ItemData>,
+    ) -> Option<Cancel> {
+        let Some(data) = item_data.get(&item.client_id) else {
+            return Some(Cancel::NotPossible);
+        };
+        let other_hand = match slot {
+            5 => self.left.as_ref(),
+            6 => self.right.as_ref(),
+            _ => None,
+        };
+        let holds_two_handed = other_hand.is_some_and(|other| {
+            item_data
+                .get(&other.client_id)
+                .is_some_and(|other| other.slot_type == Some(SlotType::TwoHanded))
+        });
+        let occupied = self
+            .clone()
+            .get_from_slot(slot)
+            .is_some_and(|current| !current.can_merge(item, item_data));
+        if occupied {
+            return Some(Cancel::NotEnoughRoom);
+        }
+        return match slot {
+            5 | 6 if data.slot_type == Some(SlotType::TwoHanded) && other_hand.is_some() => {
+                Some(Cancel::BothHandsNeedFree)
+            }
+            5 | 6 if holds_two_handed => Some(Cancel::DropTwoHandedFirst),
+            5 | 6 => None,
+            10 if data.slot_type != Some(SlotType::TwoHanded) => None,
+            _ if Inventory::is_worn_in(slot, data.slot_type) => None,
+            _ => Some(Cancel::CannotDressThere),
+        };
+    }
+
+    pub fn modifiers(&self, item_data: &HashMap<u16, ItemData>) -> EquipModifiers {
+        let mut modifiers = EquipModifiers::default();
+        for slot in 1..=10 {
+            let Some(data) = self
+                .clone()
+                .get_from_slot(slot)
+                .and_then(|item| item_data.get(&item.client_id))
+                .filter(|data| Inventory::is_worn_in(slot, data.slot_type))
+            else {
+                continue;
+            };
+            modifiers.armor += data.armor;
+            modifiers.speed += data.speed_bonus;
+            // the brightest item lights the player
+            if data.light_level > modifiers.light_level {
+                modifiers.light_level = data.light_level;
+                modifiers.light_color = data.light_color;
+            }
+            for skill in 0..SKILL_COUNT {
+                modifiers.skills[skill] += data.skill_boosts[skill];
+            }
+        }
+        return modifiers;
+    }
+
     pub fn get_from_slot(self, slot: u16) -> Option<Item> {
         let item = match slot {
             1 => self.head,
@@ -43,7 +179,7 @@ impl Inventory {
             6 => self.left,
             7 => self.legs,
             8 => self.feet,
-            9 => self.right,
+            9 => self.ring,
             10 => self.ammo,
             _ => None,
         };
@@ -86,8 +222,8 @@ impl Inventory {
                 self.feet = None;
             }
             9 => {
-                removed = self.right.clone();
-                self.right = None;
+                removed = self.ring.clone();
+                self.ring = None;
             }
             10 => {
                 removed = self.ammo.clone();
@@ -124,7 +260,7 @@ impl Inventory {
                 self.feet = Some(item);
             }
             9 => {
-                self.right = Some(item);
+                self.ring = Some(item);
             }
             10 => {
                 self.ammo = Some(item);
@@ -134,6 +270,58 @@ impl Inventory {
     }
 }
 
+// fist, club, sword, axe, distance, shielding, fishing
+pub const SKILL_COUNT: usize = 7;
+pub const SKILL_FIST: usize = 0;
+pub const SKILL_DISTANCE: usize = 4;
+pub const SKILL_SHIELDING: usize = 5;
+pub const SKILL_FISHING: usize = 6;
+// how the advance messages name the skills
+pub const SKILL_TITLES: [&str; SKILL_COUNT] = [
+    "fist fighting",
+    "club fighting",
+    "sword fighting",
+    "axe fighting",
+    "distance fighting",
+    "shielding",
+    "fishing",
+];
+// the attack of bare hands
+pub const FIST_ATTACK: u16 = 7;
+// tries needed for the first skill advance, scaled by the vocation multiplier afterwards
+pub const SKILL_BASE_TRIES: [u32; SKILL_COUNT] = [50, 50, 50, 50, 30, 100, 20];
+pub const MAGIC_LEVEL_BASE_MANA: u32 = 1600;
+pub const MIN_SKILL_LEVEL: u16 = 10;
+
+#[derive(Clone, Copy)]
+pub struct Skill {
+    pub level: u16,
+    pub tries: u32,
+}
+
+impl Skill {
+    pub fn new() -> Skill {
+        return Skill {
+            level: MIN_SKILL_LEVEL,
+            tries: 0,
+        };
+    }
+}
+
+// level 0 does not exist and needs no experience, like level 1
+pub fn experience_for_level(level: u16) -> u32 {
+    let level = (level as u64).saturating_sub(1);
+    return ((50 * level * level * level + 400 * level - 150 * level * level) / 3) as u32;
+}
+
+pub fn level_for_experience(experience: u32) -> u16 {
+    let mut level = 1;
+    while experience_for_level(level + 1) <= experience {
+        level += 1;
+    }
+    return level;
+}
+
 #[derive(Clone)]
 pub struct Character {
     pub id: u32,
@@ -143,11 +331,28 @@ pub struct Character {
     // head, body, legs, feet
     pub outfit: [u8; 4],
     pub position: (u16, u16, u8),
+    // current speed including conditions, used for walk timing
     pub speed: u16,
+    pub base_speed: u16,
     pub look_dir: Direction,
     pub health: u16,
     pub max_health: u16,
     pub inventory: Inventory,
+    pub level: u16,
+    pub experience: u32,
+    pub skills: [Skill; SKILL_COUNT],
+    pub town_id: u32,
+    pub vocation: u8,
+    pub mana: u16,
+    pub max_mana: u16,
+    pub soul: u8,
+    pub capacity: u16,
+    pub magic_level: u8,
+    pub mana_spent: u32,
+    // quest and script values, saved with the player
+    pub storage: HashMap<u32, i32>,
+    // the depot locker of each town by town id
+    pub depots: HashMap<u32, Item>,
 }
 
 impl Character {
@@ -159,14 +364,166 @@ impl Character {
             outfit: self.outfit,
             is_known: true,
             health: self.health,
-            max_health: self.health,
+            max_health: self.max_health,
             look_dir: self.look_dir.clone(),
             light_level: 0x64,
             light_color: 0xD7,
-            speed: self.speed,
+            speed: self.base_speed,
             shield: 0,
+            monster_type: None,
+            base_speed: self.base_speed,
+            base_light_level: 0x64,
+            base_light_color: 0xD7,
+            conditions: vec![],
+            storage: self.storage.clone(),
+            level: self.level,
+            vocation: self.vocation,
+            equipment: EquipModifiers::default(),
         };
     }
+
+    // the locker of the town, created empty the first time the player uses a depot there
+    pub fn locker(&mut self, town_id: u32) -> &mut Item {
+        return self
+            .depots
+            .entry(town_id)
+            .or_insert_with(|| Item::new(LOCKER));
+    }
+
+    // capacity left after the inventory, in hundredths of an ounce like item weights
+    pub fn free_capacity(&self, item_data: &HashMap<u16, ItemData>) -> u32 {
+        return (self.capacity as u32 * 100).saturating_sub(self.inventory.weight(item_data));
+    }
+
+    pub fn level_percent(&self) -> u8 {
+        let current = experience_for_level(self.level);
+        let next = experience_for_level(self.level + 1);
+        return ((self.experience - current) as u64 * 100 / (next - current) as u64) as u8;
+    }
+
+    pub fn magic_level_percent(&self, vocation: &Vocation) -> u8 {
+        let needed = vocation.mana_for_magic_level(self.magic_level).max(1);
+        return (self.mana_spent as u64 * 100 / needed as u64).min(100) as u8;
+    }
+
+    pub fn skill_percent(&self, skill: usize, vocation: &Vocation) -> u8 {
+        let needed = vocation
+            .skill_tries_for_level(skill, self.skills[skill].level)
+            .max(1);
+        return (self.skills[skill].tries as u64 * 100 / needed as u64).min(100) as u8;
+    }
+
+    // returns true when the level changed
+    pub fn add_experience(&mut self, experience: u32, vocation: &Vocation) -> bool {
+        self.experience = self.experience.saturating_add(experience);
+        return self.update_level(vocation);
+    }
+
+    fn update_level(&mut self, vocation: &Vocation) -> bool {
+        let new_level = level_for_experience(self.experience);
+        if new_level == self.level {
+            return false;
+        }
+        let levels = new_level as i32 - self.level as i32;
+        self.max_health =
+            (self.max_health as i32 + levels * vocation.gain_health as i32).max(1) as u16;
+        self.max_mana = (self.max_mana as i32 + levels * vocation.gain_mana as i32).max(0) as u16;
+        self.capacity =
+            (self.capacity as i32 + levels * vocation.gain_capacity as i32).max(0) as u16;
+        self.health = self.health.min(self.max_health);
+        self.mana = self.mana.min(self.max_mana);
+        self.level = new_level;
+        return true;
+    }
+
+    // returns true when the skill advanced
+    pub fn add_skill_tries(&mut self, skill: usize, tries: u32, vocation: &Vocation) -> bool {
+        let mut advanced = false;
+        let skill_data = &mut self.skills[skill];
+        skill_data.tries += tries;
+        loop {
+            let needed = vocation.skill_tries_for_level(skill, skill_data.level);
+            if skill_data.tries < needed {
+                break;
+            }
+            skill_data.tries -= needed;
+            skill_data.level += 1;
+            advanced = true;
+        }
+        return advanced;
+    }
+
+    // returns true when the magic level advanced
+    pub fn add_mana_spent(&mut self, mana: u32, vocation: &Vocation) -> bool {
+        let mut advanced = false;
+        self.mana_spent += mana;
+        loop {
+            let needed = vocation.mana_for_magic_level(self.magic_level);
+            if self.mana_spent < needed {
+                break;
+            }
+            self.mana_spent -= needed;
+            self.magic_level += 1;
+            advanced = true;
+        }
+        return advanced;
+    }
+
+    // returns true when the data defines a promotion for the current vocation
+    pub fn promote(&mut self, vocations: &HashMap<u8, Vocation>) -> bool {
+        if let Some(vocation_id) = promoted_vocation(vocations, self.vocation) {
+            self.vocation = vocation_id;
+            return true;
+        }
+        return false;
+    }
+
+    pub fn apply_death_penalty(&mut self, config: &DeathConfig, vocation: &Vocation) {
+        let lost_experience = (self.experience as u64 * config.experience_loss as u64 / 100) as u32;
+        self.experience -= lost_experience;
+        self.update_level(vocation);
+        // losses are a percentage of everything trained so far, so levels can be lost too
+        for skill in 0..SKILL_COUNT {
+            let mut total_tries = self.skills[skill].tries as u64;
+            for level in MIN_SKILL_LEVEL..self.skills[skill].level {
+                total_tries += vocation.skill_tries_for_level(skill, level) as u64;
+            }
+            total_tries -= total_tries * config.skill_loss as u64 / 100;
+            let mut remaining = Skill::new();
+            loop {
+                let needed = vocation.skill_tries_for_level(skill, remaining.level) as u64;
+                if total_tries < needed {
+                    break;
+                }
+                total_tries -= needed;
+                remaining.level += 1;
+            }
+            remaining.tries = total_tries as u32;
+            self.skills[skill] = remaining;
+        }
+        self.mana_spent -= (self.mana_spent as u64 * config.skill_loss as u64 / 100) as u32;
+        self.health = self.max_health;
+        self.mana = self.max_mana;
+    }
+
+    // removes the items lost on death from the inventory and returns them
+    pub fn drop_items_on_death(&mut self, config: &DeathConfig) -> Vec<(u16, Item)> {
+        let mut dropped: Vec<(u16, Item)> = Vec::new();
+        for slot in 1..=10 {
+            let chance = if slot == 3 {
+                config.container_drop_chance
+            } else {
+                config.item_drop_chance
+            };
+            if random_range(1, 100) > chance {
+                continue;
+            }
+            if let Some(item) = self.inventory.remove_from_slot(slot) {
+                dropped.push((slot, item));
+            }
+        }
+        return dropped;
+    }
 }
 
 #[derive(Clone)]
@@ -183,6 +540,53 @@ pub struct Creature {
     pub light_color: u8,
     pub speed: u16,
     pub shield: u8,
+    // None for players
+    pub monster_type: Option<String>,
+    // speed and light without any conditions
+    pub base_speed: u16,
+    pub base_light_level: u8,
+    pub base_light_color: u8,
+    pub conditions: Vec<Condition>,
+    // copy of the player storage so the world can read it, empty for monsters
+    pub storage: HashMap<u32, i32>,
+    // copied from the character for looking at players, 0 for monsters
+    pub level: u16,
+    pub vocation: u8,
+    // what the worn items of a player add to the base values
+    pub equipment: EquipModifiers,
+}
+
+// the "You see ..." text of a creature, players show their level and vocation
+pub fn describe_creature(
+    creature: &Creature,
+    vocations: &HashMap<u8, Vocation>,
+    is_self: bool,
+) -> String {
+    if creature.monster_type.is_some() {
+        let name = creature.name.to_lowercase();
+        return format!("You see {} {}.", indefinite_article(&name), name);
+    }
+    let vocation = vocations
+        .get(&creature.vocation)
+        .filter(|vocation| vocation.id != 0)
+        .map(|vocation| vocation.description.clone());
+    if is_self {
+        return match vocation {
+            Some(vocation) => format!("You see yourself. You are {}.", vocation),
+            None => "You see yourself. You have no vocation.".to_string(),
+        };
+    }
+    let name = &creature.name;
+    return match vocation {
+        Some(vocation) => format!(
+            "You see {} (Level {}). {} is {}.",
+            name, creature.level, name, vocation
+        ),
+        None => format!(
+            "You see {} (Level {}). {} has no vocation.",
+            name, creature.level, name
+        ),
+    };
 }
 
 pub fn str_fmt(s: &str) -> Vec<u8> {
@@ -195,6 +599,124 @@ pub fn str_fmt(s: &str) -> Vec<u8> {
 }
 
 impl Creature {
+    pub fn health_percent(&self) -> u8 {
+        if self.max_health == 0 {
+            return 0;
+        }
+        return (self.health as u32 * 100 / self.max_health as u32) as u8;
+    }
+
+    pub fn outfit_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        if self.has_condition(&ConditionType::Invisible) {
+            // no outfit and no item look
+            bytes.extend_from_slice(&0u16.to_le_bytes());
+            bytes.extend_from_slice(&0u16.to_le_bytes());
+            return bytes;
+        }
+        bytes.extend_from_slice(&self.outfit_type.to_le_bytes());
+        bytes.extend_from_slice(&self.outfit);
+        return bytes;
+    }
+
+    pub fn has_condition(&self, condition_type: &ConditionType) -> bool {
+        return self
+            .conditions
+            .iter()
+            .any(|condition| condition.condition_type == *condition_type);
+    }
+
+    pub fn add_condition(&mut self, condition: Condition) {
+        // haste and paralyze cancel each other out
+        let opposite = match condition.condition_type {
+            ConditionType::Haste => Some(ConditionType::Paralyze),
+            ConditionType::Paralyze => Some(ConditionType::Haste),
+            _ => None,
+        };
+        self.conditions.retain(|current| {
+            current.condition_type != condition.condition_type
+                && Some(&current.condition_type) != opposite.as_ref()
+        });
+        self.conditions.push(condition);
+        self.update_conditions();
+    }
+
+    pub fn remove_condition(&mut self, condition_type: &ConditionType) {
+        self.conditions
+            .retain(|condition| condition.condition_type != *condition_type);
+        self.update_conditions();
+    }
+
+    // returns the damage ticks that are due as (damage, attacker, magic effect), drops expired ones
+    pub fn tick_conditions(&mut self, now: Instant) -> Vec<(u16, Option<u32>, u8)> {
+        let mut hits: Vec<(u16, Option<u32>, u8)> = Vec::new();
+        for condition in self.conditions.iter_mut() {
+            if condition.damage == 0 {
+                continue;
+            }
+            let interval = condition.condition_type.tick_interval();
+            while condition.next_tick_at <= now && condition.next_tick_at <= condition.ends_at {
+                hits.push((
+                    condition.damage,
+                    condition.attacker_id,
+                    condition.condition_type.magic_effect(),
+                ));
+                condition.next_tick_at += interval;
+            }
+        }
+        let count = self.conditions.len();
+        self.conditions.retain(|condition| condition.ends_at > now);
+        if self.conditions.len() != count {
+            self.update_conditions();
+        }
+        return hits;
+    }
+
+    pub fn set_equipment(&mut self, equipment: EquipModifiers) {
+        self.equipment = equipment;
+        self.update_conditions();
+    }
+
+    fn update_conditions(&mut self) {
+        let speed_change: i32 = self
+            .conditions
+            .iter()
+            .map(|condition| condition.speed_change)
+            .sum();
+        self.speed = (self.base_speed as i32 + self.equipment.speed + speed_change)
+            .clamp(0, u16::MAX as i32) as u16;
+        match self
+            .conditions
+            .iter()
+            .find(|condition| condition.condition_type == ConditionType::Light)
+        {
+            Some(light) => {
+                self.light_level = light.light_level;
+                self.light_color = light.light_color;
+            }
+            None if self.equipment.light_level > 0 => {
+                self.light_level = self.equipment.light_level;
+                self.light_color = self.equipment.light_color;
+            }
+            None => {
+                self.light_level = self.base_light_level;
+                self.light_color = self.base_light_color;
+            }
+        }
+    }
+
+    pub fn condition_view(&self) -> ConditionView {
+        return ConditionView {
+            speed: self.speed,
+            light_level: self.light_level,
+            light_color: self.light_color,
+            icons: self.conditions.iter().fold(0, |icons, condition| {
+                icons | condition.condition_type.icon()
+            }),
+            invisible: self.has_condition(&ConditionType::Invisible),
+        };
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut bytes: Vec<u8> = Vec::new();
         // todo handle known creature
@@ -203,10 +725,9 @@ impl Creature {
         bytes.extend_from_slice(&((self.id + 10) as u32).to_le_bytes()); // idk
         bytes.extend_from_slice(&self.id.to_le_bytes());
         bytes.extend_from_slice(&str_fmt(&self.name));
-        bytes.push(((self.health / self.max_health) * 100) as u8);
+        bytes.push(self.health_percent());
         bytes.push(self.look_dir.clone() as u8);
-        bytes.extend_from_slice(&self.outfit_type.to_le_bytes());
-        bytes.extend_from_slice(&self.outfit);
+        bytes.extend_from_slice(&self.outfit_bytes());
         bytes.push(self.light_color);
         bytes.push(self.light_level);
         bytes.extend_from_slice(&self.speed.to_le_bytes());
@@ -215,6 +736,42 @@ impl Creature {
     }
 }
 
+pub fn generate_loot(monster_type: &MonsterType) -> Vec<Item> {
+    let mut loot: Vec<Item> = Vec::new();
+    for loot_item in monster_type.loot.iter() {
+        if random_range(1, MAX_LOOT_CHANCE) <= loot_item.chance {
+            loot.push(Item::new(loot_item.client_id));
+        }
+    }
+    return loot;
+}
+
+pub fn create_monster(id: u32, monster_type: &MonsterType) -> Creature {
+    return Creature {
+        id,
+        name: monster_type.name.clone(),
+        outfit_type: monster_type.outfit_type,
+        outfit: [0, 0, 0, 0],
+        is_known: false,
+        health: monster_type.health,
+        max_health: monster_type.health,
+        look_dir: Direction::South,
+        light_level: 0,
+        light_color: 0,
+        speed: monster_type.speed,
+        shield: 0,
+        monster_type: Some(monster_type.name.to_lowercase()),
+        base_speed: monster_type.speed,
+        base_light_level: 0,
+        base_light_color: 0,
+        conditions: vec![],
+        storage: HashMap::new(),
+        level: 0,
+        vocation: 0,
+        equipment: EquipModifiers::default(),
+    };
+}
+
 pub fn create_characters() -> Vec<Character> {
     let mut characters: Vec<Character> = Vec::new();
     characters.push(Character {
@@ -222,26 +779,224 @@ pub fn create_characters() -> Vec<Character> {
         name: "Some Character".to_string(),
         outfit_type: 128,
         outfit: [80, 80, 80, 80],
-        health: 100,
-        max_health: 100,
+        health: 185,
+        max_health: 185,
         look_dir: Direction::South,
         speed: 220,
+        base_speed: 220,
         world: "World".to_string(),
         position: (1024, 1024, 7),
         inventory: Inventory::new_empty(),
+        level: 8,
+        experience: experience_for_level(8),
+        skills: [Skill::new(); SKILL_COUNT],
+        town_id: 1,
+        vocation: 4,
+        mana: 35,
+        max_mana: 35,
+        soul: 100,
+        capacity: 470,
+        magic_level: 0,
+        mana_spent: 0,
+        storage: HashMap::new(),
+        depots: HashMap::new(),
     });
     characters.push(Character {
         id: 2,
         name: "Another Character".to_string(),
         outfit_type: 128,
         outfit: [30, 30, 30, 30],
-        health: 100,
-        max_health: 100,
+        health: 185,
+        max_health: 185,
         look_dir: Direction::South,
         speed: 220,
+        base_speed: 220,
         world: "World".to_string(),
         position: (1024, 1026, 7),
         inventory: Inventory::new_empty(),
+        level: 8,
+        experience: experience_for_level(8),
+        skills: [Skill::new(); SKILL_COUNT],
+        town_id: 1,
+        vocation: 1,
+        mana: 35,
+        max_mana: 35,
+        soul: 100,
+        capacity: 470,
+        magic_level: 0,
+        mana_spent: 0,
+        storage: HashMap::new(),
+        depots: HashMap::new(),
     });
     return characters;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{condition::ICON_PARALYZE, otb_io::item_loader::ItemType};
+    use std::time::Duration;
+
+    const SWORD_ID: u16 = 3264;
+    const AXE_ID: u16 = 3303;
+    const SHIELD_ID: u16 = 3409;
+    const HELMET_ID: u16 = 3351;
+    const RING_ID: u16 = 3049;
+    const TORCH_ID: u16 = 2920;
+
+    fn test_item_data() -> HashMap<u16, ItemData> {
+        let mut item_data = HashMap::new();
+        let slot_types = [
+            (SWORD_ID, Some(SlotType::Hand)),
+            (AXE_ID, Some(SlotType::TwoHanded)),
+            (SHIELD_ID, Some(SlotType::Hand)),
+            (HELMET_ID, Some(SlotType::Head)),
+            (RING_ID, Some(SlotType::Ring)),
+            (TORCH_ID, None),
+        ];
+        for (client_id, slot_type) in slot_types {
+            let mut data = ItemData::new(client_id, ItemType::Nothing, 0);
+            data.slot_type = slot_type;
+            item_data.insert(client_id, data);
+        }
+        return item_data;
+    }
+
+    #[test]
+    fn experience_for_level_starts_at_zero() {
+        assert_eq!(experience_for_level(0), 0);
+        assert_eq!(experience_for_level(1), 0);
+        assert_eq!(experience_for_level(2), 100);
+        assert_eq!(experience_for_level(8), 4200);
+    }
+
+    #[test]
+    fn level_for_experience_rounds_down() {
+        assert_eq!(level_for_experience(0), 1);
+        assert_eq!(level_for_experience(99), 1);
+        assert_eq!(level_for_experience(100), 2);
+        assert_eq!(level_for_experience(4199), 7);
+        assert_eq!(level_for_experience(4200), 8);
+    }
+
+    #[test]
+    fn equip_failure_keeps_two_handed_weapons_alone_in_the_hands() {
+        let item_data = test_item_data();
+        let mut inventory = Inventory::new_empty();
+        inventory.equip(5, Item::new(SWORD_ID));
+        let axe = Item::new(AXE_ID);
+        assert!(inventory.equip_failure(6, &axe, &item_data) == Some(Cancel::BothHandsNeedFree));
+        let mut inventory = Inventory::new_empty();
+        inventory.equip(5, Item::new(AXE_ID));
+        let shield = Item::new(SHIELD_ID);
+        assert!(
+            inventory.equip_failure(6, &shield, &item_data) == Some(Cancel::DropTwoHandedFirst)
+        );
+        assert!(inventory.equip_failure(5, &shield, &item_data) == Some(Cancel::NotEnoughRoom));
+        assert!(
+            inventory
+                .equip_failure(1, &Item::new(HELMET_ID), &item_data)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn equip_failure_checks_the_slot_type() {
+        let item_data = test_item_data();
+        let inventory = Inventory::new_empty();
+        let helmet = Item::new(HELMET_ID);
+        assert!(inventory.equip_failure(1, &helmet, &item_data).is_none());
+        assert!(inventory.equip_failure(8, &helmet, &item_data) == Some(Cancel::CannotDressThere));
+        assert!(
+            inventory
+                .equip_failure(9, &Item::new(RING_ID), &item_data)
+                .is_none()
+        );
+        assert!(
+            inventory.equip_failure(10, &Item::new(AXE_ID), &item_data)
+                == Some(Cancel::CannotDressThere)
+        );
+        // items without a slot type are only held
+        let torch = Item::new(TORCH_ID);
+        assert!(inventory.equip_failure(5, &torch, &item_data).is_none());
+        assert!(inventory.equip_failure(10, &torch, &item_data).is_none());
+        assert!(inventory.equip_failure(1, &torch, &item_data) == Some(Cancel::CannotDressThere));
+        assert!(inventory.equip_failure(9, &torch, &item_data) == Some(Cancel::CannotDressThere));
+    }
+
+    #[test]
+    fn modifiers_count_items_worn_in_their_slot() {
+        let mut item_data = test_item_data();
+        let helmet = item_data.get_mut(&HELMET_ID).unwrap();
+        helmet.armor = 2;
+        let ring = item_data.get_mut(&RING_ID).unwrap();
+        ring.speed_bonus = 20;
+        ring.skill_boosts[SKILL_FIST] = 3;
+        ring.light_level = 2;
+        ring.light_color = 215;
+        let mut inventory = Inventory::new_empty();
+        inventory.equip(1, Item::new(HELMET_ID));
+        inventory.equip(9, Item::new(RING_ID));
+        let modifiers = inventory.modifiers(&item_data);
+        assert_eq!(modifiers.armor, 2);
+        assert_eq!(modifiers.speed, 20);
+        assert_eq!(modifiers.skills[SKILL_FIST], 3);
+        assert_eq!((modifiers.light_level, modifiers.light_color), (2, 215));
+        // a helmet carried in the ammo slot is not worn
+        let mut inventory = Inventory::new_empty();
+        inventory.ammo = Some(Item::new(HELMET_ID));
+        assert_eq!(inventory.modifiers(&item_data).armor, 0);
+    }
+
+    #[test]
+    fn haste_and_paralyze_replace_each_other() {
+        let mut creature = create_characters().remove(0).as_creature();
+        let duration = Duration::from_secs(10);
+        creature.add_condition(Condition::speed(ConditionType::Haste, 60, duration));
+        assert_eq!(creature.speed, 280);
+        creature.add_condition(Condition::speed(ConditionType::Paralyze, -100, duration));
+        assert_eq!(creature.speed, 120);
+        assert!(!creature.has_condition(&ConditionType::Haste));
+        assert_eq!(creature.condition_view().icons, ICON_PARALYZE);
+        creature.remove_condition(&ConditionType::Paralyze);
+        assert_eq!(creature.speed, 220);
+        assert_eq!(creature.condition_view().icons, 0);
+    }
+
+    #[test]
+    fn tick_conditions_hits_once_per_interval_until_it_ends() {
+        let mut creature = create_characters().remove(0).as_creature();
+        let poison = Condition::damage(ConditionType::Poison, 5, 3, Some(7));
+        let started_at = poison.next_tick_at - ConditionType::Poison.tick_interval();
+        creature.add_condition(poison);
+        assert!(creature.tick_conditions(started_at).is_empty());
+        let hits = creature.tick_conditions(started_at + Duration::from_secs(4));
+        assert_eq!(hits, vec![(5, Some(7), 16), (5, Some(7), 16)]);
+        let hits = creature.tick_conditions(started_at + Duration::from_secs(6));
+        assert_eq!(hits.len(), 1);
+        assert!(!creature.has_condition(&ConditionType::Poison));
+    }
+
+    #[test]
+    fn free_capacity_subtracts_the_inventory_weight() {
+        let mut item_data = test_item_data();
+        item_data.get_mut(&HELMET_ID).unwrap().weight = 4600;
+        let mut character = create_characters().remove(0);
+        assert_eq!(character.free_capacity(&item_data), 47000);
+        character.inventory.equip(1, Item::new(HELMET_ID));
+        assert_eq!(character.free_capacity(&item_data), 42400);
+        character.capacity = 40;
+        assert_eq!(character.free_capacity(&item_data), 0);
+    }
+
+    #[test]
+    fn locker_is_kept_per_town() {
+        let mut character = create_characters().remove(0);
+        assert!(character.depots.is_empty());
+        character.locker(1).add_item(Item::new(HELMET_ID));
+        assert_eq!(character.locker(1).client_id, LOCKER);
+        assert_eq!(character.locker(1).items.len(), 1);
+        assert!(character.locker(2).items.is_empty());
+        assert_eq!(character.depots.len(), 2);
+    }
+}
@@ -1,20 +1,67 @@
 use crate::{
     StaticData,
-    connection::{Container, State},
-    creature::Creature,
+    condition::{Condition, ConditionType, DRUNK_STAGGER_CHANCE},
+    connection::{ContainerRef, FIRST_MONSTER_ID, State},
+    creature::{
+        Character, Creature, EquipModifiers, SKILL_DISTANCE, SKILL_FISHING, SKILL_SHIELDING,
+        create_monster, describe_creature, generate_loot,
+    },
+    fluid::{
+        DRUNK_DURATION, FluidType, LIFE_FLUID_MAX, LIFE_FLUID_MIN, MANA_FLUID_MAX, MANA_FLUID_MIN,
+        SLIME_POISON_DAMAGE, SLIME_POISON_TICKS, SPLASH,
+    },
+    house::{HouseSave, house_save, house_saves, rent_paid_until, unix_time, write_house_saves},
     item::Item,
-    item::get_item_action,
-    map::Direction,
-    otb_io::item_loader::ItemType,
+    item::{DEFAULT_MAX_TEXT_LENGTH, container_capacity, describe_item},
+    map::{Direction, can_see, is_sight_clear},
+    message::Cancel,
+    otb_io::item_loader::{
+        FLAG_ALLOW_DISTANCE_READ, FLAG_BLOCK_SOLID, FLAG_READABLE, ItemData, ItemType,
+    },
+    payload::{MagicEffect, MessageType, SpeechType},
+    random::random_range,
+    script::{call_script, creature_arg, item_arg},
+    xml_io::{
+        action_loader::{DoorGate, ItemAction},
+        house_loader::HouseList,
+        items_loader::FieldEffect,
+        movement_loader::{MoveAction, MoveEventType},
+        player_loader::{PLAYERS_DIRECTORY, read_player, save_player},
+        spell_loader::{Rune, SpellEffect},
+        talkaction_loader::find_talkaction,
+    },
 };
+use rhai::Dynamic;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{
         Arc, Mutex,
         mpsc::{Receiver, Sender},
     },
+    time::{Duration, Instant},
 };
 
+const CONDITION_TICK: Duration = Duration::from_millis(500);
+// how often houses are saved and checked for rent that is due
+const RENT_CHECK: Duration = Duration::from_secs(10 * 60);
+// how many tiles away distance weapons hit
+const DISTANCE_RANGE: u16 = 7;
+
+// connection id -> (player position, event sender)
+pub type Connections = HashMap<u32, ((u16, u16, u8), Sender<ServerEvent>)>;
+
+// a ground container players have open
+pub struct ContainerView {
+    // the tile it lies on or inside a container on
+    pub pos: (u16, u16, u8),
+    // the instance id of the container it is in, 0 when it lies on the tile itself
+    pub parent_id: u32,
+    pub viewers: HashSet<u32>,
+}
+
+// instance id -> who is viewing the container
+pub type ContainerViews = HashMap<u32, ContainerView>;
+
 #[derive(Clone)]
 pub enum Command {
     PutCreature {
@@ -56,38 +103,175 @@ pub enum Command {
         stack_pos: u8,
         item: Item,
         index: u8,
+        // the container the item lies in when it is used from a container window
+        window: Option<ContainerRef>,
     },
     UseItemOnTarget {
+        sender_id: u32,
         pos: (u16, u16, u8),
         stack_pos: u8,
         item: Item,
         target_pos: (u16, u16, u8),
         target_stack_pos: u8,
     },
+    UseItemOnCreature {
+        sender_id: u32,
+        pos: (u16, u16, u8),
+        stack_pos: u8,
+        item: Item,
+        creature_id: u32,
+    },
+    Attack {
+        attacker_id: u32,
+        target_id: u32,
+        skill: usize,
+        skill_level: u16,
+        attack: u16,
+        shoot_effect: Option<u8>,
+    },
+    UpdateContainerItem {
+        container: ContainerRef,
+        slot: u8,
+        item: Item,
+        sender_id: u32,
+    },
+    TeleportCreature {
+        creature_id: u32,
+        to: (u16, u16, u8),
+    },
+    DecayItem {
+        pos: (u16, u16, u8),
+        client_id: u16,
+        instance_id: u32,
+    },
     AddToContainer {
+        container: ContainerRef,
         item: Item,
         sender_id: u32,
-        slot: u8,
-        container: Container,
     },
     RemoveItemFromContainer {
-        pos: (u16, u16, u8),
-        stack_pos: u8,
+        container: ContainerRef,
         slot: u8,
         sender_id: u32,
     },
+    // the player closed the last window of a ground container
+    CloseContainer {
+        sender_id: u32,
+        instance_id: u32,
+    },
     TransformThing {
         pos: (u16, u16, u8),
         stack_pos: u8,
         from_item_id: u16,
         to_item_id: u16,
     },
+    WriteText {
+        pos: (u16, u16, u8),
+        stack_pos: u8,
+        item_id: u16,
+        text: String,
+        writer: String,
+    },
     CreatureSpeech {
+        sender_id: u32,
         pos: (u16, u16, u8),
         text: String,
         creature_name: String,
         speech_type: u8,
     },
+    ChangeHealth {
+        pos: (u16, u16, u8),
+        creature_id: u32,
+        amount: i32,
+        attacker_id: Option<u32>,
+    },
+    SetMaxHealth {
+        pos: (u16, u16, u8),
+        creature_id: u32,
+        max_health: u16,
+    },
+    PlaceCorpse {
+        pos: (u16, u16, u8),
+        corpse_id: u16,
+        items: Vec<Item>,
+    },
+    CastSpell {
+        caster_id: u32,
+        spell_name: String,
+        param: String,
+    },
+    AddCondition {
+        creature_id: u32,
+        condition: Condition,
+    },
+    RemoveCondition {
+        creature_id: u32,
+        condition_type: ConditionType,
+    },
+    ProcessConditions,
+    CreateItem {
+        pos: (u16, u16, u8),
+        item_id: u16,
+    },
+    RemoveItem {
+        pos: (u16, u16, u8),
+        item_id: u16,
+    },
+    RelocateItem {
+        from: (u16, u16, u8),
+        to: (u16, u16, u8),
+        item_id: u16,
+    },
+    SendMessage {
+        creature_id: u32,
+        text: String,
+    },
+    ShowMagicEffect {
+        pos: (u16, u16, u8),
+        effect: u8,
+    },
+    PromotePlayer {
+        creature_id: u32,
+    },
+    SetStorage {
+        creature_id: u32,
+        key: u32,
+        value: i32,
+    },
+    SetPlayerLevel {
+        creature_id: u32,
+        level: u16,
+        vocation: u8,
+    },
+    SetEquipment {
+        creature_id: u32,
+        equipment: EquipModifiers,
+    },
+    LookAt {
+        sender_id: u32,
+        pos: (u16, u16, u8),
+        stack_pos: u8,
+    },
+    CollectRent,
+    RentPaid {
+        house_id: u32,
+        paid: bool,
+    },
+    SetHouseOwner {
+        house_id: u32,
+        owner: String,
+    },
+    EditHouseList {
+        house_id: u32,
+        list: String,
+        name: String,
+        add: bool,
+    },
+    RunScript {
+        script: String,
+        function: String,
+        args: Vec<Dynamic>,
+    },
 }
 
 #[derive(Clone)]
@@ -107,16 +291,9 @@ pub enum ServerEvent {
         pos: (u16, u16, u8),
         stack_pos: u8,
     },
-    ItemMoved {
-        from: (u16, u16, u8),
-        to: (u16, u16, u8),
-        stack_pos: u8,
-        item_id: u16,
-    },
     EnterGame,
     OpenContainer {
-        pos: (u16, u16, u8),
-        stack_pos: u8,
+        container: ContainerRef,
         index: u8,
         item: Item,
         name: String,
@@ -124,19 +301,27 @@ pub enum ServerEvent {
         capacity: u8,
     },
     AddedToContainer {
-        pos: (u16, u16, u8),
-        stack_pos: u8,
+        container: ContainerRef,
+        item: Item,
+    },
+    ContainerItemUpdated {
+        container: ContainerRef,
+        slot: u8,
         item: Item,
     },
     RemovedFromContainer {
-        pos: (u16, u16, u8),
-        stack_pos: u8,
+        container: ContainerRef,
         slot: u8,
     },
+    // the viewer walked away from the ground container or it was moved
+    ContainerClosed {
+        instance_id: u32,
+    },
+    // the item now at the stack position, also sent when only its count changed
     ThingTransformed {
         pos: (u16, u16, u8),
         stack_pos: u8,
-        to_item_id: u16,
+        item: Item,
     },
     CreatureSpoke {
         pos: (u16, u16, u8),
@@ -150,6 +335,109 @@ pub enum ServerEvent {
         direction: Direction,
         creature_id: u32,
     },
+    CreatureHealthChanged {
+        creature_id: u32,
+        health: u16,
+        max_health: u16,
+    },
+    ItemAdded {
+        pos: (u16, u16, u8),
+        item: Item,
+    },
+    PlayerDied {
+        pos: (u16, u16, u8),
+    },
+    ExperienceGained {
+        experience: u32,
+    },
+    MagicEffect {
+        pos: (u16, u16, u8),
+        effect: u8,
+    },
+    CreatureSpeedChanged {
+        creature_id: u32,
+        speed: u16,
+    },
+    CreatureLightChanged {
+        creature_id: u32,
+        light_level: u8,
+        light_color: u8,
+    },
+    TextMessage {
+        message_type: u8,
+        text: String,
+    },
+    DistanceEffect {
+        from: (u16, u16, u8),
+        to: (u16, u16, u8),
+        effect: u8,
+    },
+    ItemSubtypeChanged {
+        pos: (u16, u16, u8),
+        subtype: u8,
+    },
+    ManaGained {
+        amount: u16,
+    },
+    ItemChargeUsed {
+        pos: (u16, u16, u8),
+        default_charges: u8,
+    },
+    WalkCancelled {
+        direction: Direction,
+    },
+    TextWindow {
+        pos: (u16, u16, u8),
+        stack_pos: u8,
+        item_id: u16,
+        text: String,
+        max_length: u16,
+        writeable: bool,
+    },
+    Promoted,
+    StorageChanged {
+        key: u32,
+        value: i32,
+    },
+    LockerOpened {
+        pos: (u16, u16, u8),
+        town_id: u32,
+        index: u8,
+    },
+    ChestOpened {
+        reward_id: u16,
+        storage_key: u32,
+    },
+    RentDue {
+        house_id: u32,
+        town_id: u32,
+        rent: u32,
+    },
+    GatedDoorUsed {
+        pos: (u16, u16, u8),
+        stack_pos: u8,
+        door: Item,
+        open_id: u16,
+        gate: DoorGate,
+    },
+    ItemRemoved {
+        pos: (u16, u16, u8),
+        stack_pos: u8,
+    },
+    Teleported {
+        pos: (u16, u16, u8),
+    },
+    IconsChanged {
+        icons: u8,
+    },
+    SkillTried {
+        skill: usize,
+    },
+    TargetLost,
+    CreatureOutfitChanged {
+        creature_id: u32,
+        outfit: Vec<u8>,
+    },
 }
 
 pub async fn event_handler(
@@ -158,8 +446,30 @@ pub async fn event_handler(
     state: Arc<Mutex<State>>,
     data: Arc<StaticData>,
 ) {
-    let mut connections: HashMap<u32, ((u16, u16, u8), Sender<ServerEvent>)> = HashMap::new();
+    let mut connections: Connections = HashMap::new();
+    // creatures that may still have conditions to tick
+    let mut conditioned: HashSet<u32> = HashSet::new();
+    let mut container_views: ContainerViews = HashMap::new();
+    let tick_tx = loopback_tx.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(CONDITION_TICK).await;
+            if tick_tx.send(Command::ProcessConditions).is_err() {
+                break;
+            }
+        }
+    });
+    let rent_tx = loopback_tx.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(RENT_CHECK).await;
+            if rent_tx.send(Command::CollectRent).is_err() {
+                break;
+            }
+        }
+    });
     loop {
+        close_lost_containers(state.clone(), &connections, &mut container_views);
         match event_rx.recv() {
             Ok(command) => match command {
                 Command::PutCreature { pos, creature } => {
@@ -179,16 +489,35 @@ pub async fn event_handler(
                     creature_id,
                     direction,
                 } => {
-                    let server_event = handle_move_creature(
-                        state.clone(),
-                        &mut connections,
-                        from,
-                        to,
-                        creature_id,
-                        direction,
-                    );
-                    if let Some(event) = server_event {
-                        broadcast_event(&connections, to, None, event);
+                    let (to, direction) = stagger(state.clone(), from, to, creature_id, direction);
+                    if let Some(text) = blocked_move(state.clone(), &data, from, to) {
+                        send_cancel(&connections, creature_id, &text);
+                        if let Some((_, tx)) = connections.get(&creature_id) {
+                            let _ = tx.send(ServerEvent::WalkCancelled { direction });
+                        }
+                    } else {
+                        let server_event = handle_move_creature(
+                            state.clone(),
+                            &mut connections,
+                            from,
+                            to,
+                            creature_id,
+                            direction,
+                        );
+                        if let Some(event) = server_event {
+                            broadcast_event(&connections, to, None, event);
+                            for event_type in [MoveEventType::StepOut, MoveEventType::StepIn] {
+                                on_step(
+                                    state.clone(),
+                                    &connections,
+                                    data.clone(),
+                                    &loopback_tx,
+                                    event_type,
+                                    (from, to),
+                                    creature_id,
+                                );
+                            }
+                        }
                     }
                 }
                 Command::EnterGame {
@@ -231,12 +560,17 @@ pub async fn event_handler(
                     item,
                     count,
                 } => {
-                    if let Some(event) =
-                        handle_move_item(state.clone(), from, to, stack_pos, count, item)
-                    {
-                        let location = if from.0 == 0xFFFF { to } else { from };
+                    let events = handle_move_item(
+                        state.clone(),
+                        &data.item_data,
+                        (from, to),
+                        stack_pos,
+                        count,
+                        item,
+                    );
+                    for (location, event) in events {
                         broadcast_event(&connections, location, None, event);
-                    };
+                    }
                 }
                 Command::UseItem {
                     sender_id,
@@ -244,103 +578,169 @@ pub async fn event_handler(
                     stack_pos,
                     item,
                     index,
+                    window,
                 } => {
-                    let state_handle = state.lock().unwrap();
-                    let mut item = Some(item);
-                    if pos.0 != 0xFFFF {
-                        let tile = state_handle.map.get(&pos).unwrap();
-                        if let Some(it) = tile.get_item_at_stack_pos(stack_pos) {
-                            item = Some(it.clone());
+                    let it = window_item_or(
+                        state.clone(),
+                        window.as_ref(),
+                        pos.2,
+                        ground_item_or(state.clone(), pos, stack_pos, item),
+                    );
+                    let Some(item_data) = data.item_data.get(&it.client_id) else {
+                        send_cancel(&connections, sender_id, Cancel::NotPossible.text());
+                        continue;
+                    };
+                    if it.house_door_id != 0
+                        && house_door_closed_to(
+                            state.clone(),
+                            &connections,
+                            sender_id,
+                            pos,
+                            it.house_door_id,
+                        )
+                    {
+                        let _ = loopback_tx.send(Command::SendMessage {
+                            creature_id: sender_id,
+                            text: "You are not invited.".to_string(),
+                        });
+                    } else if let Some(action) = data.actions.find(&it) {
+                        use_item_action(
+                            state.clone(),
+                            &connections,
+                            data.clone(),
+                            &loopback_tx,
+                            sender_id,
+                            (pos, stack_pos, it.clone()),
+                            action.clone(),
+                        );
+                    } else if item_data.item_type == ItemType::Writeable
+                        || item_data.has_flag(FLAG_READABLE)
+                    {
+                        read_item_text(
+                            state.clone(),
+                            &connections,
+                            sender_id,
+                            (pos, stack_pos, &it),
+                            item_data,
+                        );
+                    } else if it.depot_id != 0 {
+                        // every player has their own locker, the connection opens it
+                        let in_reach =
+                            find_creature_position(state.clone(), &connections, sender_id)
+                                .is_some_and(|user_pos| {
+                                    user_pos.2 == pos.2
+                                        && user_pos.0.abs_diff(pos.0) <= 1
+                                        && user_pos.1.abs_diff(pos.1) <= 1
+                                });
+                        if !in_reach {
+                            send_cancel(&connections, sender_id, Cancel::TooFarAway.text());
+                            continue;
                         }
-                    }
-                    if let Some(it) = item {
-                        let item_data = data.item_data.get(&it.client_id).unwrap();
-                        if let ItemType::Container = item_data.item_type {
-                            let (_, tx) = connections.get(&sender_id).unwrap();
-                            let parent_id: Option<u8> = if pos.0 == 0xFFFF && pos.1 & 0x40 == 0x40 {
-                                Some((pos.1 & 0x0F) as u8)
-                            } else {
-                                None
-                            };
-                            let _ = tx.send(ServerEvent::OpenContainer {
-                                pos,
-                                stack_pos,
-                                index,
-                                item: it.clone(),
-                                name: item_data.item_name.clone(),
-                                parent_id,
-                                capacity: 20,
-                            });
-                        } else {
-                            let item_action = get_item_action(&it.client_id);
-                            let commands = item_action(pos, stack_pos, it.client_id);
-                            for command in commands.iter() {
-                                let _ = loopback_tx.send(command.clone());
+                        let (_, tx) = connections.get(&sender_id).unwrap();
+                        let _ = tx.send(ServerEvent::LockerOpened {
+                            pos,
+                            town_id: it.depot_id as u32,
+                            index,
+                        });
+                    } else if let ItemType::Container = item_data.item_type {
+                        let container = match view_container(
+                            state.clone(),
+                            &connections,
+                            &mut container_views,
+                            sender_id,
+                            (pos, stack_pos, window),
+                        ) {
+                            Ok(container) => container,
+                            Err(cancel) => {
+                                send_cancel(&connections, sender_id, cancel.text());
+                                continue;
                             }
-                        }
+                        };
+                        let (_, tx) = connections.get(&sender_id).unwrap();
+                        let parent_id: Option<u8> = if pos.0 == 0xFFFF && pos.1 & 0x40 == 0x40 {
+                            Some((pos.1 & 0x0F) as u8)
+                        } else {
+                            None
+                        };
+                        let _ = tx.send(ServerEvent::OpenContainer {
+                            container,
+                            index,
+                            item: it.clone(),
+                            name: item_data.item_name.clone(),
+                            parent_id,
+                            capacity: container_capacity(it.client_id, &data.item_data),
+                        });
                     }
                 }
                 Command::AddToContainer {
-                    sender_id,
-                    item,
-                    slot,
                     container,
+                    item,
+                    sender_id,
                 } => {
-                    // TODO: handle drag and drop onto container
-                    let mut state_handle = state.lock().unwrap();
-                    // let mut is_target_container: bool = false;
-                    // let target_item = container.items.get(slot as usize);
-                    // if let Some(it) = target_item {
-                    //     let target_item_data = data.item_data.get(&it.client_id);
-                    //     if let ItemType::Container = target_item_data.unwrap().item_type {
-                    //         is_target_container = true;
-                    //     }
-                    // }
                     let event = ServerEvent::AddedToContainer {
-                        pos: container.pos,
-                        stack_pos: container.stack_pos,
+                        container: container.clone(),
                         item: item.clone(),
                     };
-
-                    if container.pos.0 == 0xFFFF {
+                    let ContainerRef::Ground { pos, instance_id } = container else {
                         let (_, tx) = connections.get(&sender_id).unwrap();
                         let _ = tx.send(event);
-                    } else {
-                        state_handle.map.entry(container.pos).and_modify(|tile| {
-                            // if is_target_container {
-                            // tile.bot_items[container.stack_pos as usize - 1].items
-                            //     [slot as usize]
-                            //     .add_item(item.clone());
-                            // } else {
-                            tile.bot_items[container.stack_pos as usize - 1].add_item(item.clone());
-                            broadcast_event(&connections, container.pos, None, event);
-                            // }
-                        });
+                        continue;
+                    };
+                    let mut state_handle = state.lock().unwrap();
+                    let Some(tile) = state_handle.map.get_mut(&pos) else {
+                        continue;
+                    };
+                    match tile.find_container_mut(instance_id) {
+                        Some(target) if !target.is_full(&data.item_data) => {
+                            target.add_item(item);
+                            send_to_viewers(&connections, &container_views, instance_id, event);
+                        }
+                        // the container filled up or went away after the move was checked,
+                        // the item falls onto the tile instead of getting lost
+                        _ => {
+                            tile.add_item(item.clone(), &data.item_data);
+                            broadcast_event(
+                                &connections,
+                                pos,
+                                None,
+                                ServerEvent::ItemAdded { pos, item },
+                            );
+                            send_cancel(&connections, sender_id, Cancel::ContainerFull.text());
+                        }
                     }
                 }
                 Command::RemoveItemFromContainer {
-                    pos,
-                    stack_pos,
+                    container,
                     slot,
                     sender_id,
                 } => {
-                    let state_clone = state.clone();
-                    let mut state_handle = state_clone.lock().unwrap();
                     let event = ServerEvent::RemovedFromContainer {
-                        pos,
-                        stack_pos,
+                        container: container.clone(),
                         slot,
                     };
-                    if pos.0 == 0xFFFF {
+                    let ContainerRef::Ground { pos, instance_id } = container else {
                         let (_, tx) = connections.get(&sender_id).unwrap();
                         let _ = tx.send(event);
-                    } else {
-                        state_handle.map.entry(pos).and_modify(|tile| {
-                            tile.bot_items[stack_pos as usize - 1]
-                                .items
-                                .remove(slot as usize);
-                        });
-                        broadcast_event(&connections, pos, None, event);
+                        continue;
+                    };
+                    let removed = state
+                        .lock()
+                        .unwrap()
+                        .map
+                        .get_mut(&pos)
+                        .and_then(|tile| tile.find_container_mut(instance_id))
+                        .filter(|target| (slot as usize) < target.items.len())
+                        .map(|target| target.items.remove(slot as usize));
+                    if removed.is_some() {
+                        send_to_viewers(&connections, &container_views, instance_id, event);
+                    }
+                }
+                Command::CloseContainer {
+                    sender_id,
+                    instance_id,
+                } => {
+                    if let Some(view) = container_views.get_mut(&instance_id) {
+                        view.viewers.remove(&sender_id);
                     }
                 }
                 Command::TransformThing {
@@ -351,67 +751,535 @@ pub async fn event_handler(
                 } => {
                     let mut state_handle = state.lock().unwrap();
                     if pos.0 != 0xFFFF {
-                        let tile = state_handle.map.get_mut(&pos).unwrap();
+                        let Some(tile) = state_handle.map.get_mut(&pos) else {
+                            continue;
+                        };
                         if let Some(item) = tile.get_item_at_stack_pos(stack_pos) {
                             if item.client_id == from_item_id {
                                 tile.change_at_stack_pos(stack_pos, to_item_id);
                                 let event = ServerEvent::ThingTransformed {
                                     pos,
                                     stack_pos,
-                                    to_item_id,
+                                    item: Item {
+                                        client_id: to_item_id,
+                                        ..item
+                                    },
                                 };
                                 broadcast_event(&connections, pos, None, event);
                             }
                         };
                     }
                 }
+                Command::WriteText {
+                    pos,
+                    stack_pos,
+                    item_id,
+                    text,
+                    writer,
+                } => {
+                    let mut state_handle = state.lock().unwrap();
+                    if let Some(item) = state_handle
+                        .map
+                        .get_mut(&pos)
+                        .and_then(|tile| tile.get_mut_item_at_stack_pos(stack_pos))
+                        .filter(|item| item.client_id == item_id)
+                    {
+                        item.text = text;
+                        item.writer = writer;
+                        item.written_at = unix_time();
+                    }
+                }
                 Command::CreatureSpeech {
+                    sender_id,
                     pos,
                     text,
                     creature_name,
                     speech_type,
                 } => {
-                    let event = ServerEvent::CreatureSpoke {
-                        pos,
-                        text,
-                        creature_name,
-                        speech_type,
-                    };
-                    broadcast_event(&connections, pos, None, event);
+                    // talk actions are commands to the server, nobody else hears them
+                    if let Some((talkaction, param)) = find_talkaction(&data.talkactions, &text) {
+                        let args = vec![
+                            creature_arg(sender_id, pos),
+                            Dynamic::from(talkaction.words.clone()),
+                            Dynamic::from(param),
+                        ];
+                        run_script(
+                            data.clone(),
+                            &loopback_tx,
+                            &talkaction.script,
+                            "on_say",
+                            args,
+                        );
+                    } else {
+                        let event = ServerEvent::CreatureSpoke {
+                            pos,
+                            text,
+                            creature_name,
+                            speech_type,
+                        };
+                        broadcast_event(&connections, pos, None, event);
+                    }
                 }
                 Command::UseItemOnTarget {
+                    sender_id,
                     pos,
                     stack_pos,
                     item,
                     target_pos,
                     target_stack_pos,
-                } => {}
-                Command::TurnCreature {
-                    pos,
-                    creature_id,
-                    direction,
                 } => {
-                    if let Some(event) =
-                        handle_turn_creature(state.clone(), pos, creature_id, direction)
-                    {
-                        broadcast_event(&connections, pos, None, event);
+                    let item = ground_item_or(state.clone(), pos, stack_pos, item);
+                    if let Some(rune) = data.runes.get(&item.client_id) {
+                        // runes thrown on a tile hit the creature on top of it
+                        let target_id =
+                            state.lock().unwrap().map.get(&target_pos).and_then(|tile| {
+                                tile.creatures.first().map(|creature| creature.id)
+                            });
+                        if use_rune(
+                            state.clone(),
+                            &connections,
+                            data.clone(),
+                            &loopback_tx,
+                            sender_id,
+                            rune,
+                            (target_pos, target_id),
+                        ) {
+                            use_item_charge(
+                                state.clone(),
+                                &connections,
+                                sender_id,
+                                pos,
+                                stack_pos,
+                                rune.charges,
+                            );
+                        }
+                    } else if is_item_type(&data, &item, ItemType::Fluid) {
+                        use_fluid(
+                            state.clone(),
+                            &connections,
+                            data.clone(),
+                            &loopback_tx,
+                            sender_id,
+                            (pos, stack_pos, &item),
+                            (target_pos, target_stack_pos),
+                        );
+                    } else if is_item_type(&data, &item, ItemType::Key) {
+                        use_key(
+                            state.clone(),
+                            &connections,
+                            data.clone(),
+                            &loopback_tx,
+                            sender_id,
+                            &item,
+                            (target_pos, target_stack_pos),
+                        );
+                    } else {
+                        use_tool(
+                            state.clone(),
+                            &connections,
+                            data.clone(),
+                            &loopback_tx,
+                            sender_id,
+                            item.client_id,
+                            target_pos,
+                            target_stack_pos,
+                        );
                     }
                 }
-            },
-            Err(_) => break,
-        }
-    }
-}
-
-fn broadcast_event(
-    connections: &HashMap<u32, ((u16, u16, u8), Sender<ServerEvent>)>,
+                Command::Attack {
+                    attacker_id,
+                    target_id,
+                    skill,
+                    skill_level,
+                    attack,
+                    shoot_effect,
+                } => {
+                    handle_attack(
+                        state.clone(),
+                        &connections,
+                        &data,
+                        &loopback_tx,
+                        (attacker_id, target_id),
+                        (skill, skill_level, attack, shoot_effect),
+                    );
+                }
+                Command::UseItemOnCreature {
+                    sender_id,
+                    pos,
+                    stack_pos,
+                    item,
+                    creature_id,
+                } => {
+                    let item = ground_item_or(state.clone(), pos, stack_pos, item);
+                    let target_pos =
+                        find_creature_position(state.clone(), &connections, creature_id);
+                    match (data.runes.get(&item.client_id), target_pos) {
+                        (Some(rune), Some(target_pos)) => {
+                            if use_rune(
+                                state.clone(),
+                                &connections,
+                                data.clone(),
+                                &loopback_tx,
+                                sender_id,
+                                rune,
+                                (target_pos, Some(creature_id)),
+                            ) {
+                                use_item_charge(
+                                    state.clone(),
+                                    &connections,
+                                    sender_id,
+                                    pos,
+                                    stack_pos,
+                                    rune.charges,
+                                );
+                            }
+                        }
+                        // only the user can drink from a fluid container
+                        (None, Some(target_pos))
+                            if creature_id == sender_id
+                                && is_item_type(&data, &item, ItemType::Fluid) =>
+                        {
+                            use_fluid(
+                                state.clone(),
+                                &connections,
+                                data.clone(),
+                                &loopback_tx,
+                                sender_id,
+                                (pos, stack_pos, &item),
+                                (target_pos, 0),
+                            );
+                        }
+                        _ => send_cancel(&connections, sender_id, Cancel::CannotUseObject.text()),
+                    }
+                }
+                Command::UpdateContainerItem {
+                    container,
+                    slot,
+                    item,
+                    sender_id,
+                } => {
+                    let event = ServerEvent::ContainerItemUpdated {
+                        container: container.clone(),
+                        slot,
+                        item: item.clone(),
+                    };
+                    let ContainerRef::Ground { pos, instance_id } = container else {
+                        let (_, tx) = connections.get(&sender_id).unwrap();
+                        let _ = tx.send(event);
+                        continue;
+                    };
+                    let mut state_handle = state.lock().unwrap();
+                    if let Some(container_item) = state_handle
+                        .map
+                        .get_mut(&pos)
+                        .and_then(|tile| tile.find_container_mut(instance_id))
+                        .and_then(|target| target.items.get_mut(slot as usize))
+                    {
+                        *container_item = item;
+                        send_to_viewers(&connections, &container_views, instance_id, event);
+                    }
+                }
+                Command::TeleportCreature { creature_id, to } => {
+                    if let Some(from) =
+                        handle_teleport_creature(state.clone(), &mut connections, creature_id, to)
+                    {
+                        for event_type in [MoveEventType::StepOut, MoveEventType::StepIn] {
+                            on_step(
+                                state.clone(),
+                                &connections,
+                                data.clone(),
+                                &loopback_tx,
+                                event_type,
+                                (from, to),
+                                creature_id,
+                            );
+                        }
+                    }
+                }
+                Command::DecayItem {
+                    pos,
+                    client_id,
+                    instance_id,
+                } => {
+                    handle_decay_item(
+                        state.clone(),
+                        &connections,
+                        data.clone(),
+                        &loopback_tx,
+                        pos,
+                        (client_id, instance_id),
+                    );
+                }
+                Command::TurnCreature {
+                    pos,
+                    creature_id,
+                    direction,
+                } => {
+                    if let Some(event) =
+                        handle_turn_creature(state.clone(), pos, creature_id, direction)
+                    {
+                        broadcast_event(&connections, pos, None, event);
+                    }
+                }
+                Command::ChangeHealth {
+                    pos,
+                    creature_id,
+                    amount,
+                    attacker_id,
+                } => {
+                    handle_change_health(
+                        state.clone(),
+                        &connections,
+                        data.clone(),
+                        &loopback_tx,
+                        pos,
+                        creature_id,
+                        (amount, attacker_id),
+                    );
+                }
+                Command::SetMaxHealth {
+                    pos,
+                    creature_id,
+                    max_health,
+                } => {
+                    if let Some(event) =
+                        handle_set_max_health(state.clone(), pos, creature_id, max_health)
+                    {
+                        broadcast_event(&connections, pos, None, event);
+                    }
+                }
+                Command::PlaceCorpse {
+                    pos,
+                    corpse_id,
+                    items,
+                } => {
+                    if let Some(event) =
+                        place_corpse(state.clone(), &data, &loopback_tx, pos, corpse_id, items)
+                    {
+                        broadcast_event(&connections, pos, None, event);
+                    }
+                }
+                Command::CastSpell {
+                    caster_id,
+                    spell_name,
+                    param,
+                } => {
+                    handle_cast_spell(
+                        state.clone(),
+                        &connections,
+                        data.clone(),
+                        &loopback_tx,
+                        caster_id,
+                        &spell_name,
+                        &param,
+                    );
+                }
+                Command::AddCondition {
+                    creature_id,
+                    condition,
+                } => {
+                    if update_conditions(state.clone(), &connections, creature_id, |creature| {
+                        creature.add_condition(condition)
+                    })
+                    .is_some()
+                    {
+                        conditioned.insert(creature_id);
+                    }
+                }
+                Command::RemoveCondition {
+                    creature_id,
+                    condition_type,
+                } => {
+                    update_conditions(state.clone(), &connections, creature_id, |creature| {
+                        creature.remove_condition(&condition_type)
+                    });
+                }
+                Command::ProcessConditions => {
+                    process_conditions(
+                        state.clone(),
+                        &connections,
+                        data.clone(),
+                        &loopback_tx,
+                        &mut conditioned,
+                    );
+                }
+                Command::CreateItem { pos, item_id } => {
+                    let mut item = Item::new(item_id);
+                    let created = {
+                        let mut state_handle = state.lock().unwrap();
+                        item.instance_id = new_instance_id(&mut state_handle);
+                        match state_handle.map.get_mut(&pos) {
+                            Some(tile) => {
+                                tile.add_item(item.clone(), &data.item_data);
+                                true
+                            }
+                            None => false,
+                        }
+                    };
+                    if created {
+                        start_decay(&loopback_tx, &data, pos, &item);
+                        broadcast_event(
+                            &connections,
+                            pos,
+                            None,
+                            ServerEvent::ItemAdded { pos, item },
+                        );
+                    }
+                }
+                Command::RemoveItem { pos, item_id } => {
+                    remove_item_by_id(state.clone(), &connections, pos, item_id);
+                }
+                Command::RelocateItem { from, to, item_id } => {
+                    if state.lock().unwrap().map.contains_key(&to) {
+                        if let Some(item) =
+                            remove_item_by_id(state.clone(), &connections, from, item_id)
+                        {
+                            if let Some(tile) = state.lock().unwrap().map.get_mut(&to) {
+                                tile.add_item(item.clone(), &data.item_data);
+                            }
+                            broadcast_event(
+                                &connections,
+                                to,
+                                None,
+                                ServerEvent::ItemAdded { pos: to, item },
+                            );
+                        }
+                    }
+                }
+                Command::SendMessage { creature_id, text } => {
+                    if let Some((_, tx)) = connections.get(&creature_id) {
+                        let _ = tx.send(ServerEvent::TextMessage {
+                            message_type: MessageType::InfoDescription as u8,
+                            text,
+                        });
+                    }
+                }
+                Command::ShowMagicEffect { pos, effect } => {
+                    broadcast_event(
+                        &connections,
+                        pos,
+                        None,
+                        ServerEvent::MagicEffect { pos, effect },
+                    );
+                }
+                Command::PromotePlayer { creature_id } => {
+                    if let Some((_, tx)) = connections.get(&creature_id) {
+                        let _ = tx.send(ServerEvent::Promoted);
+                    }
+                }
+                Command::SetStorage {
+                    creature_id,
+                    key,
+                    value,
+                } => {
+                    handle_set_storage(state.clone(), &connections, creature_id, key, value);
+                }
+                Command::SetPlayerLevel {
+                    creature_id,
+                    level,
+                    vocation,
+                } => {
+                    handle_set_player_level(
+                        state.clone(),
+                        &connections,
+                        creature_id,
+                        level,
+                        vocation,
+                    );
+                }
+                Command::SetEquipment {
+                    creature_id,
+                    equipment,
+                } => {
+                    update_conditions(state.clone(), &connections, creature_id, |creature| {
+                        creature.set_equipment(equipment)
+                    });
+                }
+                Command::LookAt {
+                    sender_id,
+                    pos,
+                    stack_pos,
+                } => {
+                    handle_look_at(
+                        state.clone(),
+                        &connections,
+                        &data,
+                        sender_id,
+                        pos,
+                        stack_pos,
+                    );
+                }
+                Command::CollectRent => {
+                    collect_rent(state.clone(), &connections, &data);
+                }
+                Command::RentPaid { house_id, paid } => {
+                    handle_rent_paid(state.clone(), &data, house_id, paid);
+                }
+                Command::SetHouseOwner { house_id, owner } => {
+                    let save = {
+                        let mut state_handle = state.lock().unwrap();
+                        // a player owns one house at most
+                        let owns_other = !owner.is_empty()
+                            && state_handle
+                                .houses
+                                .values()
+                                .any(|house| house.id != house_id && house.is_owner(&owner));
+                        if owns_other {
+                            println!("{} already owns a house", owner);
+                            continue;
+                        }
+                        let Some(house) = state_handle.houses.get_mut(&house_id) else {
+                            continue;
+                        };
+                        // the first rent is due once the first period is over
+                        house.set_owner(&owner, unix_time() + rent_period(&data));
+                        house_save(&state_handle, house_id, &data.item_data)
+                    };
+                    write_house_saves(save.as_slice());
+                }
+                Command::EditHouseList {
+                    house_id,
+                    list,
+                    name,
+                    add,
+                } => {
+                    let save = {
+                        let mut state_handle = state.lock().unwrap();
+                        let house = state_handle.houses.get_mut(&house_id);
+                        let (Some(house), Some(list)) = (house, HouseList::from_name(&list)) else {
+                            continue;
+                        };
+                        let names = house.list_mut(&list);
+                        let name = name.trim().to_lowercase();
+                        names.retain(|listed| *listed != name);
+                        if add {
+                            names.push(name);
+                        }
+                        house_save(&state_handle, house_id, &data.item_data)
+                    };
+                    write_house_saves(save.as_slice());
+                }
+                Command::RunScript {
+                    script,
+                    function,
+                    args,
+                } => {
+                    run_script(data.clone(), &loopback_tx, &script, &function, args);
+                }
+            },
+            Err(_) => break,
+        }
+    }
+}
+
+fn broadcast_event(
+    connections: &Connections,
     event_pos: (u16, u16, u8),
     sender_id: Option<u32>,
     event: ServerEvent,
 ) {
-    for (id, ((connection_x, connection_y, _), sender)) in connections.iter() {
-        let (e_x, e_y, _) = event_pos;
-        if e_x.abs_diff(*connection_x) < 9 && e_y.abs_diff(*connection_y) < 9 {
+    for (id, (connection_pos, sender)) in connections.iter() {
+        if can_see(*connection_pos, event_pos) {
             if let Some(client_id) = sender_id {
                 if *id == client_id {
                     continue;
@@ -424,7 +1292,7 @@ fn broadcast_event(
 
 fn put_creature(
     state: Arc<Mutex<State>>,
-    connections: &mut HashMap<u32, ((u16, u16, u8), Sender<ServerEvent>)>,
+    connections: &mut Connections,
     pos: (u16, u16, u8),
     creature: Creature,
 ) {
@@ -470,7 +1338,7 @@ fn remove_creature_from_tile(
 
 fn handle_move_creature(
     state: Arc<Mutex<State>>,
-    connections: &mut HashMap<u32, ((u16, u16, u8), Sender<ServerEvent>)>,
+    connections: &mut Connections,
     from: (u16, u16, u8),
     to: (u16, u16, u8),
     creature_id: u32,
@@ -479,7 +1347,8 @@ fn handle_move_creature(
     let creature_to_move: Option<(Creature, u8)> =
         remove_creature_from_tile(state.clone(), from, creature_id);
     let mut state_handle = state.lock().unwrap();
-    if let Some((creature, stack_pos)) = creature_to_move {
+    if let Some((mut creature, stack_pos)) = creature_to_move {
+        creature.look_dir = direction.clone();
         state_handle
             .map
             .entry(to)
@@ -501,37 +1370,79 @@ fn handle_move_creature(
     return None;
 }
 
+// moves count items of a stack or the whole item, the events go to the viewers of their position
 fn handle_move_item(
     state: Arc<Mutex<State>>,
-    from: (u16, u16, u8),
-    to: (u16, u16, u8),
+    item_data: &HashMap<u16, ItemData>,
+    (from, to): ((u16, u16, u8), (u16, u16, u8)),
     stack_pos: u8,
-    _count: u8,
+    count: u8,
     item: Item,
-) -> Option<ServerEvent> {
+) -> Vec<((u16, u16, u8), ServerEvent)> {
     let mut state_handle = state.lock().unwrap();
-    let mut item: Option<Item> = Some(item);
+    let mut events: Vec<((u16, u16, u8), ServerEvent)> = Vec::new();
+    let mut moved = item;
     if from.0 != 0xFFFF {
-        state_handle
-            .map
-            .entry(from)
-            .and_modify(|tile| item = tile.bot_items.pop());
+        let Some(tile) = state_handle.map.get_mut(&from) else {
+            return events;
+        };
+        let Some(source) = tile.get_mut_item_at_stack_pos(stack_pos) else {
+            return events;
+        };
+        if source.client_id != moved.client_id {
+            return events;
+        }
+        match source.split(count, item_data) {
+            Some(taken) => {
+                let event = ServerEvent::ThingTransformed {
+                    pos: from,
+                    stack_pos,
+                    item: source.clone(),
+                };
+                events.push((from, event));
+                moved = taken;
+            }
+            None => {
+                moved = tile.remove_at_stack_pos(stack_pos).unwrap();
+                events.push((
+                    from,
+                    ServerEvent::ItemRemoved {
+                        pos: from,
+                        stack_pos,
+                    },
+                ));
+            }
+        }
     }
-    if let Some(it) = item {
-        if to.0 != 0xFFFF {
-            state_handle
-                .map
-                .entry(to)
-                .and_modify(|tile| tile.bot_items.push(it.clone()));
+    if to.0 != 0xFFFF {
+        let Some(tile) = state_handle.map.get_mut(&to) else {
+            return events;
+        };
+        // stacks dropped onto the same item join it
+        let top_stack_pos = (tile.top_items.len() + tile.creatures.len() + 1) as u8;
+        match tile.bot_items.first_mut() {
+            Some(top) if top.can_merge(&moved, item_data) => {
+                top.count += moved.count;
+                let event = ServerEvent::ThingTransformed {
+                    pos: to,
+                    stack_pos: top_stack_pos,
+                    item: top.clone(),
+                };
+                events.push((to, event));
+            }
+            _ => {
+                tile.add_item(moved.clone(), item_data);
+                events.push((
+                    to,
+                    ServerEvent::ItemAdded {
+                        pos: to,
+                        item: moved,
+                    },
+                ));
+            }
         }
-        return Some(ServerEvent::ItemMoved {
-            from,
-            to,
-            stack_pos,
-            item_id: it.client_id,
-        });
     }
-    return None;
+    return events;
 }
 
 fn handle_turn_creature(
@@ -558,3 +1469,2080 @@ fn handle_turn_creature(
     });
     return event;
 }
+
+fn handle_change_health(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    pos: (u16, u16, u8),
+    creature_id: u32,
+    (amount, attacker_id): (i32, Option<u32>),
+) {
+    let mut changed_health: Option<(u16, u16)> = None;
+    // the armor of worn items takes part of the hits monsters deal
+    let hit_by_monster = amount < 0 && attacker_id.is_some_and(|id| id >= FIRST_MONSTER_ID);
+    {
+        let mut state_handle = state.lock().unwrap();
+        state_handle.map.entry(pos).and_modify(|tile| {
+            for creature in tile.creatures.iter_mut() {
+                if creature.id == creature_id {
+                    let armor = creature.equipment.armor as u32;
+                    let amount = if hit_by_monster {
+                        (amount + random_range(armor / 2, armor) as i32).min(0)
+                    } else {
+                        amount
+                    };
+                    let health =
+                        (creature.health as i32 + amount).clamp(0, creature.max_health as i32);
+                    creature.health = health as u16;
+                    changed_health = Some((creature.health, creature.max_health));
+                }
+            }
+        });
+    }
+    // players train shielding on the hits they take
+    let trains_shielding = hit_by_monster && changed_health.is_some();
+    if let Some((_, tx)) = connections.get(&creature_id).filter(|_| trains_shielding) {
+        let _ = tx.send(ServerEvent::SkillTried {
+            skill: SKILL_SHIELDING,
+        });
+    }
+    if let Some((health, max_health)) = changed_health {
+        let event = ServerEvent::CreatureHealthChanged {
+            creature_id,
+            health,
+            max_health,
+        };
+        broadcast_event(connections, pos, None, event);
+        if health == 0 {
+            handle_creature_death(
+                state,
+                connections,
+                data,
+                loopback_tx,
+                pos,
+                creature_id,
+                attacker_id,
+            );
+        }
+    }
+}
+
+// a hit of a player on its target, melee reaches the tiles next to the attacker and distance
+// weapons the targets in sight, every hit trains the skill of the weapon
+fn handle_attack(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: &StaticData,
+    loopback_tx: &Sender<Command>,
+    (attacker_id, target_id): (u32, u32),
+    (skill, skill_level, attack, shoot_effect): (usize, u16, u16, Option<u8>),
+) {
+    let Some((_, tx)) = connections.get(&attacker_id) else {
+        return;
+    };
+    let (Some(from), Some(to)) = (
+        find_creature_position(state.clone(), connections, attacker_id),
+        find_creature_position(state.clone(), connections, target_id),
+    ) else {
+        let _ = tx.send(ServerEvent::TargetLost);
+        return;
+    };
+    let range = if skill == SKILL_DISTANCE {
+        DISTANCE_RANGE
+    } else {
+        1
+    };
+    if from.2 != to.2 || from.0.abs_diff(to.0) > range || from.1.abs_diff(to.1) > range {
+        return;
+    }
+    if skill == SKILL_DISTANCE {
+        let state_handle = state.lock().unwrap();
+        if !is_sight_clear(&state_handle.map, &data.item_data, from, to) {
+            return;
+        }
+    }
+    if let Some(effect) = shoot_effect {
+        broadcast_event(
+            connections,
+            from,
+            None,
+            ServerEvent::DistanceEffect { from, to, effect },
+        );
+    }
+    let max_damage = attack as u32 * (skill_level as u32 + 10) / 20;
+    let damage = random_range(0, max_damage);
+    let effect = if damage == 0 {
+        MagicEffect::BlockHit
+    } else {
+        MagicEffect::DrawBlood
+    };
+    broadcast_event(
+        connections,
+        to,
+        None,
+        ServerEvent::MagicEffect {
+            pos: to,
+            effect: effect as u8,
+        },
+    );
+    let _ = loopback_tx.send(Command::ChangeHealth {
+        pos: to,
+        creature_id: target_id,
+        amount: -(damage as i32),
+        attacker_id: Some(attacker_id),
+    });
+    let _ = tx.send(ServerEvent::SkillTried { skill });
+}
+
+fn handle_set_max_health(
+    state: Arc<Mutex<State>>,
+    pos: (u16, u16, u8),
+    creature_id: u32,
+    max_health: u16,
+) -> Option<ServerEvent> {
+    let mut state_handle = state.lock().unwrap();
+    let tile = state_handle.map.get_mut(&pos)?;
+    let creature = tile.creatures.iter_mut().find(|c| c.id == creature_id)?;
+    // gained or lost maximum health is applied to the current health as well
+    let difference = max_health as i32 - creature.max_health as i32;
+    creature.max_health = max_health;
+    creature.health = (creature.health as i32 + difference).clamp(1, max_health as i32) as u16;
+    return Some(ServerEvent::CreatureHealthChanged {
+        creature_id,
+        health: creature.health,
+        max_health,
+    });
+}
+
+fn handle_creature_death(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    pos: (u16, u16, u8),
+    creature_id: u32,
+    killer_id: Option<u32>,
+) {
+    if let Some((creature, stack_pos)) = remove_creature_from_tile(state.clone(), pos, creature_id)
+    {
+        broadcast_event(
+            connections,
+            pos,
+            None,
+            ServerEvent::CreatureRemoved { pos, stack_pos },
+        );
+        match creature.monster_type {
+            Some(monster_name) => {
+                if let Some(monster_type) = data.monster_types.get(&monster_name) {
+                    if let Some((_, tx)) = killer_id.and_then(|id| connections.get(&id)) {
+                        let _ = tx.send(ServerEvent::ExperienceGained {
+                            experience: monster_type.experience,
+                        });
+                    }
+                    let loot = generate_loot(monster_type);
+                    if let Some(event) =
+                        place_corpse(state, &data, loopback_tx, pos, monster_type.corpse_id, loot)
+                    {
+                        broadcast_event(connections, pos, None, event);
+                    }
+                }
+            }
+            None => {
+                // the player connection decides what drops and sends the corpse back
+                if let Some((_, tx)) = connections.get(&creature_id) {
+                    let _ = tx.send(ServerEvent::PlayerDied { pos });
+                }
+            }
+        }
+    }
+}
+
+// corpses decay like any other item, taking the loot left in them along
+fn place_corpse(
+    state: Arc<Mutex<State>>,
+    data: &StaticData,
+    loopback_tx: &Sender<Command>,
+    pos: (u16, u16, u8),
+    corpse_id: u16,
+    items: Vec<Item>,
+) -> Option<ServerEvent> {
+    let mut state_handle = state.lock().unwrap();
+    let mut corpse = Item::new(corpse_id);
+    corpse.items = items;
+    corpse.instance_id = new_instance_id(&mut state_handle);
+    let tile = state_handle.map.get_mut(&pos)?;
+    // newest item is on top of the stack, right after the creatures
+    tile.bot_items.insert(0, corpse.clone());
+    start_decay(loopback_tx, data, pos, &corpse);
+    return Some(ServerEvent::ItemAdded { pos, item: corpse });
+}
+
+fn schedule_command(loopback_tx: &Sender<Command>, delay: Duration, command: Command) {
+    let tx = loopback_tx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let _ = tx.send(command);
+    });
+}
+
+// runs a script function and the commands it issued, delayed ones are scheduled
+fn run_script(
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    script: &str,
+    function: &str,
+    args: Vec<Dynamic>,
+) {
+    for (delay, command) in call_script(&data.scripts, script, function, args) {
+        if delay.is_zero() {
+            let _ = loopback_tx.send(command);
+        } else {
+            schedule_command(loopback_tx, delay, command);
+        }
+    }
+}
+
+// removes the topmost item with the id from the tile
+fn remove_item_by_id(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    pos: (u16, u16, u8),
+    item_id: u16,
+) -> Option<Item> {
+    let (item, stack_pos) = {
+        let mut state_handle = state.lock().unwrap();
+        let tile = state_handle.map.get_mut(&pos)?;
+        let stack_pos = tile.find_stack_pos(|item| item.client_id == item_id)?;
+        (tile.remove_at_stack_pos(stack_pos)?, stack_pos)
+    };
+    broadcast_event(
+        connections,
+        pos,
+        None,
+        ServerEvent::ItemRemoved { pos, stack_pos },
+    );
+    return Some(item);
+}
+
+fn find_creature_position(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    creature_id: u32,
+) -> Option<(u16, u16, u8)> {
+    if let Some((pos, _)) = connections.get(&creature_id) {
+        return Some(*pos);
+    }
+    let state_handle = state.lock().unwrap();
+    for (pos, tile) in state_handle.map.iter() {
+        if tile
+            .creatures
+            .iter()
+            .any(|creature| creature.id == creature_id)
+        {
+            return Some(*pos);
+        }
+    }
+    return None;
+}
+
+// saves the houses and takes the rent that is due, from the connection of owners that are
+// online and from the saved player otherwise
+fn collect_rent(state: Arc<Mutex<State>>, connections: &Connections, data: &StaticData) {
+    let now = unix_time();
+    // the houses are written once the state is unlocked again
+    let (saves, due): (Vec<HouseSave>, Vec<(u32, String, u32, u32)>) = {
+        let state_handle = state.lock().unwrap();
+        let due = state_handle
+            .houses
+            .values()
+            .filter(|house| !house.owner.is_empty() && house.paid_until <= now)
+            .map(|house| (house.id, house.owner.clone(), house.town_id, house.rent))
+            .collect();
+        (house_saves(&state_handle, &data.item_data), due)
+    };
+    write_house_saves(&saves);
+    for (house_id, owner, town_id, rent) in due {
+        if let Some(player_id) = find_player_by_name(state.clone(), connections, &owner) {
+            let (_, tx) = connections.get(&player_id).unwrap();
+            let _ = tx.send(ServerEvent::RentDue {
+                house_id,
+                town_id,
+                rent,
+            });
+            continue;
+        }
+        let paid = pay_rent_offline(data, &owner, town_id, rent);
+        handle_rent_paid(state.clone(), data, house_id, paid);
+    }
+}
+
+fn pay_rent_offline(data: &StaticData, owner: &str, town_id: u32, rent: u32) -> bool {
+    let Some(character) = data
+        .characters
+        .iter()
+        .find(|character| character.name.to_lowercase() == owner)
+    else {
+        return false;
+    };
+    let mut character: Character = character.clone();
+    read_player(PLAYERS_DIRECTORY, &mut character, &data.vocations);
+    let Some(locker) = character.depots.get_mut(&town_id) else {
+        return false;
+    };
+    if !locker.take_money(rent) {
+        return false;
+    }
+    save_player(PLAYERS_DIRECTORY, &character);
+    return true;
+}
+
+// paid rent lasts for the rent period, owners that did not pay lose the house
+fn handle_rent_paid(state: Arc<Mutex<State>>, data: &StaticData, house_id: u32, paid: bool) {
+    let save = {
+        let mut state_handle = state.lock().unwrap();
+        let Some(house) = state_handle.houses.get_mut(&house_id) else {
+            return;
+        };
+        if paid {
+            house.paid_until = rent_paid_until(house.paid_until, unix_time(), rent_period(data));
+        } else {
+            println!("{} could not pay the rent of {}", house.owner, house.name);
+            house.set_owner("", 0);
+        }
+        house_save(&state_handle, house_id, &data.item_data)
+    };
+    write_house_saves(save.as_slice());
+}
+
+fn rent_period(data: &StaticData) -> u64 {
+    return data.config.houses.rent_days as u64 * 24 * 60 * 60;
+}
+
+fn creature_name(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    creature_id: u32,
+) -> Option<String> {
+    let pos = find_creature_position(state.clone(), connections, creature_id)?;
+    let state_handle = state.lock().unwrap();
+    let tile = state_handle.map.get(&pos)?;
+    let creature = tile.creatures.iter().find(|c| c.id == creature_id)?;
+    return Some(creature.name.clone());
+}
+
+fn find_player_by_name(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    name: &str,
+) -> Option<u32> {
+    return connections.keys().copied().find(|player_id| {
+        creature_name(state.clone(), connections, *player_id)
+            .is_some_and(|player_name| player_name.to_lowercase() == name.to_lowercase())
+    });
+}
+
+// doors of a house only open for the players on its lists
+fn house_door_closed_to(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    user_id: u32,
+    pos: (u16, u16, u8),
+    door_id: u8,
+) -> bool {
+    let Some(name) = creature_name(state.clone(), connections, user_id) else {
+        return true;
+    };
+    let state_handle = state.lock().unwrap();
+    let Some(tile) = state_handle.map.get(&pos) else {
+        return false;
+    };
+    return match state_handle.houses.get(&tile.house_id) {
+        Some(house) => !house.can_open_door(&name, door_id),
+        None => false,
+    };
+}
+
+// the creature keeps a copy so that scripts can read it, the connection saves it
+fn handle_set_storage(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    creature_id: u32,
+    key: u32,
+    value: i32,
+) {
+    let Some((pos, tx)) = connections.get(&creature_id) else {
+        return;
+    };
+    let mut state_handle = state.lock().unwrap();
+    if let Some(tile) = state_handle.map.get_mut(pos) {
+        if let Some(creature) = tile.creatures.iter_mut().find(|c| c.id == creature_id) {
+            creature.storage.insert(key, value);
+        }
+    }
+    let _ = tx.send(ServerEvent::StorageChanged { key, value });
+}
+
+fn handle_set_player_level(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    creature_id: u32,
+    level: u16,
+    vocation: u8,
+) {
+    let Some((pos, _)) = connections.get(&creature_id) else {
+        return;
+    };
+    let mut state_handle = state.lock().unwrap();
+    if let Some(creature) = state_handle
+        .map
+        .get_mut(pos)
+        .and_then(|tile| tile.creatures.iter_mut().find(|c| c.id == creature_id))
+    {
+        creature.level = level;
+        creature.vocation = vocation;
+    }
+}
+
+// describes the creature or item at the stack position, held items are described by the connection
+fn handle_look_at(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: &StaticData,
+    sender_id: u32,
+    pos: (u16, u16, u8),
+    stack_pos: u8,
+) {
+    let Some((user_pos, tx)) = connections.get(&sender_id) else {
+        return;
+    };
+    let state_handle = state.lock().unwrap();
+    let Some(tile) = state_handle.map.get(&pos) else {
+        return;
+    };
+    let text = if let Some(creature) = tile.get_creature_at_stack_pos(stack_pos) {
+        describe_creature(creature, &data.vocations, creature.id == sender_id)
+    } else if let Some(item) = tile.get_item_at_stack_pos(stack_pos) {
+        let in_reach = user_pos.2 == pos.2
+            && user_pos.0.abs_diff(pos.0) <= 1
+            && user_pos.1.abs_diff(pos.1) <= 1;
+        describe_item(&item, &data.item_data, in_reach)
+    } else {
+        return;
+    };
+    let _ = tx.send(ServerEvent::TextMessage {
+        message_type: MessageType::InfoDescription as u8,
+        text,
+    });
+}
+
+fn handle_cast_spell(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    caster_id: u32,
+    spell_name: &str,
+    param: &str,
+) {
+    let Some(spell) = data.spells.iter().find(|spell| spell.name == spell_name) else {
+        return;
+    };
+    let Some(pos) = find_creature_position(state.clone(), connections, caster_id) else {
+        return;
+    };
+    let direction = {
+        let state_handle = state.lock().unwrap();
+        let tile = state_handle.map.get(&pos).unwrap();
+        match tile
+            .creatures
+            .iter()
+            .find(|creature| creature.id == caster_id)
+        {
+            Some(creature) => creature.look_dir.clone(),
+            None => return,
+        }
+    };
+    match &spell.effect {
+        SpellEffect::Heal { min, max } => {
+            broadcast_event(
+                connections,
+                pos,
+                None,
+                ServerEvent::MagicEffect {
+                    pos,
+                    effect: spell.magic_effect,
+                },
+            );
+            let amount = random_range(*min as u32, *max as u32) as i32;
+            handle_change_health(
+                state,
+                connections,
+                data.clone(),
+                loopback_tx,
+                pos,
+                caster_id,
+                (amount, None),
+            );
+        }
+        SpellEffect::AreaDamage { min, max, area } => {
+            for target_pos in area_positions(pos, area) {
+                if !state.lock().unwrap().map.contains_key(&target_pos) {
+                    continue;
+                }
+                broadcast_event(
+                    connections,
+                    target_pos,
+                    None,
+                    ServerEvent::MagicEffect {
+                        pos: target_pos,
+                        effect: spell.magic_effect,
+                    },
+                );
+                damage_tile(
+                    state.clone(),
+                    connections,
+                    data.clone(),
+                    loopback_tx,
+                    target_pos,
+                    caster_id,
+                    (*min, *max),
+                );
+            }
+        }
+        SpellEffect::Damage { min, max } => {
+            // instant damage spells hit whatever stands in front of the caster
+            let target_pos = direction.move_in_dir(pos);
+            broadcast_event(
+                connections,
+                target_pos,
+                None,
+                ServerEvent::MagicEffect {
+                    pos: target_pos,
+                    effect: spell.magic_effect,
+                },
+            );
+            damage_tile(
+                state,
+                connections,
+                data.clone(),
+                loopback_tx,
+                target_pos,
+                caster_id,
+                (*min, *max),
+            );
+        }
+        SpellEffect::Haste { speed, duration } => {
+            broadcast_event(
+                connections,
+                pos,
+                None,
+                ServerEvent::MagicEffect {
+                    pos,
+                    effect: spell.magic_effect,
+                },
+            );
+            let _ = loopback_tx.send(Command::AddCondition {
+                creature_id: caster_id,
+                condition: Condition::speed(
+                    ConditionType::Haste,
+                    *speed as i32,
+                    Duration::from_secs(*duration as u64),
+                ),
+            });
+        }
+        SpellEffect::Light {
+            level,
+            color,
+            duration,
+        } => {
+            broadcast_event(
+                connections,
+                pos,
+                None,
+                ServerEvent::MagicEffect {
+                    pos,
+                    effect: spell.magic_effect,
+                },
+            );
+            let _ = loopback_tx.send(Command::AddCondition {
+                creature_id: caster_id,
+                condition: Condition::light(*level, *color, Duration::from_secs(*duration as u64)),
+            });
+        }
+        SpellEffect::Invisible { duration } => {
+            broadcast_event(
+                connections,
+                pos,
+                None,
+                ServerEvent::MagicEffect {
+                    pos,
+                    effect: spell.magic_effect,
+                },
+            );
+            let _ = loopback_tx.send(Command::AddCondition {
+                creature_id: caster_id,
+                condition: Condition::timed(
+                    ConditionType::Invisible,
+                    Duration::from_secs(*duration as u64),
+                ),
+            });
+        }
+        SpellEffect::Cure { condition } => {
+            broadcast_event(
+                connections,
+                pos,
+                None,
+                ServerEvent::MagicEffect {
+                    pos,
+                    effect: spell.magic_effect,
+                },
+            );
+            let _ = loopback_tx.send(Command::RemoveCondition {
+                creature_id: caster_id,
+                condition_type: condition.clone(),
+            });
+        }
+        // only runes paralyze
+        SpellEffect::Paralyze { .. } => {}
+        SpellEffect::Field { item_id, area } => {
+            for field_pos in area_positions(direction.move_in_dir(pos), area) {
+                place_field(
+                    state.clone(),
+                    connections,
+                    data.clone(),
+                    loopback_tx,
+                    field_pos,
+                    *item_id,
+                );
+            }
+        }
+        SpellEffect::Summon => {
+            let summon_pos = direction.move_in_dir(pos);
+            let message = match data.monster_types.get(&param.to_lowercase()) {
+                None => Some("This creature cannot be summoned."),
+                Some(monster_type) => {
+                    let creature = {
+                        let mut state_handle = state.lock().unwrap();
+                        let free = match state_handle.map.get(&summon_pos) {
+                            Some(tile) => tile.creatures.is_empty(),
+                            None => false,
+                        };
+                        if free {
+                            state_handle.next_creature_id += 1;
+                            Some(create_monster(state_handle.next_creature_id, monster_type))
+                        } else {
+                            None
+                        }
+                    };
+                    match creature {
+                        Some(creature) => {
+                            let _ = loopback_tx.send(Command::PutCreature {
+                                pos: summon_pos,
+                                creature,
+                            });
+                            None
+                        }
+                        None => Some(Cancel::NotEnoughRoom.text()),
+                    }
+                }
+            };
+            if let Some(text) = message {
+                if let Some((_, tx)) = connections.get(&caster_id) {
+                    let _ = tx.send(ServerEvent::TextMessage {
+                        message_type: MessageType::StatusSmall as u8,
+                        text: text.to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn area_positions(center: (u16, u16, u8), area: &[(i16, i16)]) -> Vec<(u16, u16, u8)> {
+    return area
+        .iter()
+        .map(|(offset_x, offset_y)| {
+            (
+                (center.0 as i32 + *offset_x as i32) as u16,
+                (center.1 as i32 + *offset_y as i32) as u16,
+                center.2,
+            )
+        })
+        .collect();
+}
+
+// hits every creature on the tile except the attacker
+fn damage_tile(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    pos: (u16, u16, u8),
+    attacker_id: u32,
+    (min, max): (u16, u16),
+) {
+    let targets: Vec<u32> = match state.lock().unwrap().map.get(&pos) {
+        Some(tile) => tile
+            .creatures
+            .iter()
+            .filter(|creature| creature.id != attacker_id)
+            .map(|creature| creature.id)
+            .collect(),
+        None => return,
+    };
+    for target_id in targets {
+        let damage = random_range(min as u32, max as u32) as i32;
+        handle_change_health(
+            state.clone(),
+            connections,
+            data.clone(),
+            loopback_tx,
+            pos,
+            target_id,
+            (-damage, Some(attacker_id)),
+        );
+    }
+}
+
+fn send_cancel(connections: &Connections, creature_id: u32, text: &str) {
+    if let Some((pos, tx)) = connections.get(&creature_id) {
+        let _ = tx.send(ServerEvent::TextMessage {
+            message_type: MessageType::StatusSmall as u8,
+            text: text.to_string(),
+        });
+        let _ = tx.send(ServerEvent::MagicEffect {
+            pos: *pos,
+            effect: MagicEffect::Puff as u8,
+        });
+    }
+}
+
+// ground items are taken from the map, held items are trusted from the connection
+fn ground_item_or(
+    state: Arc<Mutex<State>>,
+    pos: (u16, u16, u8),
+    stack_pos: u8,
+    item: Item,
+) -> Item {
+    if pos.0 == 0xFFFF {
+        return item;
+    }
+    let state_handle = state.lock().unwrap();
+    return match state_handle.map.get(&pos) {
+        Some(tile) => tile.get_item_at_stack_pos(stack_pos).unwrap_or(item),
+        None => item,
+    };
+}
+
+// the item used from a ground container window is read from the map as well
+fn window_item_or(
+    state: Arc<Mutex<State>>,
+    window: Option<&ContainerRef>,
+    slot: u8,
+    item: Item,
+) -> Item {
+    let Some(ContainerRef::Ground { pos, instance_id }) = window else {
+        return item;
+    };
+    let state_handle = state.lock().unwrap();
+    return state_handle
+        .map
+        .get(pos)
+        .and_then(|tile| tile.find_container(*instance_id))
+        .and_then(|container| container.items.get(slot as usize))
+        .cloned()
+        .unwrap_or(item);
+}
+
+// where the used container is, a ground container gets an instance id the first time it is
+// opened and the user becomes one of its viewers
+fn view_container(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    container_views: &mut ContainerViews,
+    user_id: u32,
+    (pos, stack_pos, window): ((u16, u16, u8), u8, Option<ContainerRef>),
+) -> Result<ContainerRef, Cancel> {
+    let (tile_pos, parent_id) = match window {
+        Some(ContainerRef::Held {
+            pos: root,
+            locker,
+            path,
+        }) => {
+            let path = [&path[..], &[pos.2]].concat();
+            return Ok(ContainerRef::Held {
+                pos: root,
+                locker,
+                path,
+            });
+        }
+        None if pos.0 == 0xFFFF => {
+            return Ok(ContainerRef::Held {
+                pos,
+                locker: None,
+                path: vec![],
+            });
+        }
+        Some(ContainerRef::Ground {
+            pos: root,
+            instance_id,
+        }) => (root, instance_id),
+        None => (pos, 0),
+    };
+    let (user_pos, _) = connections.get(&user_id).ok_or(Cancel::NotPossible)?;
+    if user_pos.2 != tile_pos.2
+        || user_pos.0.abs_diff(tile_pos.0) > 1
+        || user_pos.1.abs_diff(tile_pos.1) > 1
+    {
+        return Err(Cancel::TooFarAway);
+    }
+    let mut state_handle = state.lock().unwrap();
+    let state_handle = &mut *state_handle;
+    let tile = state_handle
+        .map
+        .get_mut(&tile_pos)
+        .ok_or(Cancel::NotPossible)?;
+    let container = if parent_id == 0 {
+        tile.get_mut_item_at_stack_pos(stack_pos)
+    } else {
+        tile.find_container_mut(parent_id)
+            .and_then(|parent| parent.items.get_mut(pos.2 as usize))
+    }
+    .ok_or(Cancel::NotPossible)?;
+    if container.instance_id == 0 {
+        container.instance_id = state_handle.next_instance_id;
+        state_handle.next_instance_id += 1;
+    }
+    let instance_id = container.instance_id;
+    container_views
+        .entry(instance_id)
+        .or_insert_with(|| ContainerView {
+            pos: tile_pos,
+            parent_id,
+            viewers: HashSet::new(),
+        })
+        .viewers
+        .insert(user_id);
+    return Ok(ContainerRef::Ground {
+        pos: tile_pos,
+        instance_id,
+    });
+}
+
+fn send_to_viewers(
+    connections: &Connections,
+    container_views: &ContainerViews,
+    instance_id: u32,
+    event: ServerEvent,
+) {
+    let Some(view) = container_views.get(&instance_id) else {
+        return;
+    };
+    for viewer_id in view.viewers.iter() {
+        if let Some((_, tx)) = connections.get(viewer_id) {
+            let _ = tx.send(event.clone());
+        }
+    }
+}
+
+// closes the windows of the viewers who walked away from a ground container, and of everyone
+// once the container is no longer where it was opened
+fn close_lost_containers(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    container_views: &mut ContainerViews,
+) {
+    let state_handle = state.lock().unwrap();
+    container_views.retain(|instance_id, view| {
+        let in_place = state_handle.map.get(&view.pos).is_some_and(|tile| {
+            let is_it = |item: &Item| item.instance_id == *instance_id;
+            if view.parent_id == 0 {
+                return tile
+                    .top_items
+                    .iter()
+                    .chain(tile.bot_items.iter())
+                    .any(is_it);
+            }
+            return tile
+                .find_container(view.parent_id)
+                .is_some_and(|parent| parent.items.iter().any(is_it));
+        });
+        view.viewers.retain(|viewer_id| {
+            let Some((viewer_pos, tx)) = connections.get(viewer_id) else {
+                return false;
+            };
+            let in_reach = viewer_pos.2 == view.pos.2
+                && viewer_pos.0.abs_diff(view.pos.0) <= 1
+                && viewer_pos.1.abs_diff(view.pos.1) <= 1;
+            if in_place && in_reach {
+                return true;
+            }
+            let _ = tx.send(ServerEvent::ContainerClosed {
+                instance_id: *instance_id,
+            });
+            return false;
+        });
+        return !view.viewers.is_empty();
+    });
+}
+
+// opens the text of a sign, book or letter, writeable ones can be edited when they are reachable
+fn read_item_text(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    user_id: u32,
+    (pos, stack_pos, item): ((u16, u16, u8), u8, &Item),
+    item_data: &ItemData,
+) {
+    let Some(user_pos) = find_creature_position(state.clone(), connections, user_id) else {
+        return;
+    };
+    let in_reach = pos.0 == 0xFFFF
+        || (user_pos.2 == pos.2
+            && user_pos.0.abs_diff(pos.0) <= 1
+            && user_pos.1.abs_diff(pos.1) <= 1);
+    if !in_reach && !item_data.has_flag(FLAG_ALLOW_DISTANCE_READ) {
+        send_cancel(connections, user_id, Cancel::TooFarAway.text());
+        return;
+    }
+    let writeable = in_reach && item_data.item_type == ItemType::Writeable;
+    let max_length = if !writeable {
+        item.text.len() as u16
+    } else if item_data.max_text_length != 0 {
+        item_data.max_text_length
+    } else {
+        DEFAULT_MAX_TEXT_LENGTH
+    };
+    if let Some((_, tx)) = connections.get(&user_id) {
+        let _ = tx.send(ServerEvent::TextWindow {
+            pos,
+            stack_pos,
+            item_id: item.client_id,
+            text: item.text.clone(),
+            max_length,
+            writeable,
+        });
+    }
+}
+
+// runs the actions.xml behavior of an item a player used
+fn use_item_action(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    user_id: u32,
+    (pos, stack_pos, item): ((u16, u16, u8), u8, Item),
+    action: ItemAction,
+) {
+    let Some(user_pos) = find_creature_position(state.clone(), connections, user_id) else {
+        return;
+    };
+    // signs can be read from afar
+    if let ItemAction::Text { text } = action {
+        if let Some((_, tx)) = connections.get(&user_id) {
+            let _ = tx.send(ServerEvent::TextWindow {
+                pos,
+                stack_pos,
+                item_id: item.client_id,
+                max_length: text.len() as u16,
+                text,
+                writeable: false,
+            });
+        }
+        return;
+    }
+    let held = pos.0 == 0xFFFF;
+    if !held
+        && (user_pos.2 != pos.2 || user_pos.0.abs_diff(pos.0) > 1 || user_pos.1.abs_diff(pos.1) > 1)
+    {
+        send_cancel(connections, user_id, Cancel::TooFarAway.text());
+        return;
+    }
+    match action {
+        // only items on the map are transformed
+        ItemAction::Transform { to_item_id } => {
+            if !held {
+                let _ = loopback_tx.send(Command::TransformThing {
+                    pos,
+                    stack_pos,
+                    from_item_id: item.client_id,
+                    to_item_id,
+                });
+            }
+        }
+        ItemAction::Teleport {
+            destination,
+            relative,
+        } => {
+            let base = if held { user_pos } else { pos };
+            let to = if relative {
+                // offsets leading off the map, like up from the top floor, go nowhere
+                let (Ok(x), Ok(y), Ok(z)) = (
+                    u16::try_from(base.0 as i32 + destination.0),
+                    u16::try_from(base.1 as i32 + destination.1),
+                    u8::try_from(base.2 as i32 + destination.2),
+                ) else {
+                    send_cancel(connections, user_id, Cancel::NotPossible.text());
+                    return;
+                };
+                (x, y, z)
+            } else {
+                (
+                    destination.0 as u16,
+                    destination.1 as u16,
+                    destination.2 as u8,
+                )
+            };
+            let _ = loopback_tx.send(Command::TeleportCreature {
+                creature_id: user_id,
+                to,
+            });
+        }
+        ItemAction::Lever {
+            target_pos,
+            wall_id,
+        } => {
+            if let Some(ItemAction::Transform { to_item_id }) =
+                data.actions.item_ids.get(&item.client_id)
+            {
+                let _ = loopback_tx.send(Command::TransformThing {
+                    pos,
+                    stack_pos,
+                    from_item_id: item.client_id,
+                    to_item_id: *to_item_id,
+                });
+            }
+            let event = {
+                let mut state_handle = state.lock().unwrap();
+                let Some(tile) = state_handle.map.get_mut(&target_pos) else {
+                    return;
+                };
+                match tile.find_stack_pos(|it| it.client_id == wall_id) {
+                    Some(wall_stack_pos) => {
+                        tile.remove_at_stack_pos(wall_stack_pos);
+                        ServerEvent::ItemRemoved {
+                            pos: target_pos,
+                            stack_pos: wall_stack_pos,
+                        }
+                    }
+                    None => {
+                        tile.add_item(Item::new(wall_id), &data.item_data);
+                        ServerEvent::ItemAdded {
+                            pos: target_pos,
+                            item: Item::new(wall_id),
+                        }
+                    }
+                }
+            };
+            broadcast_event(connections, target_pos, None, event);
+        }
+        ItemAction::Script { script } => {
+            let args = vec![
+                creature_arg(user_id, user_pos),
+                item_arg(&item, pos, stack_pos),
+            ];
+            run_script(data, loopback_tx, &script, "on_use", args);
+        }
+        // the player owns its inventory and storage, so the connection decides
+        ItemAction::Chest {
+            reward_id,
+            storage_key,
+        } => {
+            if let Some((_, tx)) = connections.get(&user_id) {
+                let _ = tx.send(ServerEvent::ChestOpened {
+                    reward_id,
+                    storage_key,
+                });
+            }
+        }
+        ItemAction::LockedDoor { .. } => {
+            let _ = loopback_tx.send(Command::SendMessage {
+                creature_id: user_id,
+                text: "It is locked.".to_string(),
+            });
+        }
+        // levels and storage values are known to the connection
+        ItemAction::GatedDoor { gate, open_id } => {
+            if let Some((_, tx)) = connections.get(&user_id) {
+                let _ = tx.send(ServerEvent::GatedDoorUsed {
+                    pos,
+                    stack_pos,
+                    door: item,
+                    open_id,
+                    gate,
+                });
+            }
+        }
+        ItemAction::Fishing => {
+            broadcast_event(
+                connections,
+                pos,
+                None,
+                ServerEvent::MagicEffect {
+                    pos,
+                    effect: MagicEffect::LoseEnergy as u8,
+                },
+            );
+            if let Some((_, tx)) = connections.get(&user_id) {
+                let _ = tx.send(ServerEvent::SkillTried {
+                    skill: SKILL_FISHING,
+                });
+            }
+        }
+        ItemAction::Text { .. } => {}
+    }
+}
+
+// keys lock and unlock the doors having the same action id
+fn use_key(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    user_id: u32,
+    key: &Item,
+    (target_pos, target_stack_pos): ((u16, u16, u8), u8),
+) {
+    let Some(user_pos) = find_creature_position(state.clone(), connections, user_id) else {
+        return;
+    };
+    if user_pos.2 != target_pos.2
+        || user_pos.0.abs_diff(target_pos.0) > 1
+        || user_pos.1.abs_diff(target_pos.1) > 1
+    {
+        send_cancel(connections, user_id, Cancel::TooFarAway.text());
+        return;
+    }
+    let Some(door) = state
+        .lock()
+        .unwrap()
+        .map
+        .get(&target_pos)
+        .and_then(|tile| tile.get_item_at_stack_pos(target_stack_pos))
+    else {
+        return;
+    };
+    let to_item_id = match data.actions.find(&door) {
+        Some(ItemAction::LockedDoor { unlocked_id }) => Some(*unlocked_id),
+        _ => data.actions.locked_door_of(door.client_id),
+    };
+    let Some(to_item_id) = to_item_id else {
+        send_cancel(connections, user_id, Cancel::CannotUseObject.text());
+        return;
+    };
+    if door.action_id == 0 || door.action_id != key.action_id {
+        let _ = loopback_tx.send(Command::SendMessage {
+            creature_id: user_id,
+            text: "The key does not match.".to_string(),
+        });
+        return;
+    }
+    let _ = loopback_tx.send(Command::TransformThing {
+        pos: target_pos,
+        stack_pos: target_stack_pos,
+        from_item_id: door.client_id,
+        to_item_id,
+    });
+}
+
+// returns whether the rune went off and a charge has to be taken from it
+fn use_rune(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    user_id: u32,
+    rune: &Rune,
+    (target_pos, target_id): ((u16, u16, u8), Option<u32>),
+) -> bool {
+    let Some(user_pos) = find_creature_position(state.clone(), connections, user_id) else {
+        return false;
+    };
+    let failure = {
+        let state_handle = state.lock().unwrap();
+        if !state_handle.map.contains_key(&target_pos) {
+            Some(Cancel::NotPossible.text())
+        } else if user_pos.2 != target_pos.2 {
+            Some(Cancel::CannotThrowThere.text())
+        } else if user_pos.0.abs_diff(target_pos.0) > rune.range
+            || user_pos.1.abs_diff(target_pos.1) > rune.range
+        {
+            Some(Cancel::OutOfRange.text())
+        } else if !is_sight_clear(&state_handle.map, &data.item_data, user_pos, target_pos) {
+            Some(Cancel::CannotThrowThere.text())
+        } else if rune.need_target && target_id.is_none() {
+            Some("You can only use this rune on creatures.")
+        } else if !matches!(
+            rune.effect,
+            SpellEffect::Heal { .. }
+                | SpellEffect::Damage { .. }
+                | SpellEffect::AreaDamage { .. }
+                | SpellEffect::Paralyze { .. }
+                | SpellEffect::Field { .. }
+        ) {
+            Some(Cancel::NotPossible.text())
+        } else {
+            None
+        }
+    };
+    if let Some(text) = failure {
+        send_cancel(connections, user_id, text);
+        return false;
+    }
+    if let Some(effect) = rune.shoot_effect {
+        let event = ServerEvent::DistanceEffect {
+            from: user_pos,
+            to: target_pos,
+            effect,
+        };
+        broadcast_event(connections, user_pos, None, event);
+    }
+    let hit_positions = match &rune.effect {
+        SpellEffect::AreaDamage { area, .. } => area_positions(target_pos, area),
+        SpellEffect::Field { area, .. } => area_positions(target_pos, area),
+        _ => vec![target_pos],
+    };
+    for pos in hit_positions {
+        if !state.lock().unwrap().map.contains_key(&pos) {
+            continue;
+        }
+        let event = ServerEvent::MagicEffect {
+            pos,
+            effect: rune.magic_effect,
+        };
+        broadcast_event(connections, pos, None, event);
+        match (&rune.effect, target_id) {
+            (SpellEffect::AreaDamage { min, max, .. }, _) => {
+                damage_tile(
+                    state.clone(),
+                    connections,
+                    data.clone(),
+                    loopback_tx,
+                    pos,
+                    user_id,
+                    (*min, *max),
+                );
+            }
+            (SpellEffect::Damage { min, max }, Some(target_id)) => {
+                let damage = random_range(*min as u32, *max as u32) as i32;
+                handle_change_health(
+                    state.clone(),
+                    connections,
+                    data.clone(),
+                    loopback_tx,
+                    pos,
+                    target_id,
+                    (-damage, Some(user_id)),
+                );
+            }
+            (SpellEffect::Field { item_id, .. }, _) => {
+                place_field(
+                    state.clone(),
+                    connections,
+                    data.clone(),
+                    loopback_tx,
+                    pos,
+                    *item_id,
+                );
+            }
+            (SpellEffect::Paralyze { speed, duration }, Some(target_id)) => {
+                let _ = loopback_tx.send(Command::AddCondition {
+                    creature_id: target_id,
+                    condition: Condition::speed(
+                        ConditionType::Paralyze,
+                        -(*speed as i32),
+                        Duration::from_secs(*duration as u64),
+                    ),
+                });
+            }
+            (SpellEffect::Heal { min, max }, Some(target_id)) => {
+                let amount = random_range(*min as u32, *max as u32) as i32;
+                handle_change_health(
+                    state.clone(),
+                    connections,
+                    data.clone(),
+                    loopback_tx,
+                    pos,
+                    target_id,
+                    (amount, None),
+                );
+            }
+            _ => {}
+        }
+    }
+    return true;
+}
+
+// ground items lose their charge here, held items on the connection of their owner
+fn use_item_charge(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    user_id: u32,
+    pos: (u16, u16, u8),
+    stack_pos: u8,
+    default_charges: u8,
+) {
+    if pos.0 == 0xFFFF {
+        if let Some((_, tx)) = connections.get(&user_id) {
+            let _ = tx.send(ServerEvent::ItemChargeUsed {
+                pos,
+                default_charges,
+            });
+        }
+        return;
+    }
+    let mut state_handle = state.lock().unwrap();
+    let Some(tile) = state_handle.map.get_mut(&pos) else {
+        return;
+    };
+    let used_up = match tile.get_mut_item_at_stack_pos(stack_pos) {
+        Some(item) => item.use_charge(default_charges),
+        None => false,
+    };
+    if used_up {
+        tile.remove_at_stack_pos(stack_pos);
+        broadcast_event(
+            connections,
+            pos,
+            None,
+            ServerEvent::ItemRemoved { pos, stack_pos },
+        );
+    }
+}
+
+fn is_item_type(data: &StaticData, item: &Item, item_type: ItemType) -> bool {
+    return data
+        .item_data
+        .get(&item.client_id)
+        .is_some_and(|item_data| item_data.item_type == item_type);
+}
+
+// drinks when used on the user, fills an empty container from a fluid source or pours the
+// fluid out onto the target tile
+fn use_fluid(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    user_id: u32,
+    (pos, stack_pos, container): ((u16, u16, u8), u8, &Item),
+    (target_pos, target_stack_pos): ((u16, u16, u8), u8),
+) {
+    let Some(user_pos) = find_creature_position(state.clone(), connections, user_id) else {
+        return;
+    };
+    if user_pos.2 != target_pos.2
+        || user_pos.0.abs_diff(target_pos.0) > 1
+        || user_pos.1.abs_diff(target_pos.1) > 1
+    {
+        send_cancel(connections, user_id, Cancel::TooFarAway.text());
+        return;
+    }
+    let fluid = FluidType::from_u8(container.subtype);
+    if target_pos == user_pos {
+        if fluid == FluidType::Empty {
+            send_cancel(connections, user_id, Cancel::IsEmpty.text());
+            return;
+        }
+        drink_fluid(
+            state.clone(),
+            connections,
+            loopback_tx,
+            user_id,
+            user_pos,
+            fluid,
+        );
+        set_item_subtype(
+            state,
+            connections,
+            user_id,
+            (pos, stack_pos),
+            FluidType::Empty as u8,
+        );
+        return;
+    }
+    if fluid == FluidType::Empty {
+        let source = state
+            .lock()
+            .unwrap()
+            .map
+            .get(&target_pos)
+            .and_then(|tile| tile.get_item_at_stack_pos(target_stack_pos))
+            .and_then(|item| data.item_data.get(&item.client_id))
+            .map_or(0, |item_data| item_data.fluid_source);
+        if source == 0 {
+            send_cancel(connections, user_id, Cancel::IsEmpty.text());
+            return;
+        }
+        set_item_subtype(state, connections, user_id, (pos, stack_pos), source);
+        return;
+    }
+    {
+        let mut state_handle = state.lock().unwrap();
+        let instance_id = new_instance_id(&mut state_handle);
+        let Some(tile) = state_handle.map.get_mut(&target_pos) else {
+            return;
+        };
+        if tile.has_item_with_flag(&data.item_data, FLAG_BLOCK_SOLID) {
+            send_cancel(connections, user_id, Cancel::NotEnoughRoom.text());
+            return;
+        }
+        let mut splash = Item::new(SPLASH);
+        splash.subtype = container.subtype;
+        splash.instance_id = instance_id;
+        tile.add_item(splash.clone(), &data.item_data);
+        start_decay(loopback_tx, &data, target_pos, &splash);
+        let event = ServerEvent::ItemAdded {
+            pos: target_pos,
+            item: splash,
+        };
+        broadcast_event(connections, target_pos, None, event);
+    }
+    set_item_subtype(
+        state,
+        connections,
+        user_id,
+        (pos, stack_pos),
+        FluidType::Empty as u8,
+    );
+}
+
+fn drink_fluid(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    loopback_tx: &Sender<Command>,
+    user_id: u32,
+    user_pos: (u16, u16, u8),
+    fluid: FluidType,
+) {
+    match fluid {
+        FluidType::Mana => {
+            if let Some((_, tx)) = connections.get(&user_id) {
+                let amount = random_range(MANA_FLUID_MIN as u32, MANA_FLUID_MAX as u32);
+                let _ = tx.send(ServerEvent::ManaGained {
+                    amount: amount as u16,
+                });
+            }
+        }
+        FluidType::Life => {
+            let _ = loopback_tx.send(Command::ChangeHealth {
+                pos: user_pos,
+                creature_id: user_id,
+                amount: random_range(LIFE_FLUID_MIN as u32, LIFE_FLUID_MAX as u32) as i32,
+                attacker_id: None,
+            });
+        }
+        FluidType::Beer | FluidType::Wine => {
+            let _ = loopback_tx.send(Command::AddCondition {
+                creature_id: user_id,
+                condition: Condition::timed(
+                    ConditionType::Drunk,
+                    Duration::from_secs(DRUNK_DURATION),
+                ),
+            });
+        }
+        FluidType::Slime => {
+            let _ = loopback_tx.send(Command::AddCondition {
+                creature_id: user_id,
+                condition: Condition::damage(
+                    ConditionType::Poison,
+                    SLIME_POISON_DAMAGE,
+                    SLIME_POISON_TICKS,
+                    None,
+                ),
+            });
+        }
+        _ => {}
+    }
+    let Some(name) = creature_name(state, connections, user_id) else {
+        return;
+    };
+    let event = ServerEvent::CreatureSpoke {
+        pos: user_pos,
+        text: fluid.drink_text().to_string(),
+        creature_name: name,
+        speech_type: SpeechType::Say as u8,
+    };
+    broadcast_event(connections, user_pos, None, event);
+}
+
+// the connection owns items in the inventory and its containers, the world those on the map
+fn set_item_subtype(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    user_id: u32,
+    (pos, stack_pos): ((u16, u16, u8), u8),
+    subtype: u8,
+) {
+    if pos.0 == 0xFFFF {
+        if let Some((_, tx)) = connections.get(&user_id) {
+            let _ = tx.send(ServerEvent::ItemSubtypeChanged { pos, subtype });
+        }
+        return;
+    }
+    let mut state_handle = state.lock().unwrap();
+    let Some(tile) = state_handle.map.get_mut(&pos) else {
+        return;
+    };
+    let Some(item) = tile.get_mut_item_at_stack_pos(stack_pos) else {
+        return;
+    };
+    item.subtype = subtype;
+    let event = ServerEvent::ThingTransformed {
+        pos,
+        stack_pos,
+        item: item.clone(),
+    };
+    broadcast_event(connections, pos, None, event);
+}
+
+// tools run the action registered for them and the target as if the target was used
+fn use_tool(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    user_id: u32,
+    tool_id: u16,
+    target_pos: (u16, u16, u8),
+    target_stack_pos: u8,
+) {
+    let target = {
+        let state_handle = state.lock().unwrap();
+        state_handle
+            .map
+            .get(&target_pos)
+            .and_then(|tile| tile.get_item_at_stack_pos(target_stack_pos))
+    };
+    let Some((target, action)) = target.and_then(|item| {
+        let action = data.actions.tools.get(&(tool_id, item.client_id))?.clone();
+        Some((item, action))
+    }) else {
+        send_cancel(connections, user_id, Cancel::CannotUseObject.text());
+        return;
+    };
+    use_item_action(
+        state,
+        connections,
+        data,
+        loopback_tx,
+        user_id,
+        (target_pos, target_stack_pos, target),
+        action,
+    );
+}
+
+fn handle_teleport_creature(
+    state: Arc<Mutex<State>>,
+    connections: &mut Connections,
+    creature_id: u32,
+    to: (u16, u16, u8),
+) -> Option<(u16, u16, u8)> {
+    let from = find_creature_position(state.clone(), connections, creature_id)?;
+    if !state.lock().unwrap().map.contains_key(&to) {
+        return None;
+    }
+    let (creature, stack_pos) = remove_creature_from_tile(state.clone(), from, creature_id)?;
+    broadcast_event(
+        connections,
+        from,
+        None,
+        ServerEvent::CreatureRemoved {
+            pos: from,
+            stack_pos,
+        },
+    );
+    let event = ServerEvent::MagicEffect {
+        pos: from,
+        effect: MagicEffect::EnergyArea as u8,
+    };
+    broadcast_event(connections, from, None, event);
+    put_creature(state, connections, to, creature.clone());
+    broadcast_event(
+        connections,
+        to,
+        Some(creature_id),
+        ServerEvent::CreatureAdded { pos: to, creature },
+    );
+    if let Some((_, tx)) = connections.get(&creature_id) {
+        let _ = tx.send(ServerEvent::Teleported { pos: to });
+    }
+    // after the teleported player got its new map, so it sees the effect too
+    let event = ServerEvent::MagicEffect {
+        pos: to,
+        effect: MagicEffect::EnergyArea as u8,
+    };
+    broadcast_event(connections, to, None, event);
+    return Some(from);
+}
+
+// changes the conditions of a creature and shows the result to everyone around
+fn update_conditions<T>(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    creature_id: u32,
+    update: impl FnOnce(&mut Creature) -> T,
+) -> Option<((u16, u16, u8), T)> {
+    let pos = find_creature_position(state.clone(), connections, creature_id)?;
+    let (before, after, outfit, result) = {
+        let mut state_handle = state.lock().unwrap();
+        let tile = state_handle.map.get_mut(&pos)?;
+        let creature = tile.creatures.iter_mut().find(|c| c.id == creature_id)?;
+        let before = creature.condition_view();
+        let result = update(creature);
+        (
+            before,
+            creature.condition_view(),
+            creature.outfit_bytes(),
+            result,
+        )
+    };
+    if before.speed != after.speed {
+        let event = ServerEvent::CreatureSpeedChanged {
+            creature_id,
+            speed: after.speed,
+        };
+        broadcast_event(connections, pos, None, event);
+    }
+    if before.light_level != after.light_level || before.light_color != after.light_color {
+        let event = ServerEvent::CreatureLightChanged {
+            creature_id,
+            light_level: after.light_level,
+            light_color: after.light_color,
+        };
+        broadcast_event(connections, pos, None, event);
+    }
+    if before.invisible != after.invisible {
+        let event = ServerEvent::CreatureOutfitChanged {
+            creature_id,
+            outfit,
+        };
+        broadcast_event(connections, pos, None, event);
+    }
+    if before.icons != after.icons {
+        if let Some((_, tx)) = connections.get(&creature_id) {
+            let _ = tx.send(ServerEvent::IconsChanged { icons: after.icons });
+        }
+    }
+    return Some((pos, result));
+}
+
+fn process_conditions(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    conditioned: &mut HashSet<u32>,
+) {
+    let now = Instant::now();
+    for creature_id in conditioned.clone() {
+        let ticked = update_conditions(state.clone(), connections, creature_id, |creature| {
+            (
+                creature.tick_conditions(now),
+                creature.conditions.is_empty(),
+            )
+        });
+        let Some((pos, (hits, done))) = ticked else {
+            // the creature died or logged out
+            conditioned.remove(&creature_id);
+            continue;
+        };
+        if done {
+            conditioned.remove(&creature_id);
+        }
+        for (damage, attacker_id, effect) in hits {
+            broadcast_event(
+                connections,
+                pos,
+                None,
+                ServerEvent::MagicEffect { pos, effect },
+            );
+            handle_change_health(
+                state.clone(),
+                connections,
+                data.clone(),
+                loopback_tx,
+                pos,
+                creature_id,
+                (-(damage as i32), attacker_id),
+            );
+        }
+    }
+}
+
+// a drunk creature sometimes steps somewhere else than it wanted to
+fn stagger(
+    state: Arc<Mutex<State>>,
+    from: (u16, u16, u8),
+    to: (u16, u16, u8),
+    creature_id: u32,
+    direction: Direction,
+) -> ((u16, u16, u8), Direction) {
+    let state_handle = state.lock().unwrap();
+    let drunk = match state_handle.map.get(&from) {
+        Some(tile) => tile
+            .creatures
+            .iter()
+            .any(|c| c.id == creature_id && c.has_condition(&ConditionType::Drunk)),
+        None => false,
+    };
+    if !drunk || random_range(1, 100) > DRUNK_STAGGER_CHANCE {
+        return (to, direction);
+    }
+    let staggered = match random_range(0, 3) {
+        0 => Direction::North,
+        1 => Direction::East,
+        2 => Direction::South,
+        _ => Direction::West,
+    };
+    let staggered_to = staggered.move_in_dir(from);
+    return match state_handle.map.get(&staggered_to) {
+        Some(tile) if tile.creatures.is_empty() => (staggered_to, staggered),
+        _ => (to, direction),
+    };
+}
+
+// a creature arrived on a tile
+// the actions registered for the items on pos and for the tile flags crossed between pos
+// and other_pos, item actions come with the stack position and id of their item
+fn tile_move_actions(
+    state: Arc<Mutex<State>>,
+    data: &StaticData,
+    event_type: MoveEventType,
+    pos: (u16, u16, u8),
+    other_pos: (u16, u16, u8),
+) -> Vec<(Option<(u8, Item)>, MoveAction)> {
+    let state_handle = state.lock().unwrap();
+    let Some(tile) = state_handle.map.get(&pos) else {
+        return vec![];
+    };
+    let other_flags = state_handle
+        .map
+        .get(&other_pos)
+        .map(|other| other.flags)
+        .unwrap_or(0);
+    let mut actions: Vec<(Option<(u8, Item)>, MoveAction)> = Vec::new();
+    for action in data
+        .movements
+        .flag_actions(event_type, tile.flags & !other_flags)
+    {
+        actions.push((None, action.clone()));
+    }
+    let bot_start = tile.top_items.len() + tile.creatures.len() + 1;
+    let stacked = tile
+        .top_items
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| (idx + 1, item))
+        .chain(
+            tile.bot_items
+                .iter()
+                .enumerate()
+                .map(|(idx, item)| (bot_start + idx, item)),
+        );
+    for (stack_pos, item) in stacked {
+        for action in data.movements.item_actions(event_type, item) {
+            actions.push((Some((stack_pos as u8, item.clone())), action.clone()));
+        }
+        // teleports placed in the map editor carry their own destination
+        if let Some(destination) = item.teleport_destination {
+            if event_type == MoveEventType::StepIn && is_teleport(data, item) {
+                let action = MoveAction::Teleport { destination };
+                actions.push((Some((stack_pos as u8, item.clone())), action));
+            }
+        }
+    }
+    return actions;
+}
+
+// why a step is refused, a tile that cannot be walked on or the first block action of the
+// tile being left or entered
+fn blocked_move(
+    state: Arc<Mutex<State>>,
+    data: &StaticData,
+    from: (u16, u16, u8),
+    to: (u16, u16, u8),
+) -> Option<String> {
+    let walkable = {
+        let state_handle = state.lock().unwrap();
+        state_handle.map.get(&to).is_some_and(|tile| {
+            tile.creatures.is_empty() && !tile.has_item_with_flag(&data.item_data, FLAG_BLOCK_SOLID)
+        })
+    };
+    if !walkable {
+        return Some(Cancel::NotPossible.text().to_string());
+    }
+    let leaving = tile_move_actions(state.clone(), data, MoveEventType::StepOut, from, to);
+    let entering = tile_move_actions(state, data, MoveEventType::StepIn, to, from);
+    return leaving
+        .into_iter()
+        .chain(entering)
+        .find_map(|(_, action)| match action {
+            MoveAction::Block { text } => Some(text),
+            _ => None,
+        });
+}
+
+// runs the step out actions of from or the step in actions of to
+fn on_step(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    event_type: MoveEventType,
+    (from, to): ((u16, u16, u8), (u16, u16, u8)),
+    creature_id: u32,
+) {
+    let (pos, other_pos) = match event_type {
+        MoveEventType::StepOut => (from, to),
+        _ => (to, from),
+    };
+    for (item, action) in tile_move_actions(state.clone(), &data, event_type, pos, other_pos) {
+        match action {
+            MoveAction::Transform { to_item_id } => {
+                if let Some((stack_pos, item)) = &item {
+                    let _ = loopback_tx.send(Command::TransformThing {
+                        pos,
+                        stack_pos: *stack_pos,
+                        from_item_id: item.client_id,
+                        to_item_id,
+                    });
+                }
+            }
+            MoveAction::Teleport { destination } => {
+                let _ = loopback_tx.send(Command::TeleportCreature {
+                    creature_id,
+                    to: destination,
+                });
+            }
+            MoveAction::Message { text } => {
+                if let Some((_, tx)) = connections.get(&creature_id) {
+                    let _ = tx.send(ServerEvent::TextMessage {
+                        message_type: MessageType::InfoDescription as u8,
+                        text,
+                    });
+                }
+            }
+            MoveAction::Field => {
+                let field = item
+                    .as_ref()
+                    .and_then(|(_, item)| data.item_data.get(&item.client_id))
+                    .and_then(|item_data| item_data.field.clone());
+                if let Some(field) = field {
+                    apply_field(
+                        state.clone(),
+                        connections,
+                        data.clone(),
+                        loopback_tx,
+                        pos,
+                        creature_id,
+                        &field,
+                    );
+                }
+            }
+            MoveAction::AddCondition(spec) => {
+                let _ = loopback_tx.send(Command::AddCondition {
+                    creature_id,
+                    condition: spec.create(),
+                });
+            }
+            MoveAction::RemoveCondition { condition_type } => {
+                let _ = loopback_tx.send(Command::RemoveCondition {
+                    creature_id,
+                    condition_type,
+                });
+            }
+            MoveAction::Script { script } => {
+                let item_arg = match &item {
+                    Some((stack_pos, item)) => item_arg(item, pos, *stack_pos),
+                    None => Dynamic::UNIT,
+                };
+                run_script(
+                    data.clone(),
+                    loopback_tx,
+                    &script,
+                    event_type.script_function(),
+                    vec![creature_arg(creature_id, to), item_arg],
+                );
+            }
+            // checked before the step is made
+            MoveAction::Block { .. } => {}
+        }
+    }
+}
+
+fn apply_field(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    pos: (u16, u16, u8),
+    creature_id: u32,
+    field: &FieldEffect,
+) {
+    let event = ServerEvent::MagicEffect {
+        pos,
+        effect: field.condition.magic_effect(),
+    };
+    broadcast_event(connections, pos, None, event);
+    if field.damage > 0 {
+        handle_change_health(
+            state,
+            connections,
+            data,
+            loopback_tx,
+            pos,
+            creature_id,
+            (-(field.damage as i32), None),
+        );
+    }
+    if field.ticks > 0 {
+        let _ = loopback_tx.send(Command::AddCondition {
+            creature_id,
+            condition: Condition::damage(
+                field.condition.clone(),
+                field.tick_damage,
+                field.ticks,
+                None,
+            ),
+        });
+    }
+}
+
+fn is_teleport(data: &StaticData, item: &Item) -> bool {
+    return match data.item_data.get(&item.client_id) {
+        Some(item_data) => matches!(item_data.item_type, ItemType::Teleport),
+        None => false,
+    };
+}
+
+fn is_magic_field(data: &StaticData, item: &Item) -> bool {
+    return match data.item_data.get(&item.client_id) {
+        Some(item_data) => {
+            matches!(item_data.item_type, ItemType::Magicfield) || item_data.field.is_some()
+        }
+        None => false,
+    };
+}
+
+// a new field replaces the one on the tile and hits everyone standing there
+fn place_field(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    pos: (u16, u16, u8),
+    field_id: u16,
+) {
+    let mut field = Item::new(field_id);
+    let standing: Vec<u32> = {
+        let mut state_handle = state.lock().unwrap();
+        field.instance_id = new_instance_id(&mut state_handle);
+        let Some(tile) = state_handle.map.get_mut(&pos) else {
+            return;
+        };
+        if tile.has_item_with_flag(&data.item_data, FLAG_BLOCK_SOLID) {
+            return;
+        }
+        while let Some(stack_pos) = tile.find_stack_pos(|item| is_magic_field(&data, item)) {
+            tile.remove_at_stack_pos(stack_pos);
+            broadcast_event(
+                connections,
+                pos,
+                None,
+                ServerEvent::ItemRemoved { pos, stack_pos },
+            );
+        }
+        tile.add_item(field.clone(), &data.item_data);
+        broadcast_event(
+            connections,
+            pos,
+            None,
+            ServerEvent::ItemAdded {
+                pos,
+                item: field.clone(),
+            },
+        );
+        tile.creatures.iter().map(|creature| creature.id).collect()
+    };
+    start_decay(loopback_tx, &data, pos, &field);
+    if let Some(field) = data.item_data.get(&field_id).and_then(|d| d.field.as_ref()) {
+        for creature_id in standing {
+            apply_field(
+                state.clone(),
+                connections,
+                data.clone(),
+                loopback_tx,
+                pos,
+                creature_id,
+                field,
+            );
+        }
+    }
+}
+
+// items get their own instance id before they are placed, so that only that item decays
+fn new_instance_id(state_handle: &mut State) -> u32 {
+    let instance_id = state_handle.next_instance_id;
+    state_handle.next_instance_id += 1;
+    return instance_id;
+}
+
+fn start_decay(loopback_tx: &Sender<Command>, data: &StaticData, pos: (u16, u16, u8), item: &Item) {
+    if let Some(item_data) = data.item_data.get(&item.client_id) {
+        if item_data.decay_time > 0 {
+            schedule_command(
+                loopback_tx,
+                Duration::from_secs(item_data.decay_time as u64),
+                Command::DecayItem {
+                    pos,
+                    client_id: item.client_id,
+                    instance_id: item.instance_id,
+                },
+            );
+        }
+    }
+}
+
+fn handle_decay_item(
+    state: Arc<Mutex<State>>,
+    connections: &Connections,
+    data: Arc<StaticData>,
+    loopback_tx: &Sender<Command>,
+    pos: (u16, u16, u8),
+    (client_id, instance_id): (u16, u32),
+) {
+    let Some(decay_to) = data.item_data.get(&client_id).map(|d| d.decay_to) else {
+        return;
+    };
+    let mut state_handle = state.lock().unwrap();
+    let Some(tile) = state_handle.map.get_mut(&pos) else {
+        return;
+    };
+    // the item may have been moved or replaced in the meantime
+    let Some(stack_pos) =
+        tile.find_stack_pos(|item| item.instance_id == instance_id && item.client_id == client_id)
+    else {
+        return;
+    };
+    if decay_to == 0 {
+        tile.remove_at_stack_pos(stack_pos);
+        let event = ServerEvent::ItemRemoved { pos, stack_pos };
+        broadcast_event(connections, pos, None, event);
+        return;
+    }
+    tile.change_at_stack_pos(stack_pos, decay_to);
+    let Some(item) = tile.get_item_at_stack_pos(stack_pos) else {
+        return;
+    };
+    start_decay(loopback_tx, &data, pos, &item);
+    let event = ServerEvent::ThingTransformed {
+        pos,
+        stack_pos,
+        item,
+    };
+    broadcast_event(connections, pos, None, event);
+}
@@ -0,0 +1,142 @@
+/*
+*   fluid containers and splashes keep their fluid as the item subtype, the client only knows
+*   eight fluid colors so every fluid is sent as its subtype modulo 8
+*       using a fluid container on yourself drinks it and leaves it empty
+*       using it on a tile pours the fluid out as a splash that decays
+*       using an empty one on a fluid source like water fills it
+*/
+
+// the pool left on the ground when a fluid is poured out
+pub const SPLASH: u16 = 2886;
+
+pub const MANA_FLUID_MIN: i32 = 50;
+pub const MANA_FLUID_MAX: i32 = 150;
+pub const LIFE_FLUID_MIN: i32 = 40;
+pub const LIFE_FLUID_MAX: i32 = 75;
+// seconds a drink of beer or wine makes the drinker drunk
+pub const DRUNK_DURATION: u64 = 120;
+// slime poisons whoever drinks it
+pub const SLIME_POISON_DAMAGE: u16 = 5;
+pub const SLIME_POISON_TICKS: u32 = 10;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FluidType {
+    Empty = 0,
+    Water = 1,
+    Blood = 2,
+    Beer = 3,
+    Slime = 4,
+    Lemonade = 5,
+    Milk = 6,
+    Mana = 7,
+    Life = 10,
+    Oil = 11,
+    Urine = 13,
+    CoconutMilk = 14,
+    Wine = 15,
+    Mud = 19,
+    FruitJuice = 21,
+}
+
+impl FluidType {
+    pub fn from_u8(byte: u8) -> FluidType {
+        return match byte {
+            1 => FluidType::Water,
+            2 => FluidType::Blood,
+            3 => FluidType::Beer,
+            4 => FluidType::Slime,
+            5 => FluidType::Lemonade,
+            6 => FluidType::Milk,
+            7 => FluidType::Mana,
+            10 => FluidType::Life,
+            11 => FluidType::Oil,
+            13 => FluidType::Urine,
+            14 => FluidType::CoconutMilk,
+            15 => FluidType::Wine,
+            19 => FluidType::Mud,
+            21 => FluidType::FruitJuice,
+            _ => FluidType::Empty,
+        };
+    }
+
+    pub fn from_name(name: &str) -> Option<FluidType> {
+        return match name {
+            "water" => Some(FluidType::Water),
+            "blood" => Some(FluidType::Blood),
+            "beer" => Some(FluidType::Beer),
+            "slime" => Some(FluidType::Slime),
+            "lemonade" => Some(FluidType::Lemonade),
+            "milk" => Some(FluidType::Milk),
+            "mana" => Some(FluidType::Mana),
+            "life" => Some(FluidType::Life),
+            "oil" => Some(FluidType::Oil),
+            "urine" => Some(FluidType::Urine),
+            "coconutmilk" => Some(FluidType::CoconutMilk),
+            "wine" => Some(FluidType::Wine),
+            "mud" => Some(FluidType::Mud),
+            "fruitjuice" => Some(FluidType::FruitJuice),
+            _ => None,
+        };
+    }
+
+    pub fn name(&self) -> &str {
+        return match self {
+            FluidType::Empty => "nothing",
+            FluidType::Water => "water",
+            FluidType::Blood => "blood",
+            FluidType::Beer => "beer",
+            FluidType::Slime => "slime",
+            FluidType::Lemonade => "lemonade",
+            FluidType::Milk => "milk",
+            FluidType::Mana => "manafluid",
+            FluidType::Life => "lifefluid",
+            FluidType::Oil => "oil",
+            FluidType::Urine => "urine",
+            FluidType::CoconutMilk => "coconut milk",
+            FluidType::Wine => "wine",
+            FluidType::Mud => "mud",
+            FluidType::FruitJuice => "fruit juice",
+        };
+    }
+
+    // what the drinker says
+    pub fn drink_text(&self) -> &str {
+        return match self {
+            FluidType::Mana | FluidType::Life => "Aaaah...",
+            FluidType::Slime | FluidType::Urine | FluidType::Mud | FluidType::Oil => "Urgh!",
+            _ => "Gulp.",
+        };
+    }
+}
+
+pub fn fluid_color(subtype: u8) -> u8 {
+    return subtype % 8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fluids_read_back_from_their_subtype_and_name() {
+        for subtype in 0..=21 {
+            let fluid = FluidType::from_u8(subtype);
+            if fluid != FluidType::Empty {
+                assert_eq!(fluid as u8, subtype);
+            }
+        }
+        assert!(FluidType::from_u8(8) == FluidType::Empty);
+        assert!(FluidType::from_name("coconutmilk") == Some(FluidType::CoconutMilk));
+        assert!(FluidType::from_name("lava").is_none());
+        assert_eq!(FluidType::Life.name(), "lifefluid");
+        assert_eq!(FluidType::Mud.drink_text(), "Urgh!");
+        assert_eq!(FluidType::Beer.drink_text(), "Gulp.");
+    }
+
+    #[test]
+    fn fluid_color_wraps_around_the_client_colors() {
+        assert_eq!(fluid_color(FluidType::Mana as u8), 7);
+        assert_eq!(fluid_color(FluidType::Life as u8), 2);
+        assert_eq!(fluid_color(FluidType::FruitJuice as u8), 5);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{
+    connection::State,
+    item::Item,
+    map::Tile,
+    otb_io::item_loader::{FLAG_MOVEABLE, ItemData},
+    xml_io::house_loader::{HOUSES_DIRECTORY, House, HouseTiles, read_house_state, save_house},
+};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/*
+*   houses are owned by players, the owner gives others access through lists
+*       subowners   move items inside, open every door and edit the guests and door lists
+*       guests      move items inside
+*       door lists  open that door of the house
+*   the world keeps the houses in State, they are saved with the moveable items on their
+*   tiles whenever the rent is checked and when the owner or the lists change
+*
+*   a player owns one house at most, the first rent is due one rent period after buying it
+*   rent is taken from the depot of the owner in the town of the house, owners that cannot
+*   pay lose the house
+*/
+
+pub fn unix_time() -> u64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+}
+
+// day/month/year of a unix time
+pub fn format_date(unix_time: u64) -> String {
+    // days since 1970 to a civil date, years start in march so leap days come last
+    let days = (unix_time / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    return format!("{:02}/{:02}/{}", day, month, year);
+}
+
+// replaces the moveable items of house tiles with the saved ones
+pub fn load_houses(
+    map: &mut HashMap<(u16, u16, u8), Tile>,
+    houses: &mut HashMap<u32, House>,
+    item_data: &HashMap<u16, ItemData>,
+) {
+    for house in houses.values_mut() {
+        let Some(saved_tiles) = read_house_state(HOUSES_DIRECTORY, house) else {
+            continue;
+        };
+        for tile in map.values_mut().filter(|tile| tile.house_id == house.id) {
+            tile.top_items.retain(|item| !is_moveable(item, item_data));
+            tile.bot_items.retain(|item| !is_moveable(item, item_data));
+        }
+        for (pos, items) in saved_tiles {
+            let Some(tile) = map.get_mut(&pos) else {
+                continue;
+            };
+            // items are saved top to bottom, adding puts them on top
+            for item in items.into_iter().rev() {
+                tile.add_item(item, item_data);
+            }
+        }
+    }
+}
+
+fn is_moveable(item: &Item, item_data: &HashMap<u16, ItemData>) -> bool {
+    return item_data
+        .get(&item.client_id)
+        .is_some_and(|data| data.has_flag(FLAG_MOVEABLE));
+}
+
+pub fn house_items(
+    map: &HashMap<(u16, u16, u8), Tile>,
+    house_id: u32,
+    item_data: &HashMap<u16, ItemData>,
+) -> HouseTiles {
+    let mut tiles: HouseTiles = Vec::new();
+    for (pos, tile) in map.iter().filter(|(_, tile)| tile.house_id == house_id) {
+        let items: Vec<Item> = tile
+            .top_items
+            .iter()
+            .rev()
+            .chain(tile.bot_items.iter())
+            .filter(|item| is_moveable(item, item_data))
+            .cloned()
+            .collect();
+        if !items.is_empty() {
+            tiles.push((*pos, items));
+        }
+    }
+    return tiles;
+}
+
+// a copy of a house and the items on its tiles, taken to be written without the state locked
+pub type HouseSave = (House, HouseTiles);
+
+pub fn house_save(
+    state: &State,
+    house_id: u32,
+    item_data: &HashMap<u16, ItemData>,
+) -> Option<HouseSave> {
+    let house = state.houses.get(&house_id)?;
+    return Some((house.clone(), house_items(&state.map, house_id, item_data)));
+}
+
+pub fn house_saves(state: &State, item_data: &HashMap<u16, ItemData>) -> Vec<HouseSave> {
+    return state
+        .houses
+        .values()
+        .map(|house| (house.clone(), house_items(&state.map, house.id, item_data)))
+        .collect();
+}
+
+pub fn write_house_saves(saves: &[HouseSave]) {
+    for (house, tiles) in saves {
+        save_house(HOUSES_DIRECTORY, house, tiles);
+    }
+}
+
+// paid rent adds a period to what was paid before, but never to a time more than one period
+// ago, so a house left unpaid for long does not get its missed periods for free
+pub fn rent_paid_until(paid_until: u64, now: u64, period: u64) -> u64 {
+    return paid_until.max(now.saturating_sub(period)) + period;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_date_counts_from_1970() {
+        assert_eq!(format_date(0), "01/01/1970");
+        assert_eq!(format_date(946598400 + 86399), "31/12/1999");
+    }
+
+    #[test]
+    fn format_date_has_leap_days() {
+        assert_eq!(format_date(951782400), "29/02/2000");
+        assert_eq!(format_date(1709164800), "29/02/2024");
+        assert_eq!(format_date(1677628800), "01/03/2023");
+        // centuries are no leap years unless they divide by 400
+        assert_eq!(format_date(4107456000), "28/02/2100");
+        assert_eq!(format_date(4107542400), "01/03/2100");
+    }
+
+    #[test]
+    fn rent_paid_until_adds_a_period() {
+        let day = 24 * 60 * 60;
+        // paid in time, the next period follows the current one
+        assert_eq!(rent_paid_until(10 * day, 9 * day, 30 * day), 40 * day);
+        // paid late, missed periods are not given back
+        assert_eq!(rent_paid_until(10 * day, 100 * day, 30 * day), 100 * day);
+        // periods longer than the time since 1970 do not underflow
+        assert_eq!(rent_paid_until(0, day, 30 * day), 30 * day);
+    }
+}
@@ -1,13 +1,79 @@
-use crate::{event_handler::Command, otb_io::map_loader::OtbMapItem};
+use crate::{
+    fluid::FluidType,
+    house::format_date,
+    otb_io::{
+        item_loader::{FLAG_STACKABLE, ItemData, ItemType},
+        map_loader::OtbMapItem,
+    },
+};
 use std::collections::HashMap;
 
+// slots of the containers whose item data has no size
+pub const CONTAINER_CAPACITY: u8 = 20;
+
+// the most items one stack holds
+pub const MAX_STACK_COUNT: u8 = 100;
+
+// how much fits on a writeable item without a maxtextlen in items.xml
+pub const DEFAULT_MAX_TEXT_LENGTH: u16 = 512;
+
+// the container every player gets at each depot
+pub const LOCKER: u16 = 3497;
+
+pub const GOLD_COIN: u16 = 3031;
+pub const PLATINUM_COIN: u16 = 3035;
+pub const CRYSTAL_COIN: u16 = 3043;
+// coins with their worth in gold, the most valuable first
+const COINS: [(u16, u32); 3] = [(CRYSTAL_COIN, 10000), (PLATINUM_COIN, 100), (GOLD_COIN, 1)];
+
 #[derive(Clone)]
 pub struct Item {
     pub client_id: u16,
     pub items: Vec<Item>,
+    // how many items a stackable item is made of, 1 for everything else
+    pub count: u8,
+    // the fluid kind of fluid containers and splashes
+    pub subtype: u8,
+    // remaining uses of a rune
+    pub charges: u8,
+    // set in the map editor, used to find the movement and action scripts of the item
+    pub action_id: u16,
+    pub unique_id: u16,
+    // where a teleport sends the creatures stepping on it
+    pub teleport_destination: Option<(u16, u16, u8)>,
+    // doors of a house have their own access lists, 0 for everything else
+    pub house_door_id: u8,
+    // depot chests open the locker of the player for the town with this id, 0 otherwise
+    pub depot_id: u16,
+    // what is written on signs, books and letters, with who wrote it and when in unix seconds
+    pub text: String,
+    pub writer: String,
+    pub written_at: u64,
+    // given by the world to a container on the ground the first time it is opened, its
+    // windows find it by this wherever it ends up, 0 for everything else
+    pub instance_id: u32,
 }
 
 impl Item {
+    pub fn new(client_id: u16) -> Item {
+        return Item {
+            client_id,
+            items: vec![],
+            count: 1,
+            subtype: 0,
+            charges: 0,
+            action_id: 0,
+            unique_id: 0,
+            teleport_destination: None,
+            house_door_id: 0,
+            depot_id: 0,
+            text: String::new(),
+            writer: String::new(),
+            written_at: 0,
+            instance_id: 0,
+        };
+    }
+
     pub fn from_otb_map_item(
         otb_item: &OtbMapItem,
         server_id_to_client_id: &HashMap<u16, u16>,
@@ -15,6 +81,19 @@ impl Item {
         let mut item = Item {
             client_id: *server_id_to_client_id.get(&otb_item.server_id).unwrap(),
             items: vec![],
+            // the map stores stack counts and fluid kinds in the same property
+            count: otb_item.count.max(1),
+            subtype: otb_item.count,
+            charges: otb_item.charges,
+            action_id: otb_item.action_id,
+            unique_id: otb_item.unique_id,
+            teleport_destination: otb_item.teleport_destination,
+            house_door_id: otb_item.house_door_id,
+            depot_id: otb_item.depot_id,
+            text: otb_item.text.clone(),
+            writer: String::new(),
+            written_at: 0,
+            instance_id: 0,
         };
         for inner_item in otb_item.items.iter() {
             item.items
@@ -23,62 +102,397 @@ impl Item {
         return item;
     }
 
+    pub fn with_count(client_id: u16, count: u8) -> Item {
+        let mut item = Item::new(client_id);
+        item.count = count;
+        return item;
+    }
+
+    pub fn is_stackable(&self, item_data: &HashMap<u16, ItemData>) -> bool {
+        return item_data
+            .get(&self.client_id)
+            .is_some_and(|data| data.has_flag(FLAG_STACKABLE));
+    }
+
+    // whether the whole other stack fits onto this one
+    pub fn can_merge(&self, other: &Item, item_data: &HashMap<u16, ItemData>) -> bool {
+        return self.client_id == other.client_id
+            && self.is_stackable(item_data)
+            && self.count as u16 + other.count as u16 <= MAX_STACK_COUNT as u16;
+    }
+
+    // takes count items off a stackable item, None when the whole item is taken
+    pub fn split(&mut self, count: u8, item_data: &HashMap<u16, ItemData>) -> Option<Item> {
+        if !self.is_stackable(item_data) || count == 0 || count >= self.count {
+            return None;
+        }
+        self.count -= count;
+        let mut taken = self.clone();
+        taken.count = count;
+        return Some(taken);
+    }
+
+    // items placed without charges start from the default, returns whether the item is used up
+    pub fn use_charge(&mut self, default_charges: u8) -> bool {
+        if self.charges == 0 {
+            self.charges = default_charges;
+        }
+        self.charges = self.charges.saturating_sub(1);
+        return self.charges == 0;
+    }
+
+    // weight of the item and everything inside it, in hundredths of an ounce
+    pub fn weight(&self, item_data: &HashMap<u16, ItemData>) -> u32 {
+        let own = match item_data.get(&self.client_id) {
+            Some(data) if data.has_flag(FLAG_STACKABLE) => data.weight * self.count as u32,
+            Some(data) => data.weight,
+            None => 0,
+        };
+        return own
+            + self
+                .items
+                .iter()
+                .map(|item| item.weight(item_data))
+                .sum::<u32>();
+    }
+
+    // gold worth of the coins in the item and everything inside it
+    pub fn money(&self) -> u32 {
+        let own = COINS
+            .iter()
+            .find(|(coin_id, _)| *coin_id == self.client_id)
+            .map_or(0, |(_, worth)| *worth * self.count as u32);
+        return own + self.items.iter().map(|item| item.money()).sum::<u32>();
+    }
+
+    // takes coins out of the item until the amount is paid and puts the change back,
+    // nothing is taken when there is not enough money
+    pub fn take_money(&mut self, amount: u32) -> bool {
+        if self.money() < amount {
+            return false;
+        }
+        let mut taken: u32 = 0;
+        // the cheapest coins go first to keep the change small
+        for (coin_id, worth) in COINS.iter().rev() {
+            while taken < amount && self.remove_coin(*coin_id) {
+                taken += worth;
+            }
+        }
+        let mut change = taken - amount;
+        for (coin_id, worth) in COINS.iter() {
+            while change >= *worth {
+                let count = (change / worth).min(MAX_STACK_COUNT as u32) as u8;
+                self.add_item(Item::with_count(*coin_id, count));
+                change -= worth * count as u32;
+            }
+        }
+        return true;
+    }
+
+    // takes a single coin off the first stack of that coin
+    fn remove_coin(&mut self, coin_id: u16) -> bool {
+        if let Some(idx) = self.items.iter().position(|item| item.client_id == coin_id) {
+            if self.items[idx].count > 1 {
+                self.items[idx].count -= 1;
+            } else {
+                self.items.remove(idx);
+            }
+            return true;
+        }
+        return self.items.iter_mut().any(|item| item.remove_coin(coin_id));
+    }
+
     pub fn add_item(&mut self, item: Item) {
         let mut updated_items = vec![item];
         updated_items.append(&mut self.items);
         self.items = updated_items;
     }
+
+    pub fn is_full(&self, item_data: &HashMap<u16, ItemData>) -> bool {
+        return self.items.len() >= container_capacity(self.client_id, item_data) as usize;
+    }
+
+    // the container reached by taking the item at each slot of the path in turn
+    pub fn nested(&self, path: &[u8]) -> Option<&Item> {
+        let mut item = self;
+        for slot in path {
+            item = item.items.get(*slot as usize)?;
+        }
+        return Some(item);
+    }
+
+    pub fn nested_mut(&mut self, path: &[u8]) -> Option<&mut Item> {
+        let mut item = self;
+        for slot in path {
+            item = item.items.get_mut(*slot as usize)?;
+        }
+        return Some(item);
+    }
+
+    // this item or a container anywhere inside it with the instance id
+    pub fn find_instance(&self, instance_id: u32) -> Option<&Item> {
+        if self.instance_id == instance_id {
+            return Some(self);
+        }
+        return self
+            .items
+            .iter()
+            .find_map(|item| item.find_instance(instance_id));
+    }
+
+    pub fn find_instance_mut(&mut self, instance_id: u32) -> Option<&mut Item> {
+        if self.instance_id == instance_id {
+            return Some(self);
+        }
+        return self
+            .items
+            .iter_mut()
+            .find_map(|item| item.find_instance_mut(instance_id));
+    }
 }
 
-pub fn get_item_action(item_id: &u16) -> impl Fn((u16, u16, u8), u8, u16) -> Vec<Command> {
-    match item_id {
-        1644 => {
-            let func = |pos: (u16, u16, u8), stack_pos: u8, item_id: u16| {
-                vec![Command::TransformThing {
-                    pos: pos,
-                    stack_pos,
-                    from_item_id: 1644,
-                    to_item_id: 1645,
-                }]
-            };
-            func
-        } // closed door
-        1645 => {
-            let func = |pos: (u16, u16, u8), stack_pos: u8, item_id: u16| {
-                vec![Command::TransformThing {
-                    pos: pos,
-                    stack_pos,
-                    from_item_id: 1645,
-                    to_item_id: 1644,
-                }]
-            };
-            func
-        } // opened door
-        2772 => {
-            let func = |pos: (u16, u16, u8), stack_pos: u8, item_id: u16| {
-                vec![Command::TransformThing {
-                    pos: pos,
-                    stack_pos,
-                    from_item_id: 2772,
-                    to_item_id: 2773,
-                }]
-            };
-            func
-        } // switch left
-        2773 => {
-            let func = |pos: (u16, u16, u8), stack_pos: u8, item_id: u16| {
-                vec![Command::TransformThing {
-                    pos: pos,
-                    stack_pos,
-                    from_item_id: 2773,
-                    to_item_id: 2772,
-                }]
-            };
-            func
-        } // switch right
-        _ => {
-            let f = |_, _, _| vec![];
-            f
+pub fn container_capacity(client_id: u16, item_data: &HashMap<u16, ItemData>) -> u8 {
+    return match item_data.get(&client_id) {
+        Some(data) if data.container_size > 0 => data.container_size,
+        _ => CONTAINER_CAPACITY,
+    };
+}
+
+pub fn indefinite_article(name: &str) -> &str {
+    return match name.chars().next() {
+        Some('a' | 'e' | 'i' | 'o' | 'u') => "an",
+        _ => "a",
+    };
+}
+
+// the "You see ..." text of an item, the weight is only shown for items within reach
+pub fn describe_item(item: &Item, item_data: &HashMap<u16, ItemData>, in_reach: bool) -> String {
+    let Some(data) = item_data.get(&item.client_id) else {
+        return "You see nothing special.".to_string();
+    };
+    let name = &data.item_name;
+    let mut text = if data.has_flag(FLAG_STACKABLE) && item.count > 1 {
+        let plural = if data.plural.is_empty() {
+            format!("{}s", name)
+        } else {
+            data.plural.clone()
+        };
+        format!("You see {} {}", item.count, plural)
+    } else if data.article.is_empty() {
+        format!("You see {} {}", indefinite_article(name), name)
+    } else {
+        format!("You see {} {}", data.article, name)
+    };
+    if matches!(data.item_type, ItemType::Fluid | ItemType::Splash) && item.subtype != 0 {
+        text.push_str(&format!(" of {}", FluidType::from_u8(item.subtype).name()));
+    }
+    if data.attack != 0 {
+        text.push_str(&format!(" (Atk:{} Def:{})", data.attack, data.defense));
+    } else if data.defense != 0 {
+        text.push_str(&format!(" (Def:{})", data.defense));
+    } else if data.armor != 0 {
+        text.push_str(&format!(" (Arm:{})", data.armor));
+    }
+    text.push('.');
+    let weight = item.weight(item_data);
+    if in_reach && weight != 0 {
+        let weighs = if item.count > 1 {
+            "They weigh"
+        } else {
+            "It weighs"
+        };
+        text.push_str(&format!(
+            "\n{} {}.{:02} oz.",
+            weighs,
+            weight / 100,
+            weight % 100
+        ));
+    }
+    if !item.text.is_empty() {
+        if item.writer.is_empty() {
+            text.push_str(&format!("\nYou read: {}", item.text));
+        } else {
+            text.push_str(&format!(
+                "\n{} wrote on {}:\n{}",
+                item.writer,
+                format_date(item.written_at),
+                item.text
+            ));
         }
     }
+    return text;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COIN_ID: u16 = 3031;
+    const SWORD_ID: u16 = 3264;
+    const LETTER_ID: u16 = 3505;
+
+    fn test_item_data() -> HashMap<u16, ItemData> {
+        let mut coin = ItemData::new(COIN_ID, ItemType::Nothing, FLAG_STACKABLE);
+        coin.item_name = "gold coin".to_string();
+        coin.weight = 10;
+        let mut sword = ItemData::new(SWORD_ID, ItemType::Weapon, 0);
+        sword.item_name = "sword".to_string();
+        sword.weight = 3500;
+        sword.attack = 14;
+        sword.defense = 12;
+        let mut letter = ItemData::new(LETTER_ID, ItemType::Writeable, 0);
+        letter.item_name = "envelope".to_string();
+        letter.article = "an old".to_string();
+        let mut item_data = HashMap::new();
+        item_data.insert(COIN_ID, coin);
+        item_data.insert(SWORD_ID, sword);
+        item_data.insert(LETTER_ID, letter);
+        return item_data;
+    }
+
+    fn stack(client_id: u16, count: u8) -> Item {
+        let mut item = Item::new(client_id);
+        item.count = count;
+        return item;
+    }
+
+    #[test]
+    fn split_takes_part_of_a_stack() {
+        let item_data = test_item_data();
+        let mut coins = stack(COIN_ID, 50);
+        let taken = coins.split(20, &item_data).unwrap();
+        assert_eq!(taken.count, 20);
+        assert_eq!(coins.count, 30);
+    }
+
+    #[test]
+    fn split_leaves_the_stack_for_no_items_or_all_of_them() {
+        let item_data = test_item_data();
+        let mut coins = stack(COIN_ID, 50);
+        assert!(coins.split(0, &item_data).is_none());
+        assert!(coins.split(50, &item_data).is_none());
+        assert!(coins.split(80, &item_data).is_none());
+        assert_eq!(coins.count, 50);
+        let mut sword = Item::new(SWORD_ID);
+        assert!(sword.split(1, &item_data).is_none());
+    }
+
+    #[test]
+    fn can_merge_stacks_of_the_same_item_up_to_a_full_stack() {
+        let item_data = test_item_data();
+        let coins = stack(COIN_ID, 60);
+        assert!(coins.can_merge(&stack(COIN_ID, 40), &item_data));
+        assert!(!coins.can_merge(&stack(COIN_ID, 41), &item_data));
+        assert!(!coins.can_merge(&stack(SWORD_ID, 1), &item_data));
+        let sword = Item::new(SWORD_ID);
+        assert!(!sword.can_merge(&Item::new(SWORD_ID), &item_data));
+    }
+
+    #[test]
+    fn describe_item_counts_stacks_and_weighs_items_in_reach() {
+        let item_data = test_item_data();
+        assert_eq!(
+            describe_item(&stack(COIN_ID, 50), &item_data, true),
+            "You see 50 gold coins.\nThey weigh 5.00 oz."
+        );
+        assert_eq!(
+            describe_item(&stack(COIN_ID, 1), &item_data, true),
+            "You see a gold coin.\nIt weighs 0.10 oz."
+        );
+        assert_eq!(
+            describe_item(&Item::new(SWORD_ID), &item_data, false),
+            "You see a sword (Atk:14 Def:12)."
+        );
+        assert_eq!(
+            describe_item(&Item::new(1), &item_data, true),
+            "You see nothing special."
+        );
+    }
+
+    #[test]
+    fn describe_item_shows_who_wrote_a_text() {
+        let item_data = test_item_data();
+        let mut letter = Item::new(LETTER_ID);
+        letter.text = "Meet me at the depot.".to_string();
+        assert_eq!(
+            describe_item(&letter, &item_data, false),
+            "You see an old envelope.\nYou read: Meet me at the depot."
+        );
+        letter.writer = "Some Character".to_string();
+        letter.written_at = 1709164800;
+        assert_eq!(
+            describe_item(&letter, &item_data, false),
+            "You see an old envelope.\nSome Character wrote on 29/02/2024:\nMeet me at the depot."
+        );
+    }
+
+    #[test]
+    fn use_charge_starts_from_the_default_charges() {
+        let mut rune = Item::new(3198);
+        assert!(!rune.use_charge(3));
+        assert_eq!(rune.charges, 2);
+        assert!(!rune.use_charge(3));
+        assert!(rune.use_charge(3));
+        assert_eq!(rune.charges, 0);
+        let mut single = Item::new(3198);
+        assert!(single.use_charge(1));
+    }
+
+    #[test]
+    fn weight_counts_the_items_inside() {
+        let item_data = test_item_data();
+        let mut bag = Item::new(SWORD_ID);
+        bag.items.push(Item::new(COIN_ID));
+        // unknown items weigh nothing
+        bag.items.push(Item::new(9999));
+        assert_eq!(bag.weight(&item_data), 3510);
+    }
+
+    #[test]
+    fn containers_fill_up_to_their_size() {
+        let mut item_data = test_item_data();
+        item_data.get_mut(&LETTER_ID).unwrap().container_size = 2;
+        assert_eq!(container_capacity(LETTER_ID, &item_data), 2);
+        // containers without a size in the item data get the default one
+        assert_eq!(container_capacity(SWORD_ID, &item_data), CONTAINER_CAPACITY);
+        assert_eq!(container_capacity(9999, &item_data), CONTAINER_CAPACITY);
+        let mut bag = Item::new(LETTER_ID);
+        bag.add_item(Item::new(SWORD_ID));
+        assert!(!bag.is_full(&item_data));
+        bag.add_item(Item::new(SWORD_ID));
+        assert!(bag.is_full(&item_data));
+    }
+
+    #[test]
+    fn nested_follows_the_slots_of_the_path() {
+        let mut inner = Item::new(LETTER_ID);
+        inner.add_item(stack(COIN_ID, 7));
+        let mut bag = Item::new(LETTER_ID);
+        bag.add_item(inner);
+        bag.add_item(Item::new(SWORD_ID));
+        assert_eq!(bag.nested(&[]).unwrap().items.len(), 2);
+        assert_eq!(bag.nested(&[1]).unwrap().client_id, LETTER_ID);
+        assert_eq!(bag.nested(&[1, 0]).unwrap().count, 7);
+        assert!(bag.nested(&[2]).is_none());
+        assert!(bag.nested(&[0, 0]).is_none());
+        bag.nested_mut(&[1, 0]).unwrap().count = 3;
+        assert_eq!(bag.items[1].items[0].count, 3);
+    }
+
+    #[test]
+    fn find_instance_searches_nested_containers() {
+        let mut inner = Item::new(LETTER_ID);
+        inner.instance_id = 2;
+        let mut bag = Item::new(LETTER_ID);
+        bag.instance_id = 1;
+        bag.add_item(Item::new(SWORD_ID));
+        bag.add_item(inner);
+        assert_eq!(bag.find_instance(1).unwrap().items.len(), 2);
+        assert_eq!(bag.find_instance(2).unwrap().client_id, LETTER_ID);
+        assert!(bag.find_instance(3).is_none());
+        bag.find_instance_mut(2)
+            .unwrap()
+            .add_item(Item::new(SWORD_ID));
+        assert_eq!(bag.items[0].items.len(), 1);
+    }
 }
@@ -1,10 +1,17 @@
+mod condition;
 mod connection;
 mod creature;
 mod event_handler;
+mod fluid;
+mod house;
 mod map;
+mod message;
 mod otb_io;
 mod payload;
 mod item;
+mod random;
+mod script;
+mod xml_io;
 use std::{
     collections::HashMap,
     io::ErrorKind,
@@ -15,31 +22,79 @@ use std::{
 };
 
 use crate::{
-    connection::{Connection, State},
+    connection::{Connection, FIRST_MONSTER_ID, State},
     creature::{Character, create_characters},
     event_handler::{Command, ServerEvent, event_handler},
     map::{Direction, create_tile_map},
-    otb_io::item_loader::ItemData,
+    script::Scripts,
+    otb_io::{item_loader::ItemData, map_loader::OtbTown},
+    xml_io::{
+        action_loader::Actions, config_loader::Config, monster_loader::MonsterType, movement_loader::Movements,
+        quest_loader::Quest, spell_loader::{Rune, Spell}, talkaction_loader::TalkAction,
+        vocation_loader::Vocation,
+    },
 };
 use tokio::{io::AsyncWriteExt, net::TcpListener};
 
 struct StaticData {
     item_data: HashMap<u16, ItemData>,
     characters: Vec<Character>,
+    config: Config,
+    monster_types: HashMap<String, MonsterType>,
+    towns: HashMap<u32, OtbTown>,
+    vocations: HashMap<u8, Vocation>,
+    spells: Vec<Spell>,
+    runes: HashMap<u16, Rune>,
+    movements: Movements,
+    actions: Actions,
+    talkactions: Vec<TalkAction>,
+    scripts: Scripts,
+    quests: Vec<Quest>,
 }
 
 #[tokio::main]
 async fn main() {
     let mut connection_counter = 0;
-    let (item_data, server_id_to_client_id) =
+    let (mut item_data, server_id_to_client_id) =
         otb_io::item_loader::read_otb_items("./data/items.otb");
+    xml_io::items_loader::read_items("./data/items.xml", &mut item_data);
+    let map_data = otb_io::map_loader::read_file("./data/testmap.otbm");
+    let mut towns: HashMap<u32, OtbTown> = HashMap::new();
+    for town in map_data.towns.iter() {
+        towns.insert(town.id, town.clone());
+    }
+    let runes = xml_io::spell_loader::read_runes("./data/spells.xml");
+    for rune in runes.values() {
+        if let Some(rune_data) = item_data.get_mut(&rune.client_id) {
+            rune_data.charges = rune.charges;
+        }
+    }
+    let movements = xml_io::movement_loader::read_movements("./data/movements.xml", &item_data);
     let data = Arc::new(StaticData {
         item_data,
         characters: create_characters(),
+        config: xml_io::config_loader::read_config("./data/config.xml"),
+        monster_types: xml_io::monster_loader::read_monsters("./data/monsters.xml"),
+        towns,
+        vocations: xml_io::vocation_loader::read_vocations("./data/vocations.xml"),
+        spells: xml_io::spell_loader::read_spells("./data/spells.xml"),
+        runes,
+        movements,
+        actions: xml_io::action_loader::read_actions("./data/actions.xml"),
+        talkactions: xml_io::talkaction_loader::read_talkactions("./data/talkactions.xml"),
+        scripts: script::read_scripts("./data/scripts"),
+        quests: xml_io::quest_loader::read_quests("./data/quests.xml"),
     });
-    let map_data = otb_io::map_loader::read_file("./data/testmap.otbm");
-    let map = create_tile_map(&map_data, &data.item_data, &server_id_to_client_id);
-    let state = Arc::new(Mutex::new(State { map }));
+    let mut map = create_tile_map(&map_data, &data.item_data, &server_id_to_client_id);
+    let mut houses = xml_io::house_loader::read_houses("./data/testmap-house.xml");
+    house::load_houses(&mut map, &mut houses, &data.item_data);
+    let state = Arc::new(Mutex::new(State {
+        map,
+        next_creature_id: FIRST_MONSTER_ID,
+        next_instance_id: 1,
+        houses,
+    }));
+    data.scripts.attach(state.clone());
 
     let listener = TcpListener::bind("127.0.0.1:7171").await.unwrap();
     let (event_handler_in, event_handler_rx): (Sender<Command>, Receiver<Command>) =
@@ -90,13 +145,15 @@ async fn on_new_connection(connection: Connection) {
         connection.read_buffer = vec![0; 4096];
         match connection.socket.try_read(&mut connection.read_buffer) {
             Ok(0) => {
-                let _ = connection.event_handler_in.send(Command::Logout {
-                    sender_id: connection.id,
-                });
+                connection.logout();
                 break;
             }
             Err(ref e) if e.kind() == ErrorKind::WouldBlock => continue,
-            Err(e) => println!("Error in reading from socket {}", e),
+            Err(e) => {
+                println!("Error in reading from socket {}", e);
+                connection.logout();
+                break;
+            }
             Ok(_) => {
                 connection.read_idx = 0;
             }
@@ -107,9 +164,7 @@ async fn on_new_connection(connection: Connection) {
             }
             0x14 => {
                 let _ = connection.socket.shutdown().await;
-                let _ = connection.event_handler_in.send(Command::Logout {
-                    sender_id: connection.id,
-                });
+                connection.logout();
                 break;
             }
             0x0A => {
@@ -120,25 +175,27 @@ async fn on_new_connection(connection: Connection) {
             }
             0x65 => {
                 connection
-                    .handle_move_character_packets(Direction::North);
+                    .handle_move_character_packets(Direction::North)
+                    .await;
             }
             0x66 => {
                 connection
-                    .handle_move_character_packets(Direction::East);
+                    .handle_move_character_packets(Direction::East)
+                    .await;
             }
             0x67 => {
                 connection
-                    .handle_move_character_packets(Direction::South);
+                    .handle_move_character_packets(Direction::South)
+                    .await;
             }
             0x68 => {
                 connection
-                    .handle_move_character_packets(Direction::West);
+                    .handle_move_character_packets(Direction::West)
+                    .await;
             }
             0x69 => {
                 let _ = connection.socket.shutdown().await;
-                let _ = connection.event_handler_in.send(Command::Logout {
-                    sender_id: connection.id,
-                });
+                connection.logout();
                 break;
             }
             0x6F =>{
@@ -159,14 +216,35 @@ async fn on_new_connection(connection: Connection) {
             0x82 => {
                 connection.handle_use_item();
             }
+            0x83 => {
+                connection.handle_use_item_with_packet().await;
+            }
+            0x84 => {
+                connection.handle_use_item_on_creature_packet().await;
+            }
             0x87 => {
                 connection.handle_close_container().await;
             }
             0x88 => {
                 connection.handle_container_up().await;
             }
+            0x89 => {
+                connection.handle_edit_text();
+            }
+            0x8C => {
+                connection.handle_look_at().await;
+            }
             0x96 =>{
-                connection.handle_say_packet();
+                connection.handle_say_packet().await;
+            }
+            0xA1 => {
+                connection.handle_attack_packet();
+            }
+            0xF0 => {
+                connection.handle_quest_log().await;
+            }
+            0xF1 => {
+                connection.handle_quest_line().await;
             }
             _ => {
                 println!("packet id not handled");
@@ -2,10 +2,10 @@ use crate::{
     connection::State,
     creature::Creature,
     otb_io::{
-        item_loader::ItemData,
+        item_loader::{FLAG_BLOCK_PROJECTILE, ItemData},
         map_loader::{OtbMapData, OtbTile},
     },
-    payload::ServerPacketType,
+    payload::{ServerPacketType, write_item},
     item::Item
 };
 use std::vec;
@@ -17,10 +17,19 @@ use std::{
 pub const VIEWPORT_X: u16 = 8;
 pub const VIEWPORT_Y: u16 = 6;
 
+// tile flags set in the map editor
+pub const TILE_FLAG_PROTECTION_ZONE: u32 = 1 << 0;
+pub const TILE_FLAG_NO_PVP: u32 = 1 << 2;
+pub const TILE_FLAG_NO_LOGOUT: u32 = 1 << 3;
+pub const TILE_FLAG_PVP_ZONE: u32 = 1 << 4;
+
 
 #[derive(Clone)]
 pub struct Tile {
     pub floor_item_client_id: u16,
+    pub flags: u32,
+    // 0 for tiles outside of houses
+    pub house_id: u32,
     pub top_items: Vec<Item>,
     pub bot_items: Vec<Item>,
     pub creatures: Vec<Creature>,
@@ -36,6 +45,8 @@ impl Tile {
             floor_item_client_id: *server_id_to_client_id
                 .get(&otb_tile.floor_item_server_id)
                 .unwrap(),
+            flags: otb_tile.flags,
+            house_id: otb_tile.house_id,
             top_items: vec![],
             bot_items: vec![],
             creatures: vec![],
@@ -52,6 +63,9 @@ impl Tile {
         return tile;
     }
     pub fn get_item_at_stack_pos(&self, stack_pos: u8) -> Option<Item> {
+        if stack_pos == 0 {
+            return Some(Item::new(self.floor_item_client_id));
+        }
         let mut counter = 1;
         for item in self.top_items.iter() {
             if counter == stack_pos {
@@ -69,23 +83,90 @@ impl Tile {
         return None;
     }
 
+    pub fn get_creature_at_stack_pos(&self, stack_pos: u8) -> Option<&Creature> {
+        let idx = stack_pos.checked_sub(self.top_items.len() as u8 + 1)? as usize;
+        return self.creatures.get(idx);
+    }
+
     pub fn change_at_stack_pos(&mut self, stack_pos: u8, to_item_id: u16) {
-        let mut counter = 1;
-        for item in self.top_items.iter_mut() {
-            if counter == stack_pos {
-                item.client_id = to_item_id;
-                return;
-            }
-            counter += 1;
+        if stack_pos == 0 {
+            self.floor_item_client_id = to_item_id;
+            return;
         }
-        counter += self.creatures.len() as u8;
-        for item in self.bot_items.iter_mut() {
-            if counter == stack_pos {
-                item.client_id = to_item_id;
-                return;
-            }
-            counter += 1;
+        if let Some(item) = self.get_mut_item_at_stack_pos(stack_pos) {
+            item.client_id = to_item_id;
+        }
+    }
+
+    pub fn get_mut_item_at_stack_pos(&mut self, stack_pos: u8) -> Option<&mut Item> {
+        let top_count = self.top_items.len() as u8;
+        if stack_pos >= 1 && stack_pos <= top_count {
+            return self.top_items.get_mut(stack_pos as usize - 1);
+        }
+        let bot_start = top_count + self.creatures.len() as u8 + 1;
+        let idx = stack_pos.checked_sub(bot_start)? as usize;
+        return self.bot_items.get_mut(idx);
+    }
+
+    pub fn remove_at_stack_pos(&mut self, stack_pos: u8) -> Option<Item> {
+        let top_count = self.top_items.len() as u8;
+        if stack_pos >= 1 && stack_pos <= top_count {
+            return Some(self.top_items.remove(stack_pos as usize - 1));
+        }
+        let bot_start = top_count + self.creatures.len() as u8 + 1;
+        let idx = stack_pos.checked_sub(bot_start)? as usize;
+        if idx < self.bot_items.len() {
+            return Some(self.bot_items.remove(idx));
         }
+        return None;
+    }
+
+    pub fn has_item_with_flag(&self, item_data: &HashMap<u16, ItemData>, flag: u32) -> bool {
+        let has_flag = |client_id: &u16| match item_data.get(client_id) {
+            Some(data) => data.has_flag(flag),
+            None => false,
+        };
+        return has_flag(&self.floor_item_client_id)
+            || self.top_items.iter().any(|item| has_flag(&item.client_id))
+            || self.bot_items.iter().any(|item| has_flag(&item.client_id));
+    }
+
+    pub fn blocks_projectile(&self, item_data: &HashMap<u16, ItemData>) -> bool {
+        return self.has_item_with_flag(item_data, FLAG_BLOCK_PROJECTILE);
+    }
+
+    // new items go on top of their part of the stack
+    pub fn add_item(&mut self, item: Item, item_data: &HashMap<u16, ItemData>) {
+        match item_data.get(&item.client_id) {
+            Some(data) if data.top_order < 255 => self.top_items.push(item),
+            _ => self.bot_items.insert(0, item),
+        }
+    }
+
+    // an opened container lying on the tile or inside a container on it
+    pub fn find_container(&self, instance_id: u32) -> Option<&Item> {
+        return self
+            .top_items
+            .iter()
+            .chain(self.bot_items.iter())
+            .find_map(|item| item.find_instance(instance_id));
+    }
+
+    pub fn find_container_mut(&mut self, instance_id: u32) -> Option<&mut Item> {
+        return self
+            .top_items
+            .iter_mut()
+            .chain(self.bot_items.iter_mut())
+            .find_map(|item| item.find_instance_mut(instance_id));
+    }
+
+    pub fn find_stack_pos(&self, predicate: impl Fn(&Item) -> bool) -> Option<u8> {
+        if let Some(idx) = self.top_items.iter().position(&predicate) {
+            return Some(idx as u8 + 1);
+        }
+        let bot_start = self.top_items.len() + self.creatures.len() + 1;
+        let idx = self.bot_items.iter().position(&predicate)?;
+        return Some((bot_start + idx) as u8);
     }
 }
 
@@ -96,7 +177,7 @@ pub fn create_tile_map(
 ) -> HashMap<(u16, u16, u8), Tile> {
     let mut map: HashMap<(u16, u16, u8), Tile> = HashMap::new();
     for area in map_data.tile_areas.iter() {
-        for tile in area.tiles.iter() {
+        for tile in area.tiles.iter().chain(area.house_tiles.iter()) {
             let map_tile = Tile::form_otb_tile(tile, item_data, server_id_to_client_id);
             map.insert(
                 (area.x + tile.x as u16, area.y + tile.y as u16, area.z),
@@ -108,17 +189,50 @@ pub fn create_tile_map(
     return map;
 }
 
-pub fn get_tile_description(tile: &Tile) -> Vec<u8> {
+// steps along the line between two tiles of a floor, missing tiles do not block the view
+pub fn is_sight_clear(
+    map: &HashMap<(u16, u16, u8), Tile>,
+    item_data: &HashMap<u16, ItemData>,
+    from: (u16, u16, u8),
+    to: (u16, u16, u8),
+) -> bool {
+    let (mut x, mut y) = (from.0 as i32, from.1 as i32);
+    let (to_x, to_y) = (to.0 as i32, to.1 as i32);
+    let dx = (to_x - x).abs();
+    let dy = -(to_y - y).abs();
+    let step_x = if x < to_x { 1 } else { -1 };
+    let step_y = if y < to_y { 1 } else { -1 };
+    let mut error = dx + dy;
+    while x != to_x || y != to_y {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+        if let Some(tile) = map.get(&(x as u16, y as u16, from.2)) {
+            if tile.blocks_projectile(item_data) {
+                return false;
+            }
+        }
+    }
+    return true;
+}
+
+pub fn get_tile_description(tile: &Tile, item_data: &HashMap<u16, ItemData>) -> Vec<u8> {
     let mut bytes: Vec<u8> = Vec::new();
     bytes.extend_from_slice(&tile.floor_item_client_id.to_le_bytes());
     for item in &tile.top_items {
-        bytes.extend_from_slice(&item.client_id.to_le_bytes());
+        bytes.extend_from_slice(&write_item(item, item_data));
     }
     for creautre in &tile.creatures {
         bytes.extend_from_slice(&creautre.as_bytes());
     }
     for item in &tile.bot_items {
-        bytes.extend_from_slice(&item.client_id.to_le_bytes());
+        bytes.extend_from_slice(&write_item(item, item_data));
     }
     return bytes;
 }
@@ -129,23 +243,30 @@ pub fn get_map_description(
     to_x: u16,
     from_y: u16,
     to_y: u16,
-    from_z: u8,
-    to_z: u8,
+    z: u8,
+    item_data: &HashMap<u16, ItemData>,
 ) -> Vec<u8> {
     let state_handle = state.lock().unwrap();
     let mut map_description: Vec<u8> = Vec::new();
     let mut skip: i32 = -1;
-    for z in (from_z..=to_z).rev() {
+    for floor in visible_floors(z) {
+        // other floors are shifted diagonally, one tile per floor of difference
+        let offset = z as i32 - floor as i32;
         for x in from_x..=to_x {
             for y in from_y..=to_y {
-                match state_handle.map.get(&(x, y, z as u8)) {
+                let tile_pos = (
+                    (x as i32 + offset) as u16,
+                    (y as i32 + offset) as u16,
+                    floor,
+                );
+                match state_handle.map.get(&tile_pos) {
                     Some(tile) => {
                         if skip >= 0 {
                             map_description.push(skip as u8);
                             map_description.push(0xFF);
                         }
                         skip = 0;
-                        map_description.extend_from_slice(&get_tile_description(tile));
+                        map_description.extend_from_slice(&get_tile_description(tile, item_data));
                     }
                     None => {
                         skip += 1;
@@ -166,6 +287,26 @@ pub fn get_map_description(
     return map_description;
 }
 
+// the floors seen from z in the order the client reads them, the surface
+// shows everything above ground and underground two floors up and down
+pub fn visible_floors(z: u8) -> Vec<u8> {
+    if z <= 7 {
+        return (0..=7).rev().collect();
+    }
+    return (z - 2..=(z + 2).min(15)).collect();
+}
+
+// whether a player at viewer has pos on screen
+pub fn can_see(viewer: (u16, u16, u8), pos: (u16, u16, u8)) -> bool {
+    if !visible_floors(viewer.2).contains(&pos.2) {
+        return false;
+    }
+    let offset = viewer.2 as i32 - pos.2 as i32;
+    let dx = pos.0 as i32 - offset - viewer.0 as i32;
+    let dy = pos.1 as i32 - offset - viewer.1 as i32;
+    return dx.abs() < 9 && dy.abs() < 9;
+}
+
 #[derive(Clone)]
 pub enum Direction {
     North = 0,
@@ -175,6 +316,19 @@ pub enum Direction {
 }
 
 impl Direction {
+    // the direction of a step from one position to a neighbouring one, none for any other position
+    pub fn towards(from: (u16, u16, u8), to: (u16, u16, u8)) -> Option<Direction> {
+        if from.2 != to.2 {
+            return None;
+        }
+        return match (to.0 as i32 - from.0 as i32, to.1 as i32 - from.1 as i32) {
+            (0, -1) => Some(Direction::North),
+            (1, 0) => Some(Direction::East),
+            (0, 1) => Some(Direction::South),
+            (-1, 0) => Some(Direction::West),
+            _ => None,
+        };
+    }
     pub fn move_in_dir(&self, from: (u16, u16, u8)) -> (u16, u16, u8) {
         return match self {
             Direction::North => (from.0, from.1 - 1, from.2),
@@ -220,3 +374,62 @@ impl Direction {
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visible_floors_stop_at_the_surface() {
+        assert_eq!(visible_floors(7), vec![7, 6, 5, 4, 3, 2, 1, 0]);
+        assert_eq!(visible_floors(0), vec![7, 6, 5, 4, 3, 2, 1, 0]);
+        assert_eq!(visible_floors(8), vec![6, 7, 8, 9, 10]);
+        assert_eq!(visible_floors(14), vec![12, 13, 14, 15]);
+    }
+
+    #[test]
+    fn can_see_shifts_other_floors_diagonally() {
+        let viewer = (1000, 1000, 7);
+        assert!(can_see(viewer, (1008, 992, 7)));
+        assert!(!can_see(viewer, (1009, 1000, 7)));
+        // one floor up shows the tiles one step further to the bottom right
+        assert!(can_see(viewer, (1009, 1009, 6)));
+        assert!(!can_see(viewer, (992, 1000, 6)));
+        assert!(!can_see(viewer, (1000, 1000, 8)));
+    }
+
+    #[test]
+    fn towards_only_finds_neighbours_on_the_same_floor() {
+        let from = (100, 100, 7);
+        assert!(matches!(Direction::towards(from, (100, 99, 7)), Some(Direction::North)));
+        assert!(matches!(Direction::towards(from, (101, 100, 7)), Some(Direction::East)));
+        assert!(matches!(Direction::towards(from, (100, 101, 7)), Some(Direction::South)));
+        assert!(matches!(Direction::towards(from, (99, 100, 7)), Some(Direction::West)));
+        assert!(Direction::towards(from, (101, 101, 7)).is_none());
+        assert!(Direction::towards(from, (100, 102, 7)).is_none());
+        assert!(Direction::towards(from, (100, 101, 6)).is_none());
+        assert!(Direction::towards(from, from).is_none());
+    }
+
+    #[test]
+    fn find_container_looks_through_both_item_stacks() {
+        let mut chest = Item::new(2472);
+        chest.instance_id = 5;
+        let mut bag = Item::new(2853);
+        bag.instance_id = 6;
+        chest.add_item(bag);
+        let mut tile = Tile {
+            floor_item_client_id: 4526,
+            flags: 0,
+            house_id: 0,
+            top_items: vec![],
+            bot_items: vec![Item::new(3031), chest],
+            creatures: vec![],
+        };
+        assert_eq!(tile.find_container(5).unwrap().items.len(), 1);
+        assert_eq!(tile.find_container(6).unwrap().client_id, 2853);
+        assert!(tile.find_container(7).is_none());
+        tile.find_container_mut(6).unwrap().add_item(Item::new(3031));
+        assert_eq!(tile.bot_items[1].items[0].items.len(), 1);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::payload::{MessageType, text_message_payload};
+
+/*
+*   text messages of the server, the message type decides where the client shows them
+*       status small    white at the bottom of the game window, why an action failed
+*       info            green in the game window and the console, what a looked at thing is
+*       event           white in the game window and the console, advances and server events
+*       warning         red in the game window and the console
+*       console         only in the default channel of the console
+*   failed actions send one of the standard cancel reasons as a status small message
+*/
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cancel {
+    NotPossible,
+    NotEnoughRoom,
+    CannotThrowThere,
+    TooFarAway,
+    OutOfRange,
+    NotMoveable,
+    CannotUseObject,
+    IsEmpty,
+    TooHeavy,
+    CannotDressThere,
+    BothHandsNeedFree,
+    DropTwoHandedFirst,
+    ContainerFull,
+    ThisIsImpossible,
+}
+
+impl Cancel {
+    pub fn text(&self) -> &str {
+        return match self {
+            Cancel::NotPossible => "Sorry, not possible.",
+            Cancel::NotEnoughRoom => "There is not enough room.",
+            Cancel::CannotThrowThere => "You cannot throw there.",
+            Cancel::TooFarAway => "Too far away.",
+            Cancel::OutOfRange => "Destination is out of range.",
+            Cancel::NotMoveable => "You cannot move this object.",
+            Cancel::CannotUseObject => "You cannot use this object.",
+            Cancel::IsEmpty => "It is empty.",
+            Cancel::TooHeavy => "This object is too heavy.",
+            Cancel::CannotDressThere => "You cannot dress this object there.",
+            Cancel::BothHandsNeedFree => "Both hands need to be free.",
+            Cancel::DropTwoHandedFirst => "Drop the double-handed object first.",
+            Cancel::ContainerFull => "You cannot put more objects in this container.",
+            Cancel::ThisIsImpossible => "This is impossible.",
+        };
+    }
+}
+
+pub fn message_payload(message_type: MessageType, text: &str) -> Vec<u8> {
+    return text_message_payload(message_type as u8, text);
+}
+
+pub fn cancel_payload(cancel: Cancel) -> Vec<u8> {
+    return message_payload(MessageType::StatusSmall, cancel.text());
+}
@@ -1,5 +1,10 @@
-use crate::otb_io::{
-    OTB_BLOCK_START, is_otb_block_end, read_str_otb, read_u8_otb, read_u16_le_otb,
+use crate::{
+    creature::SKILL_COUNT,
+    otb_io::{
+        OTB_BLOCK_START, is_otb_block_end, read_str_otb, read_u8_otb, read_u16_le_otb,
+        read_u32_le_otb,
+    },
+    xml_io::items_loader::FieldEffect,
 };
 use std::collections::HashMap;
 
@@ -24,8 +29,22 @@ use std::collections::HashMap;
 const ITEM_SERVER_ID_ATTR: u8 = 0x10;
 const ITEM_CLIENT_ID_ATTR: u8 = 0x11;
 const ITEM_NAME_ATTR: u8 = 0x12;
+const ITEM_SPEED_ATTR: u8 = 0x14;
+// how many items fit in a container
+const ITEM_MAX_ITEMS_ATTR: u8 = 0x16;
+// a double in ounces
+const ITEM_WEIGHT_ATTR: u8 = 0x17;
 const ITEM_TOP_ORDER: u8 = 0x2B;
 
+// item flags stored right after the item type
+pub const FLAG_BLOCK_SOLID: u32 = 1 << 0;
+pub const FLAG_BLOCK_PROJECTILE: u32 = 1 << 1;
+pub const FLAG_MOVEABLE: u32 = 1 << 6;
+pub const FLAG_STACKABLE: u32 = 1 << 7;
+pub const FLAG_READABLE: u32 = 1 << 14;
+pub const FLAG_ALLOW_DISTANCE_READ: u32 = 1 << 20;
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum ItemType {
     Nothing = 0,
     Ground = 1,
@@ -43,6 +62,39 @@ pub enum ItemType {
     Last = 13, // Not sure what is that
 }
 
+// where an item is worn, anything can be held in the hands and the ammo slot
+#[derive(Clone, Copy, PartialEq)]
+pub enum SlotType {
+    Hand,
+    TwoHanded,
+    Head,
+    Necklace,
+    Backpack,
+    Body,
+    Legs,
+    Feet,
+    Ring,
+    Ammo,
+}
+
+impl SlotType {
+    pub fn from_name(name: &str) -> Option<SlotType> {
+        return match name {
+            "hand" => Some(SlotType::Hand),
+            "two-handed" => Some(SlotType::TwoHanded),
+            "head" => Some(SlotType::Head),
+            "necklace" => Some(SlotType::Necklace),
+            "backpack" => Some(SlotType::Backpack),
+            "body" => Some(SlotType::Body),
+            "legs" => Some(SlotType::Legs),
+            "feet" => Some(SlotType::Feet),
+            "ring" => Some(SlotType::Ring),
+            "ammo" => Some(SlotType::Ammo),
+            _ => None,
+        };
+    }
+}
+
 impl ItemType {
     pub fn from_u8(byte: u8) -> ItemType {
         match byte {
@@ -73,6 +125,79 @@ pub struct ItemData {
     pub attributes: Vec<u8>,
     pub item_name: String,
     pub top_order: u8,
+    // ground speed, 0 for items that are not ground
+    pub speed: u16,
+    // set from items.xml, a decay time of 0 means the item never decays
+    pub decay_to: u16,
+    pub decay_time: u32,
+    pub field: Option<FieldEffect>,
+    // in hundredths of an ounce, items.xml overrides the items.otb weight
+    pub weight: u32,
+    // slots of a container, 0 when neither items.otb nor items.xml has a size
+    pub container_size: u8,
+    // the charges runes are made with, set from spells.xml
+    pub charges: u8,
+    // the fluid empty containers used on this item are filled with, 0 for none
+    pub fluid_source: u8,
+    // the longest text that can be written on a writeable item, set from items.xml
+    pub max_text_length: u16,
+    // how the item is described when looked at, set from items.xml
+    pub article: String,
+    pub plural: String,
+    pub attack: u16,
+    pub defense: u16,
+    pub armor: u16,
+    // the skill fighting with the item trains and the 0 based distance effect of its shots,
+    // set from items.xml, items without a skill are no weapons
+    pub weapon_skill: Option<usize>,
+    pub shoot_effect: Option<u8>,
+    // where the item is worn and what it gives while it is, set from items.xml, items it does
+    // not give a slot type only fit the hands and the ammo slot
+    pub slot_type: Option<SlotType>,
+    pub speed_bonus: i32,
+    pub light_level: u8,
+    pub light_color: u8,
+    pub skill_boosts: [u16; SKILL_COUNT],
+}
+
+impl ItemData {
+    // an item as items.otb describes it before items.xml adds to it
+    pub fn new(client_id: u16, item_type: ItemType, flags: u32) -> ItemData {
+        return ItemData {
+            server_id: 0,
+            client_id,
+            item_type,
+            flags,
+            attributes: vec![],
+            item_name: String::new(),
+            top_order: 255,
+            speed: 0,
+            decay_to: 0,
+            decay_time: 0,
+            field: None,
+            weight: 0,
+            container_size: 0,
+            charges: 0,
+            fluid_source: 0,
+            max_text_length: 0,
+            article: String::new(),
+            plural: String::new(),
+            attack: 0,
+            defense: 0,
+            armor: 0,
+            weapon_skill: None,
+            shoot_effect: None,
+            slot_type: None,
+            speed_bonus: 0,
+            light_level: 0,
+            light_color: 0,
+            skill_boosts: [0; SKILL_COUNT],
+        };
+    }
+
+    pub fn has_flag(&self, flag: u32) -> bool {
+        return self.flags & flag == flag;
+    }
 }
 
 pub fn read_otb_items(filepath: &str) -> (HashMap<u16, ItemData>, HashMap<u16, u16>) {
@@ -111,12 +236,14 @@ pub fn read_otb_items(filepath: &str) -> (HashMap<u16, ItemData>, HashMap<u16, u
 
 fn parse_item_block(idx: &mut usize, bytes: &[u8]) -> ItemData {
     let item_type = ItemType::from_u8(read_u8_otb(idx, bytes));
-    // skip flags for now
-    *idx += 4;
+    let flags = read_u32_le_otb(idx, bytes);
     let mut server_id: u16 = 0;
     let mut client_id: u16 = 0;
     let mut item_name: String = "".to_string();
     let mut top_order: u8 = 255;
+    let mut speed: u16 = 0;
+    let mut weight: u32 = 0;
+    let mut container_size: u8 = 0;
     let mut item_byte: Vec<u8> = Vec::new();
     loop {
         if is_otb_block_end(*idx, bytes) {
@@ -149,6 +276,35 @@ fn parse_item_block(idx: &mut usize, bytes: &[u8]) -> ItemData {
                 item_name = read_str_otb(idx, bytes);
                 item_byte.extend_from_slice(&item_name.as_bytes());
             }
+            ITEM_SPEED_ATTR => {
+                if speed != 0 {
+                    continue;
+                }
+                *idx += 2;
+                speed = read_u16_le_otb(idx, bytes);
+                item_byte.extend_from_slice(&speed.to_le_bytes());
+            }
+            ITEM_MAX_ITEMS_ATTR => {
+                if container_size != 0 {
+                    continue;
+                }
+                *idx += 2;
+                let max_items = read_u16_le_otb(idx, bytes);
+                container_size = max_items.min(u8::MAX as u16) as u8;
+                item_byte.extend_from_slice(&max_items.to_le_bytes());
+            }
+            ITEM_WEIGHT_ATTR => {
+                if weight != 0 {
+                    continue;
+                }
+                *idx += 2;
+                let mut ounces = [0u8; 8];
+                for byte in ounces.iter_mut() {
+                    *byte = read_u8_otb(idx, bytes);
+                }
+                weight = (f64::from_le_bytes(ounces) * 100.0).round() as u32;
+                item_byte.extend_from_slice(&ounces);
+            }
             ITEM_TOP_ORDER => {
                 top_order = read_u8_otb(idx, bytes);
                 item_byte.push(top_order);
@@ -158,11 +314,55 @@ fn parse_item_block(idx: &mut usize, bytes: &[u8]) -> ItemData {
     }
     return ItemData {
         server_id,
-        client_id,
-        item_type,
         top_order,
         item_name,
-        flags: 0,
-        attributes: vec![],
+        speed,
+        weight,
+        container_size,
+        ..ItemData::new(client_id, item_type, flags)
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::otb_io::OTB_BLOCK_END;
+
+    // an item block after its block start byte, attributes have a u16 length before the data
+    fn item_block(attributes: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = vec![0];
+        bytes.extend_from_slice(&FLAG_STACKABLE.to_le_bytes());
+        for (attribute, data) in attributes {
+            bytes.push(*attribute);
+            bytes.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes.push(OTB_BLOCK_END);
+        return bytes;
+    }
+
+    #[test]
+    fn parse_item_block_reads_the_weight_in_hundredths_of_an_ounce() {
+        let bytes = item_block(&[
+            (ITEM_CLIENT_ID_ATTR, 3031u16.to_le_bytes().to_vec()),
+            (ITEM_WEIGHT_ATTR, 0.1f64.to_le_bytes().to_vec()),
+        ]);
+        let mut idx = 0;
+        let data = parse_item_block(&mut idx, &bytes);
+        assert_eq!(data.client_id, 3031);
+        assert_eq!(data.weight, 10);
+        assert!(data.has_flag(FLAG_STACKABLE));
+        assert_eq!(idx, bytes.len());
+    }
+
+    #[test]
+    fn parse_item_block_reads_the_container_size() {
+        let bytes = item_block(&[
+            (ITEM_CLIENT_ID_ATTR, 2854u16.to_le_bytes().to_vec()),
+            (ITEM_MAX_ITEMS_ATTR, 20u16.to_le_bytes().to_vec()),
+        ]);
+        assert_eq!(parse_item_block(&mut 0, &bytes).container_size, 20);
+        let bytes = item_block(&[(ITEM_MAX_ITEMS_ATTR, 300u16.to_le_bytes().to_vec())]);
+        assert_eq!(parse_item_block(&mut 0, &bytes).container_size, u8::MAX);
+    }
+}
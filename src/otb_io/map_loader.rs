@@ -1,6 +1,6 @@
 use crate::otb_io::{
-    OTB_BLOCK_START, is_otb_block_end, read_u8_otb, read_u16_le_otb,
-    skip_otb_block,
+    OTB_BLOCK_START, is_otb_block_end, read_str_otb, read_u8_otb, read_u16_le_otb,
+    read_u32_le_otb, skip_otb_block,
 };
 
 /*
@@ -26,11 +26,11 @@ use crate::otb_io::{
 *           z 1 byte
 *   towns 0x0C
 *       town 0x0D
-*           id 2 bytes
+*           id 4 bytes
 *           name N bytes
 *           temple position x 2 bytes
 *           temple position y 2 bytes
-*           temple position y 1 bytes
+*           temple position z 1 byte
 *   tile area 0x04
 *       x 2 bytes
 *       y 2 bytes
@@ -44,10 +44,11 @@ use crate::otb_io::{
 *               additional properties N bytes
 *               nested item 0x06
 *       housetile 0x0E
-*           x 2 bytes
-*           y 2 bytes
-*           id 4 bytes
+*           x 1 byte relative to tile area
+*           y 1 byte relative to tile area
+*           house id 4 bytes
 *           additional properties N bytes
+*           item 0x06
 *
 * additional properties
 *    0x01 DESCRIPTION
@@ -69,16 +70,27 @@ use crate::otb_io::{
 const MAP_DATA_BLOCK_START: u8 = 0x02;
 const WAYPOINTS_BLOCK_START: u8 = 0x0F;
 const TOWNS_BLOCK_START: u8 = 0x0C;
+const TOWN_BLOCK_START: u8 = 0x0D;
 const TILE_AREA_BLOCK_START: u8 = 0x04;
 const TILE_BLOCK_START: u8 = 0x05;
 const ITEM_BLOCK_START: u8 = 0x06;
-const HOUSE_TILE_BLOCK_START: u8 = 0x06;
+const HOUSE_TILE_BLOCK_START: u8 = 0x0E;
+const TILE_FLAGS_PROPERTY: u8 = 0x03;
 const TILE_SPRITE_PROPERTY: u8 = 0x09;
+const DESCRIPTION_PROPERTY: u8 = 0x01;
+const ACTION_ID_PROPERTY: u8 = 0x04;
+const UNIQUE_ID_PROPERTY: u8 = 0x05;
+const TEXT_PROPERTY: u8 = 0x06;
+const TELE_DEST_PROPERTY: u8 = 0x08;
+const DEPOT_ID_PROPERTY: u8 = 0x0A;
+const HOUSE_DOOR_ID_PROPERTY: u8 = 0x0E;
+const COUNT_PROPERTY: u8 = 0x0F;
+const RUNE_CHARGES_PROPERTY: u8 = 0x16;
 
 pub struct OtbMapData {
     pub attrs: Vec<u8>,
     pub waypoints: Vec<u8>,
-    pub towns: Vec<u8>,
+    pub towns: Vec<OtbTown>,
     pub tile_areas: Vec<OtbTileArea>,
 }
 
@@ -90,12 +102,22 @@ pub struct OtbTileArea {
     pub house_tiles: Vec<OtbTile>,
 }
 
+#[derive(Clone)]
+pub struct OtbTown {
+    pub id: u32,
+    pub name: String,
+    pub temple_pos: (u16, u16, u8),
+}
+
 #[derive(Clone)]
 pub struct OtbTile {
     pub x: u8,
     pub y: u8,
     pub floor_item_server_id: u16,
+    pub flags: u32,
     pub items: Vec<OtbMapItem>,
+    // 0 for tiles outside of houses
+    pub house_id: u32,
 }
 
 
@@ -103,6 +125,15 @@ pub struct OtbTile {
 pub struct OtbMapItem {
     pub server_id: u16,
     pub items: Vec<OtbMapItem>,
+    pub charges: u8,
+    // stack count or fluid kind
+    pub count: u8,
+    pub action_id: u16,
+    pub unique_id: u16,
+    pub teleport_destination: Option<(u16, u16, u8)>,
+    pub house_door_id: u8,
+    pub depot_id: u16,
+    pub text: String,
 }
 
 impl OtbMapData {
@@ -130,7 +161,7 @@ pub fn read_file(filepath: &str) -> OtbMapData {
         }
     }
     let mut tile_areas: Vec<OtbTileArea> = Vec::new();
-    let towns: Vec<u8> = Vec::new();
+    let mut towns: Vec<OtbTown> = Vec::new();
     let waypoints: Vec<u8> = Vec::new();
     loop {
         if is_otb_block_end(idx, &bytes) {
@@ -144,7 +175,7 @@ pub fn read_file(filepath: &str) -> OtbMapData {
                     skip_otb_block(&mut idx, &bytes);
                 }
                 TOWNS_BLOCK_START => {
-                    skip_otb_block(&mut idx, &bytes);
+                    towns = parse_towns(&bytes, &mut idx);
                 }
                 TILE_AREA_BLOCK_START => {
                     let tile_area = parse_tile_area(&bytes, &mut idx);
@@ -165,10 +196,42 @@ pub fn read_file(filepath: &str) -> OtbMapData {
     };
 }
 
+pub fn parse_towns(bytes: &[u8], idx: &mut usize) -> Vec<OtbTown> {
+    let mut towns: Vec<OtbTown> = Vec::new();
+    loop {
+        if is_otb_block_end(*idx, bytes) {
+            *idx += 1;
+            break;
+        }
+        let next = read_u8_otb(idx, bytes);
+        if next == OTB_BLOCK_START {
+            let block_id = read_u8_otb(idx, bytes);
+            match block_id {
+                TOWN_BLOCK_START => {
+                    let id = read_u32_le_otb(idx, bytes);
+                    let name = read_str_otb(idx, bytes);
+                    let x = read_u16_le_otb(idx, bytes);
+                    let y = read_u16_le_otb(idx, bytes);
+                    let z = read_u8_otb(idx, bytes);
+                    towns.push(OtbTown {
+                        id,
+                        name,
+                        temple_pos: (x, y, z),
+                    });
+                    skip_otb_block(idx, bytes);
+                }
+                _ => {
+                    skip_otb_block(idx, bytes);
+                }
+            }
+        }
+    }
+    return towns;
+}
 
 pub fn parse_tile_area(bytes: &Vec<u8>, idx: &mut usize) -> OtbTileArea {
     let mut tiles: Vec<OtbTile> = Vec::new();
-    let house_tiles: Vec<OtbTile> = Vec::new();
+    let mut house_tiles: Vec<OtbTile> = Vec::new();
     let x = read_u16_le_otb(idx, bytes);
     let y = read_u16_le_otb(idx, bytes);
     let z = read_u8_otb(idx, bytes);
@@ -182,11 +245,12 @@ pub fn parse_tile_area(bytes: &Vec<u8>, idx: &mut usize) -> OtbTileArea {
             let block_id = read_u8_otb(idx, bytes);
             match block_id {
                 TILE_BLOCK_START => {
-                    let tile = parse_tile(&bytes, idx);
+                    let tile = parse_tile(&bytes, idx, false);
                     tiles.push(tile);
                 }
                 HOUSE_TILE_BLOCK_START => {
-                    skip_otb_block(idx, bytes);
+                    let tile = parse_tile(bytes, idx, true);
+                    house_tiles.push(tile);
                 }
                 _ => {
                     skip_otb_block(idx, bytes);
@@ -203,14 +267,27 @@ pub fn parse_tile_area(bytes: &Vec<u8>, idx: &mut usize) -> OtbTileArea {
     };
 }
 
-pub fn parse_tile(bytes: &[u8], idx: &mut usize) -> OtbTile {
+pub fn parse_tile(bytes: &[u8], idx: &mut usize, is_house_tile: bool) -> OtbTile {
     let mut items: Vec<OtbMapItem> = Vec::new();
     let x = read_u8_otb(idx, bytes);
     let y = read_u8_otb(idx, bytes);
+    let mut house_id: u32 = 0;
+    if is_house_tile {
+        house_id = read_u32_le_otb(idx, bytes);
+    }
     let mut tile_sprite_id: u16 = 0;
-    if bytes[*idx] == TILE_SPRITE_PROPERTY {
-        *idx += 1;
-        tile_sprite_id = read_u16_le_otb(idx, bytes);
+    let mut flags: u32 = 0;
+    // the tile properties come before the item nodes
+    loop {
+        if bytes[*idx] == TILE_SPRITE_PROPERTY {
+            *idx += 1;
+            tile_sprite_id = read_u16_le_otb(idx, bytes);
+        } else if bytes[*idx] == TILE_FLAGS_PROPERTY {
+            *idx += 1;
+            flags = read_u32_le_otb(idx, bytes);
+        } else {
+            break;
+        }
     }
     loop {
         if is_otb_block_end(*idx, bytes) {
@@ -236,12 +313,60 @@ pub fn parse_tile(bytes: &[u8], idx: &mut usize) -> OtbTile {
         y,
         items,
         floor_item_server_id: tile_sprite_id,
+        flags,
+        house_id,
     };
 }
 
 pub fn parse_items(bytes: &[u8], idx: &mut usize) -> OtbMapItem {
     let mut items: Vec<OtbMapItem> = Vec::new();
     let id = read_u16_le_otb(idx, bytes);
+    let mut charges: u8 = 0;
+    let mut count: u8 = 0;
+    let mut action_id: u16 = 0;
+    let mut unique_id: u16 = 0;
+    let mut teleport_destination: Option<(u16, u16, u8)> = None;
+    let mut house_door_id: u8 = 0;
+    let mut depot_id: u16 = 0;
+    let mut text = String::new();
+    // properties come before the nested item nodes
+    while bytes[*idx] != OTB_BLOCK_START && !is_otb_block_end(*idx, bytes) {
+        let property = read_u8_otb(idx, bytes);
+        match property {
+            RUNE_CHARGES_PROPERTY => {
+                charges = read_u8_otb(idx, bytes);
+            }
+            COUNT_PROPERTY => {
+                count = read_u8_otb(idx, bytes);
+            }
+            HOUSE_DOOR_ID_PROPERTY => {
+                house_door_id = read_u8_otb(idx, bytes);
+            }
+            ACTION_ID_PROPERTY => {
+                action_id = read_u16_le_otb(idx, bytes);
+            }
+            UNIQUE_ID_PROPERTY => {
+                unique_id = read_u16_le_otb(idx, bytes);
+            }
+            DEPOT_ID_PROPERTY => {
+                depot_id = read_u16_le_otb(idx, bytes);
+            }
+            TEXT_PROPERTY => {
+                text = read_str_otb(idx, bytes);
+            }
+            DESCRIPTION_PROPERTY => {
+                read_str_otb(idx, bytes);
+            }
+            TELE_DEST_PROPERTY => {
+                let x = read_u16_le_otb(idx, bytes);
+                let y = read_u16_le_otb(idx, bytes);
+                let z = read_u8_otb(idx, bytes);
+                teleport_destination = Some((x, y, z));
+            }
+            // unknown property length, let the node loop skip to the next block
+            _ => break,
+        }
+    }
     loop {
         if is_otb_block_end(*idx, bytes) {
             *idx += 1;
@@ -264,5 +389,62 @@ pub fn parse_items(bytes: &[u8], idx: &mut usize) -> OtbMapItem {
     return OtbMapItem {
         server_id: id,
         items,
+        charges,
+        count,
+        action_id,
+        unique_id,
+        teleport_destination,
+        house_door_id,
+        depot_id,
+        text,
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::otb_io::OTB_BLOCK_END;
+
+    #[test]
+    fn parse_items_reads_the_teleport_destination() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend_from_slice(&1387u16.to_le_bytes());
+        bytes.push(TELE_DEST_PROPERTY);
+        bytes.extend_from_slice(&1000u16.to_le_bytes());
+        bytes.extend_from_slice(&1010u16.to_le_bytes());
+        bytes.push(7);
+        bytes.push(ACTION_ID_PROPERTY);
+        bytes.extend_from_slice(&1001u16.to_le_bytes());
+        bytes.push(OTB_BLOCK_END);
+        let mut idx = 0;
+        let item = parse_items(&bytes, &mut idx);
+        assert_eq!(item.server_id, 1387);
+        assert_eq!(item.teleport_destination, Some((1000, 1010, 7)));
+        assert_eq!(item.action_id, 1001);
+        assert_eq!(idx, bytes.len());
+    }
+
+    #[test]
+    fn parse_items_reads_the_depot_id() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend_from_slice(&3498u16.to_le_bytes());
+        bytes.push(DEPOT_ID_PROPERTY);
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.push(OTB_BLOCK_END);
+        let item = parse_items(&bytes, &mut 0);
+        assert_eq!(item.depot_id, 2);
+        assert_eq!(item.teleport_destination, None);
+    }
+
+    #[test]
+    fn parse_items_reads_the_text() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend_from_slice(&1811u16.to_le_bytes());
+        bytes.push(TEXT_PROPERTY);
+        bytes.extend_from_slice(&7u16.to_le_bytes());
+        bytes.extend_from_slice(b"Welcome");
+        bytes.push(OTB_BLOCK_END);
+        let item = parse_items(&bytes, &mut 0);
+        assert_eq!(item.text, "Welcome");
+    }
+}
@@ -26,6 +26,12 @@ pub fn read_u16_le_otb(idx: &mut usize, bytes: &[u8]) -> u16 {
     return word;
 }
 
+pub fn read_u32_le_otb(idx: &mut usize, bytes: &[u8]) -> u32 {
+    let first = read_u16_le_otb(idx, bytes);
+    let second = read_u16_le_otb(idx, bytes);
+    return first as u32 | ((second as u32) << 16);
+}
+
 
 pub fn is_otb_block_end(idx: usize, bytes: &[u8]) -> bool {
     return bytes[idx] == OTB_BLOCK_END;
@@ -1,9 +1,16 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
     connection::{Container, State},
-    creature::{Character, Creature},
+    creature::{Character, Creature, SKILL_COUNT},
+    fluid::fluid_color,
+    item::Item,
     map::{Direction, VIEWPORT_X, VIEWPORT_Y, get_map_description},
+    otb_io::item_loader::{FLAG_STACKABLE, ItemData, ItemType},
+    xml_io::vocation_loader::Vocation,
 };
 
 const GAME_WORLD_IP: [u8; 4] = [127, 0, 0, 1];
@@ -15,7 +22,10 @@ pub enum LoginPacketType {
 }
 
 pub enum MagicEffect {
+    DrawBlood = 0x00,
+    LoseEnergy = 0x01,
     Puff = 0x02,
+    BlockHit = 0x03,
     EnergyArea = 0x0A,
 }
 
@@ -35,15 +45,53 @@ pub enum ServerPacketType {
     OpenContainer = 0x6E,
     CloseContainer = 0x6F,
     AddItemToContainer = 0x70,
+    UpdateContainerItem = 0x71,
     RemoveItemFromContainer = 0x72,
     AddItemToInventory = 0x78,
     RemoveItemFromInventory = 0x79,
     MagicEffect = 0x83,
+    DistanceEffect = 0x85,
+    CreatureHealth = 0x8C,
+    CreatureLight = 0x8D,
+    CreatureOutfit = 0x8E,
+    TextWindow = 0x96,
+    CreatureSpeed = 0x8F,
+    PlayerStats = 0xA0,
+    PlayerSkills = 0xA1,
+    PlayerIcons = 0xA2,
+    CancelTarget = 0xA3,
+    TextMessage = 0xB4,
+    CancelWalk = 0xB5,
+    QuestLog = 0xF0,
+    QuestLine = 0xF1,
     CreatureSpeak = 0xAA,
 }
 
+// where the client shows a text message, see message.rs
+pub enum MessageType {
+    Warning = 0x12,
+    EventAdvance = 0x13,
+    EventDefault = 0x14,
+    InfoDescription = 0x16,
+    StatusSmall = 0x17,
+    StatusConsole = 0x18,
+}
+
 pub enum SpeechType {
     Say = 0x01,
+    Whisper = 0x02,
+    Yell = 0x03,
+}
+
+impl SpeechType {
+    // the types players talk with on the map, anything else is shown as said
+    pub fn from_u8(byte: u8) -> SpeechType {
+        return match byte {
+            0x02 => SpeechType::Whisper,
+            0x03 => SpeechType::Yell,
+            _ => SpeechType::Say,
+        };
+    }
 }
 
 pub fn write_str(s: &str) -> Vec<u8> {
@@ -63,6 +111,26 @@ pub fn write_position(pos: &(u16, u16, u8)) -> Vec<u8> {
     return payload;
 }
 
+// stackables are followed by their count, runes by their charges and fluids by their kind
+pub fn write_item(item: &Item, item_data: &HashMap<u16, ItemData>) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    bytes.extend_from_slice(&item.client_id.to_le_bytes());
+    let Some(data) = item_data.get(&item.client_id) else {
+        return bytes;
+    };
+    if data.has_flag(FLAG_STACKABLE) {
+        bytes.push(item.count);
+        return bytes;
+    }
+    match data.item_type {
+        ItemType::Rune if item.charges == 0 => bytes.push(data.charges),
+        ItemType::Rune => bytes.push(item.charges),
+        ItemType::Fluid | ItemType::Splash => bytes.push(fluid_color(item.subtype)),
+        _ => {}
+    }
+    return bytes;
+}
+
 pub fn creature_added_payload(pos: &(u16, u16, u8), creature: Creature) -> Vec<u8> {
     let mut payload: Vec<u8> = Vec::new();
     payload.push(ServerPacketType::AddThing as u8);
@@ -79,11 +147,29 @@ pub fn thing_moved_payload(from: &(u16, u16, u8), stack_pos: u8, to: &(u16, u16,
     payload.extend_from_slice(&write_position(to));
     return payload;
 }
-pub fn add_item_to_container_payload(client_item_id: &u16, container_id: u8) -> Vec<u8> {
+pub fn add_item_to_container_payload(
+    item: &Item,
+    container_id: u8,
+    item_data: &HashMap<u16, ItemData>,
+) -> Vec<u8> {
     let mut payload: Vec<u8> = Vec::new();
     payload.push(ServerPacketType::AddItemToContainer as u8);
     payload.push(container_id);
-    payload.extend_from_slice(&client_item_id.to_le_bytes());
+    payload.extend_from_slice(&write_item(item, item_data));
+    return payload;
+}
+
+pub fn update_container_item_payload(
+    container_id: u8,
+    slot: u8,
+    item: &Item,
+    item_data: &HashMap<u16, ItemData>,
+) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    payload.push(ServerPacketType::UpdateContainerItem as u8);
+    payload.push(container_id);
+    payload.push(slot);
+    payload.extend_from_slice(&write_item(item, item_data));
     return payload;
 }
 
@@ -95,11 +181,15 @@ pub fn remove_item_from_container_payload(container_id: u8, slot: u8) -> Vec<u8>
     return payload;
 }
 
-pub fn add_item_to_inventory_payload(client_item_id: u16, slot: u8) -> Vec<u8> {
+pub fn add_item_to_inventory_payload(
+    item: &Item,
+    slot: u8,
+    item_data: &HashMap<u16, ItemData>,
+) -> Vec<u8> {
     let mut payload: Vec<u8> = Vec::new();
     payload.push(ServerPacketType::AddItemToInventory as u8);
     payload.push(slot);
-    payload.extend_from_slice(&client_item_id.to_le_bytes());
+    payload.extend_from_slice(&write_item(item, item_data));
     return payload;
 }
 
@@ -117,14 +207,25 @@ pub fn remove_thing_payload(pos: &(u16, u16, u8), stack_pos: u8) -> Vec<u8> {
     return payload;
 }
 
-pub fn add_thing_payload(to: &(u16, u16, u8), item_id: &u16) -> Vec<u8> {
+pub fn add_thing_payload(
+    to: &(u16, u16, u8),
+    item: &Item,
+    item_data: &HashMap<u16, ItemData>,
+) -> Vec<u8> {
     let mut payload: Vec<u8> = Vec::new();
     payload.push(ServerPacketType::AddThing as u8);
     payload.extend_from_slice(&write_position(to));
-    payload.extend_from_slice(&item_id.to_le_bytes());
+    payload.extend_from_slice(&write_item(item, item_data));
     return payload;
 }
-pub fn container_payload(container: &Container, name: &str, capacity: u8, parent: u8) -> Vec<u8> {
+pub fn container_payload(
+    container: &Container,
+    items: &[Item],
+    name: &str,
+    capacity: u8,
+    parent: u8,
+    item_data: &HashMap<u16, ItemData>,
+) -> Vec<u8> {
     let mut payload: Vec<u8> = Vec::new();
     payload.push(ServerPacketType::OpenContainer as u8);
     payload.push(container.container_id);
@@ -132,9 +233,9 @@ pub fn container_payload(container: &Container, name: &str, capacity: u8, parent
     payload.extend_from_slice(&write_str(&name));
     payload.push(capacity);
     payload.push(parent);
-    payload.push(container.items.len() as u8);
-    for inner_item in container.items.iter() {
-        payload.extend_from_slice(&inner_item.client_id.to_le_bytes());
+    payload.push(items.len() as u8);
+    for inner_item in items.iter() {
+        payload.extend_from_slice(&write_item(inner_item, item_data));
     }
     return payload;
 }
@@ -150,12 +251,26 @@ pub fn enter_game_payload(
     state: Arc<Mutex<State>>,
     pos: &(u16, u16, u8),
     character_id: u32,
+    item_data: &HashMap<u16, ItemData>,
 ) -> Vec<u8> {
     let mut payload: Vec<u8> = Vec::new();
     payload.push(ServerPacketType::GameInit as u8);
     payload.extend_from_slice(&character_id.to_le_bytes());
     payload.extend_from_slice(&50u16.to_le_bytes()); // beat 
     payload.push(0); // can report bugs
+    payload.extend_from_slice(&full_map_payload(state, pos, item_data));
+    payload.push(0x82); //world light
+    payload.push(0x6F);
+    payload.push(0xD7);
+    return payload;
+}
+
+pub fn full_map_payload(
+    state: Arc<Mutex<State>>,
+    pos: &(u16, u16, u8),
+    item_data: &HashMap<u16, ItemData>,
+) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
     payload.push(ServerPacketType::FullMap as u8);
     payload.extend_from_slice(&write_position(&pos));
     payload.extend_from_slice(&get_map_description(
@@ -164,12 +279,9 @@ pub fn enter_game_payload(
         pos.0 + VIEWPORT_X + 1,
         pos.1 - VIEWPORT_Y,
         pos.1 + VIEWPORT_Y + 1,
-        0,
-        7,
+        pos.2,
+        item_data,
     ));
-    payload.push(0x82); //world light
-    payload.push(0x6F);
-    payload.push(0xD7);
     return payload;
 }
 
@@ -191,6 +303,7 @@ pub fn map_direction_payload(
     state: Arc<Mutex<State>>,
     direction: Direction,
     to: (u16, u16, u8),
+    item_data: &HashMap<u16, ItemData>,
 ) -> Vec<u8> {
     let mut payload: Vec<u8> = Vec::new();
     payload.push(direction.packet_id());
@@ -201,20 +314,25 @@ pub fn map_direction_payload(
         to_map_x,
         form_map_y,
         to_map_y,
-        0,
         to.2,
+        item_data,
     );
     payload.extend_from_slice(&map_desc);
     return payload;
 }
 
-pub fn thing_transformed_payload(pos: &(u16, u16, u8), stack_pos: u8, id: Option<u16>) -> Vec<u8> {
+pub fn thing_transformed_payload(
+    pos: &(u16, u16, u8),
+    stack_pos: u8,
+    item: Option<&Item>,
+    item_data: &HashMap<u16, ItemData>,
+) -> Vec<u8> {
     let mut payload: Vec<u8> = Vec::new();
     payload.push(ServerPacketType::ThingTransformed as u8);
     payload.extend_from_slice(&write_position(&pos));
     payload.push(stack_pos);
-    if let Some(i) = id {
-        payload.extend_from_slice(&i.to_le_bytes());
+    if let Some(it) = item {
+        payload.extend_from_slice(&write_item(it, item_data));
     }
     return payload;
 }
@@ -250,3 +368,151 @@ pub fn creature_turn_payload(direction: Direction, creature_id: u32) -> Vec<u8>
     payload.push(direction as u8);
     return payload;
 }
+
+pub fn creature_health_payload(creature_id: u32, health_percent: u8) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.push(ServerPacketType::CreatureHealth as u8);
+    payload.extend_from_slice(&creature_id.to_le_bytes());
+    payload.push(health_percent);
+    return payload;
+}
+
+pub fn player_stats_payload(
+    character: &Character,
+    vocation: &Vocation,
+    item_data: &HashMap<u16, ItemData>,
+) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.push(ServerPacketType::PlayerStats as u8);
+    payload.extend_from_slice(&character.health.to_le_bytes());
+    payload.extend_from_slice(&character.max_health.to_le_bytes());
+    // the client shows the capacity left in whole ounces
+    let free_capacity = (character.free_capacity(item_data) / 100).min(u16::MAX as u32) as u16;
+    payload.extend_from_slice(&free_capacity.to_le_bytes());
+    payload.extend_from_slice(&character.experience.to_le_bytes());
+    payload.extend_from_slice(&character.level.to_le_bytes());
+    payload.push(character.level_percent());
+    payload.extend_from_slice(&character.mana.to_le_bytes());
+    payload.extend_from_slice(&character.max_mana.to_le_bytes());
+    payload.push(character.magic_level);
+    payload.push(character.magic_level_percent(vocation));
+    payload.push(character.soul);
+    return payload;
+}
+
+pub fn player_skills_payload(
+    character: &Character,
+    vocation: &Vocation,
+    item_data: &HashMap<u16, ItemData>,
+) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.push(ServerPacketType::PlayerSkills as u8);
+    // worn items boost the skills shown
+    let boosts = character.inventory.modifiers(item_data).skills;
+    for skill in 0..SKILL_COUNT {
+        let level = character.skills[skill].level + boosts[skill];
+        payload.push(level.min(0xFF) as u8);
+        payload.push(character.skill_percent(skill, vocation));
+    }
+    return payload;
+}
+
+pub fn creature_light_payload(creature_id: u32, light_level: u8, light_color: u8) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.push(ServerPacketType::CreatureLight as u8);
+    payload.extend_from_slice(&creature_id.to_le_bytes());
+    payload.push(light_level);
+    payload.push(light_color);
+    return payload;
+}
+
+pub fn creature_speed_payload(creature_id: u32, speed: u16) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.push(ServerPacketType::CreatureSpeed as u8);
+    payload.extend_from_slice(&creature_id.to_le_bytes());
+    payload.extend_from_slice(&speed.to_le_bytes());
+    return payload;
+}
+
+pub fn text_message_payload(message_type: u8, text: &str) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.push(ServerPacketType::TextMessage as u8);
+    payload.push(message_type);
+    payload.extend_from_slice(&write_str(text));
+    return payload;
+}
+
+pub fn distance_effect_payload(
+    from: &(u16, u16, u8),
+    to: &(u16, u16, u8),
+    effect_type: u8,
+) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    payload.push(ServerPacketType::DistanceEffect as u8);
+    payload.extend_from_slice(&write_position(from));
+    payload.extend_from_slice(&write_position(to));
+    payload.push(effect_type + 1);
+    return payload;
+}
+
+pub fn icons_payload(icons: u8) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    payload.push(ServerPacketType::PlayerIcons as u8);
+    payload.push(icons);
+    return payload;
+}
+
+pub fn creature_outfit_payload(creature_id: u32, outfit: &[u8]) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    payload.push(ServerPacketType::CreatureOutfit as u8);
+    payload.extend_from_slice(&creature_id.to_le_bytes());
+    payload.extend_from_slice(outfit);
+    return payload;
+}
+
+pub fn cancel_walk_payload(direction: Direction) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    payload.push(ServerPacketType::CancelWalk as u8);
+    payload.push(direction as u8);
+    return payload;
+}
+
+pub fn cancel_target_payload() -> Vec<u8> {
+    return vec![ServerPacketType::CancelTarget as u8];
+}
+
+pub fn text_window_payload(window_id: u32, item_id: u16, max_length: u16, text: &str) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    payload.push(ServerPacketType::TextWindow as u8);
+    payload.extend_from_slice(&window_id.to_le_bytes());
+    payload.extend_from_slice(&item_id.to_le_bytes());
+    payload.extend_from_slice(&max_length.to_le_bytes());
+    payload.extend_from_slice(&write_str(text));
+    return payload;
+}
+
+// (quest id, name, completed) of every quest the player started
+pub fn quest_log_payload(quests: &[(u16, String, bool)]) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    payload.push(ServerPacketType::QuestLog as u8);
+    payload.extend_from_slice(&(quests.len() as u16).to_le_bytes());
+    for (id, name, completed) in quests.iter() {
+        payload.extend_from_slice(&id.to_le_bytes());
+        payload.extend_from_slice(&write_str(name));
+        payload.push(*completed as u8);
+    }
+    return payload;
+}
+
+// (name, description) of the started missions of a quest
+pub fn quest_line_payload(quest_id: u16, missions: &[(String, String)]) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    payload.push(ServerPacketType::QuestLine as u8);
+    payload.extend_from_slice(&quest_id.to_le_bytes());
+    payload.push(missions.len() as u8);
+    for (name, description) in missions.iter() {
+        payload.extend_from_slice(&write_str(name));
+        payload.extend_from_slice(&write_str(description));
+    }
+    return payload;
+}
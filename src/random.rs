@@ -0,0 +1,31 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static SEED: AtomicU64 = AtomicU64::new(0);
+
+// xorshift64, good enough for loot and drop rolls
+fn next_u64() -> u64 {
+    let mut x = SEED.load(Ordering::Relaxed);
+    if x == 0 {
+        x = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64
+            | 1;
+    }
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    SEED.store(x, Ordering::Relaxed);
+    return x;
+}
+
+// inclusive on both ends
+pub fn random_range(min: u32, max: u32) -> u32 {
+    if max <= min {
+        return min;
+    }
+    return min + (next_u64() % (max - min + 1) as u64) as u32;
+}
@@ -0,0 +1,588 @@
+use crate::{
+    connection::State, event_handler::Command, item::Item, xml_io::quest_loader::storage_value,
+};
+use rhai::{AST, Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+// scripts are stopped once they run longer or nest deeper than this
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_EXPR_DEPTH: usize = 64;
+const MAX_FUNCTION_EXPR_DEPTH: usize = 32;
+
+/*
+*   scripts live in data/scripts as .rhai files and are hooked in from the data files
+*       actions.xml         behavior="script" script="lever.rhai"   fn on_use(player, item)
+*       movements.xml       action="script" script="trap.rhai"      fn on_step_in(creature, item)
+*                                                                   fn on_step_out(creature, item)
+*                                                                   fn on_equip(player, item)
+*                                                                   fn on_deequip(player, item)
+*       talkactions.xml     words="!pos" script="commands.rhai"     fn on_say(player, words, param)
+*
+*   creatures are maps with id and pos, items are maps with id, pos, stack_pos, count, action_id
+*   and unique_id, items in the inventory or a container have pos x 0xFFFF
+*
+*   api
+*       pos(x, y, z)                            a position with x, y and z properties
+*       item_at(pos, stack_pos)                 client id of the item, 0 when there is none
+*       find_item(pos, item_id)                 stack position of the item, -1 when missing
+*       transform_item(pos, stack_pos, from_id, to_id)
+*       create_item(pos, item_id)
+*       remove_item(pos, item_id)
+*       move_item(from, item_id, to)
+*       teleport(creature_id, pos)
+*       send_message(creature_id, text)
+*       magic_effect(pos, effect)               0 based effect id
+*       promote(player_id)                      moves the player to its promoted vocation
+*       get_storage(player, key)                storage value of the player, -1 when unset
+*       set_storage(player_id, key, value)      saved with the player, quests are built on them
+*       creature_name(creature)                 empty when the creature is gone
+*       house_at(pos)                           id of the house the tile belongs to, 0 for none
+*       house_owner(house_id)                   lower case name, empty when nobody owns it
+*       owned_house(name)                       id of the house the player owns, 0 for none
+*       house_access(house_id, name)            owner, subowner, guest or none
+*       set_house_owner(house_id, name)         an empty name gives the house up
+*       house_invite(house_id, list, name)      list is subowners, guests or door and its id
+*       house_uninvite(house_id, list, name)
+*       schedule(delay_ms, function, args)      calls a function of the same script later
+*
+*   a script is aborted and its commands dropped when it exceeds the operation or call depth
+*   limits, scripts nesting expressions too deep do not compile
+*
+*   script functions only see their arguments, constants of the script are read as global::NAME
+*
+*   scripts never change the world themselves, everything they do becomes a Command the world
+*   runs once the script returned, so reads still see the world as it was before the script
+*/
+
+#[derive(Clone, Copy)]
+pub struct ScriptPos {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+impl ScriptPos {
+    pub fn new(x: i64, y: i64, z: i64) -> ScriptPos {
+        return ScriptPos { x, y, z };
+    }
+
+    pub fn from_pos(pos: (u16, u16, u8)) -> ScriptPos {
+        return ScriptPos::new(pos.0 as i64, pos.1 as i64, pos.2 as i64);
+    }
+
+    pub fn to_pos(self) -> (u16, u16, u8) {
+        return (self.x as u16, self.y as u16, self.z as u8);
+    }
+}
+
+type World = Arc<OnceLock<Arc<Mutex<State>>>>;
+type Issued = Arc<Mutex<Vec<(Duration, Command)>>>;
+
+// compiled scripts keyed by their file name and the one engine they all run on
+pub struct Scripts {
+    pub asts: HashMap<String, AST>,
+    pub engine: Engine,
+    // the world the engine reads, attached once the state exists
+    pub world: World,
+    // commands issued by the running script and its name
+    pub issued: Issued,
+    pub running: Arc<Mutex<String>>,
+}
+
+impl Scripts {
+    pub fn attach(&self, state: Arc<Mutex<State>>) {
+        let _ = self.world.set(state);
+    }
+}
+
+pub fn read_scripts(directory: &str) -> Scripts {
+    let world: World = Arc::new(OnceLock::new());
+    let issued: Issued = Arc::new(Mutex::new(vec![]));
+    let running = Arc::new(Mutex::new(String::new()));
+    let mut scripts = Scripts {
+        asts: HashMap::new(),
+        engine: create_engine(world.clone(), issued.clone(), running.clone()),
+        world,
+        issued,
+        running,
+    };
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        println!("no scripts found in {}", directory);
+        return scripts;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|extension| extension != "rhai") {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        println!("compiling {}", name);
+        match scripts.engine.compile_file(path) {
+            Ok(ast) => {
+                scripts.asts.insert(name, ast);
+            }
+            Err(error) => println!("failed to compile {}: {}", name, error),
+        }
+    }
+    return scripts;
+}
+
+pub fn creature_arg(creature_id: u32, pos: (u16, u16, u8)) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("id".into(), Dynamic::from(creature_id as i64));
+    map.insert("pos".into(), Dynamic::from(ScriptPos::from_pos(pos)));
+    return Dynamic::from_map(map);
+}
+
+pub fn item_arg(item: &Item, pos: (u16, u16, u8), stack_pos: u8) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("id".into(), Dynamic::from(item.client_id as i64));
+    map.insert("pos".into(), Dynamic::from(ScriptPos::from_pos(pos)));
+    map.insert("stack_pos".into(), Dynamic::from(stack_pos as i64));
+    map.insert("count".into(), Dynamic::from(item.count as i64));
+    map.insert("action_id".into(), Dynamic::from(item.action_id as i64));
+    map.insert("unique_id".into(), Dynamic::from(item.unique_id as i64));
+    return Dynamic::from_map(map);
+}
+
+// runs a function of a script, returns the commands it issued with the delay to run them after
+pub fn call_script(
+    scripts: &Scripts,
+    script: &str,
+    function: &str,
+    args: Vec<Dynamic>,
+) -> Vec<(Duration, Command)> {
+    let Some(ast) = scripts.asts.get(script) else {
+        println!("unknown script {}", script);
+        return vec![];
+    };
+    scripts.issued.lock().unwrap().clear();
+    *scripts.running.lock().unwrap() = script.to_string();
+    let result = scripts
+        .engine
+        .call_fn::<Dynamic>(&mut Scope::new(), ast, function, args);
+    let issued: Vec<(Duration, Command)> = scripts.issued.lock().unwrap().drain(..).collect();
+    if let Err(error) = result {
+        // hooks a script does not define are fine
+        let missing_hook = matches!(
+            *error,
+            EvalAltResult::ErrorFunctionNotFound(ref name, _) if name.starts_with(function)
+        );
+        if matches!(
+            error.unwrap_inner(),
+            EvalAltResult::ErrorTooManyOperations(_) | EvalAltResult::ErrorStackOverflow(_)
+        ) {
+            println!("{} {} aborted: {}", script, function, error);
+            return vec![];
+        }
+        if !missing_hook {
+            println!("{} {}: {}", script, function, error);
+        }
+    }
+    return issued;
+}
+
+// true when the world has a tile at the position, scripts may only change existing tiles
+fn has_tile(world: &World, pos: ScriptPos) -> bool {
+    let Some(state) = world.get() else {
+        return false;
+    };
+    let exists = state.lock().unwrap().map.contains_key(&pos.to_pos());
+    if !exists {
+        println!("script used a position without a tile {:?}", pos.to_pos());
+    }
+    return exists;
+}
+
+fn create_engine(world: World, issued: Issued, running: Arc<Mutex<String>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_FUNCTION_EXPR_DEPTH);
+    engine
+        .register_type_with_name::<ScriptPos>("Position")
+        .register_fn("pos", ScriptPos::new)
+        .register_get("x", |pos: &mut ScriptPos| pos.x)
+        .register_get("y", |pos: &mut ScriptPos| pos.y)
+        .register_get("z", |pos: &mut ScriptPos| pos.z);
+
+    let reader = world.clone();
+    engine.register_fn("item_at", move |pos: ScriptPos, stack_pos: i64| -> i64 {
+        let Some(state) = reader.get() else {
+            return 0;
+        };
+        let state_handle = state.lock().unwrap();
+        return state_handle
+            .map
+            .get(&pos.to_pos())
+            .and_then(|tile| tile.get_item_at_stack_pos(stack_pos as u8))
+            .map(|item| item.client_id as i64)
+            .unwrap_or(0);
+    });
+    let reader = world.clone();
+    engine.register_fn("find_item", move |pos: ScriptPos, item_id: i64| -> i64 {
+        let Some(state) = reader.get() else {
+            return -1;
+        };
+        let state_handle = state.lock().unwrap();
+        return state_handle
+            .map
+            .get(&pos.to_pos())
+            .and_then(|tile| tile.find_stack_pos(|item| item.client_id as i64 == item_id))
+            .map(|stack_pos| stack_pos as i64)
+            .unwrap_or(-1);
+    });
+
+    let out = issued.clone();
+    let reader = world.clone();
+    engine.register_fn(
+        "transform_item",
+        move |pos: ScriptPos, stack_pos: i64, from_id: i64, to_id: i64| {
+            if !has_tile(&reader, pos) {
+                return;
+            }
+            issue(
+                &out,
+                Command::TransformThing {
+                    pos: pos.to_pos(),
+                    stack_pos: stack_pos as u8,
+                    from_item_id: from_id as u16,
+                    to_item_id: to_id as u16,
+                },
+            );
+        },
+    );
+    let out = issued.clone();
+    let reader = world.clone();
+    engine.register_fn("create_item", move |pos: ScriptPos, item_id: i64| {
+        if !has_tile(&reader, pos) {
+            return;
+        }
+        issue(
+            &out,
+            Command::CreateItem {
+                pos: pos.to_pos(),
+                item_id: item_id as u16,
+            },
+        );
+    });
+    let out = issued.clone();
+    let reader = world.clone();
+    engine.register_fn("remove_item", move |pos: ScriptPos, item_id: i64| {
+        if !has_tile(&reader, pos) {
+            return;
+        }
+        issue(
+            &out,
+            Command::RemoveItem {
+                pos: pos.to_pos(),
+                item_id: item_id as u16,
+            },
+        );
+    });
+    let out = issued.clone();
+    let reader = world.clone();
+    engine.register_fn(
+        "move_item",
+        move |from: ScriptPos, item_id: i64, to: ScriptPos| {
+            if !has_tile(&reader, from) || !has_tile(&reader, to) {
+                return;
+            }
+            issue(
+                &out,
+                Command::RelocateItem {
+                    from: from.to_pos(),
+                    to: to.to_pos(),
+                    item_id: item_id as u16,
+                },
+            );
+        },
+    );
+    let out = issued.clone();
+    engine.register_fn("teleport", move |creature_id: i64, to: ScriptPos| {
+        issue(
+            &out,
+            Command::TeleportCreature {
+                creature_id: creature_id as u32,
+                to: to.to_pos(),
+            },
+        );
+    });
+    let out = issued.clone();
+    engine.register_fn("send_message", move |creature_id: i64, text: &str| {
+        issue(
+            &out,
+            Command::SendMessage {
+                creature_id: creature_id as u32,
+                text: text.to_string(),
+            },
+        );
+    });
+    let out = issued.clone();
+    engine.register_fn("magic_effect", move |pos: ScriptPos, effect: i64| {
+        issue(
+            &out,
+            Command::ShowMagicEffect {
+                pos: pos.to_pos(),
+                effect: effect as u8,
+            },
+        );
+    });
+    let reader = world.clone();
+    engine.register_fn("get_storage", move |player: Map, key: i64| -> i64 {
+        let (Some(id), Some(pos)) = (
+            player.get("id").and_then(|id| id.as_int().ok()),
+            player
+                .get("pos")
+                .and_then(|pos| pos.clone().try_cast::<ScriptPos>()),
+        ) else {
+            return -1;
+        };
+        let Some(state) = reader.get() else {
+            return -1;
+        };
+        let state_handle = state.lock().unwrap();
+        return state_handle
+            .map
+            .get(&pos.to_pos())
+            .and_then(|tile| tile.creatures.iter().find(|c| c.id as i64 == id))
+            .map(|creature| storage_value(&creature.storage, key as u32) as i64)
+            .unwrap_or(-1);
+    });
+    let reader = world.clone();
+    engine.register_fn("creature_name", move |creature: Map| -> String {
+        let (Some(id), Some(pos)) = (
+            creature.get("id").and_then(|id| id.as_int().ok()),
+            creature
+                .get("pos")
+                .and_then(|pos| pos.clone().try_cast::<ScriptPos>()),
+        ) else {
+            return String::new();
+        };
+        let Some(state) = reader.get() else {
+            return String::new();
+        };
+        let state_handle = state.lock().unwrap();
+        return state_handle
+            .map
+            .get(&pos.to_pos())
+            .and_then(|tile| tile.creatures.iter().find(|c| c.id as i64 == id))
+            .map(|creature| creature.name.clone())
+            .unwrap_or_default();
+    });
+    let reader = world.clone();
+    engine.register_fn("house_at", move |pos: ScriptPos| -> i64 {
+        let Some(state) = reader.get() else {
+            return 0;
+        };
+        let state_handle = state.lock().unwrap();
+        return state_handle
+            .map
+            .get(&pos.to_pos())
+            .map_or(0, |tile| tile.house_id as i64);
+    });
+    let reader = world.clone();
+    engine.register_fn("house_owner", move |house_id: i64| -> String {
+        let Some(state) = reader.get() else {
+            return String::new();
+        };
+        let state_handle = state.lock().unwrap();
+        return state_handle
+            .houses
+            .get(&(house_id as u32))
+            .map(|house| house.owner.clone())
+            .unwrap_or_default();
+    });
+    let reader = world.clone();
+    engine.register_fn("owned_house", move |name: &str| -> i64 {
+        let Some(state) = reader.get() else {
+            return 0;
+        };
+        let state_handle = state.lock().unwrap();
+        return state_handle
+            .houses
+            .values()
+            .find(|house| house.is_owner(name))
+            .map_or(0, |house| house.id as i64);
+    });
+    let reader = world.clone();
+    engine.register_fn("house_access", move |house_id: i64, name: &str| -> String {
+        let Some(state) = reader.get() else {
+            return "none".to_string();
+        };
+        let state_handle = state.lock().unwrap();
+        let Some(house) = state_handle.houses.get(&(house_id as u32)) else {
+            return "none".to_string();
+        };
+        let name = name.to_lowercase();
+        let access = if house.is_owner(&name) {
+            "owner"
+        } else if house.subowners.contains(&name) {
+            "subowner"
+        } else if house.guests.contains(&name) {
+            "guest"
+        } else {
+            "none"
+        };
+        return access.to_string();
+    });
+    let out = issued.clone();
+    engine.register_fn("set_house_owner", move |house_id: i64, name: &str| {
+        issue(
+            &out,
+            Command::SetHouseOwner {
+                house_id: house_id as u32,
+                owner: name.to_string(),
+            },
+        );
+    });
+    let out = issued.clone();
+    engine.register_fn(
+        "house_invite",
+        move |house_id: i64, list: &str, name: &str| {
+            issue(
+                &out,
+                Command::EditHouseList {
+                    house_id: house_id as u32,
+                    list: list.to_string(),
+                    name: name.to_string(),
+                    add: true,
+                },
+            );
+        },
+    );
+    let out = issued.clone();
+    engine.register_fn(
+        "house_uninvite",
+        move |house_id: i64, list: &str, name: &str| {
+            issue(
+                &out,
+                Command::EditHouseList {
+                    house_id: house_id as u32,
+                    list: list.to_string(),
+                    name: name.to_string(),
+                    add: false,
+                },
+            );
+        },
+    );
+    let out = issued.clone();
+    engine.register_fn(
+        "set_storage",
+        move |player_id: i64, key: i64, value: i64| {
+            issue(
+                &out,
+                Command::SetStorage {
+                    creature_id: player_id as u32,
+                    key: key as u32,
+                    value: value as i32,
+                },
+            );
+        },
+    );
+    let out = issued.clone();
+    engine.register_fn("promote", move |player_id: i64| {
+        issue(
+            &out,
+            Command::PromotePlayer {
+                creature_id: player_id as u32,
+            },
+        );
+    });
+    let out = issued.clone();
+    engine.register_fn(
+        "schedule",
+        move |delay: i64, function: &str, args: Array| {
+            out.lock().unwrap().push((
+                Duration::from_millis(delay.max(0) as u64),
+                Command::RunScript {
+                    script: running.lock().unwrap().clone(),
+                    function: function.to_string(),
+                    args,
+                },
+            ));
+        },
+    );
+    return engine;
+}
+
+fn issue(issued: &Mutex<Vec<(Duration, Command)>>, command: Command) {
+    issued.lock().unwrap().push((Duration::ZERO, command));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // each test compiles its own directory, tests run in parallel
+    fn test_scripts(test: &str) -> Scripts {
+        let directory = std::env::temp_dir().join(format!("tibia-{}-{}", std::process::id(), test));
+        std::fs::create_dir_all(&directory).unwrap();
+        let files = [
+            (
+                "greet.rhai",
+                r#"fn on_use(player, item) {
+                    send_message(player.id, "You used " + item.id + ".");
+                    schedule(500, "later", [item.id]);
+                }"#,
+            ),
+            (
+                "loop.rhai",
+                "fn on_use(player, item) { teleport(player.id, pos(1, 2, 7)); loop {} }",
+            ),
+            ("broken.rhai", "fn on_use(player, item) {"),
+            ("notes.txt", "not a script"),
+        ];
+        for (name, text) in files {
+            std::fs::write(directory.join(name), text).unwrap();
+        }
+        return read_scripts(&directory.to_string_lossy());
+    }
+
+    fn use_args() -> Vec<Dynamic> {
+        return vec![
+            creature_arg(7, (100, 100, 7)),
+            item_arg(&Item::new(1945), (101, 100, 7), 1),
+        ];
+    }
+
+    #[test]
+    fn read_scripts_compiles_only_valid_rhai_files() {
+        let scripts = test_scripts("compile");
+        let mut names: Vec<&String> = scripts.asts.keys().collect();
+        names.sort();
+        assert_eq!(names, vec!["greet.rhai", "loop.rhai"]);
+    }
+
+    #[test]
+    fn call_script_returns_the_issued_commands() {
+        let scripts = test_scripts("issue");
+        let issued = call_script(&scripts, "greet.rhai", "on_use", use_args());
+        assert_eq!(issued.len(), 2);
+        assert!(matches!(
+            &issued[0],
+            (delay, Command::SendMessage { creature_id: 7, text })
+                if delay.is_zero() && text == "You used 1945."
+        ));
+        assert!(matches!(
+            &issued[1],
+            (delay, Command::RunScript { script, function, .. })
+                if *delay == Duration::from_millis(500) && script == "greet.rhai" && function == "later"
+        ));
+        // hooks the script does not define issue nothing
+        assert!(call_script(&scripts, "greet.rhai", "on_say", vec![]).is_empty());
+    }
+
+    #[test]
+    fn call_script_drops_the_commands_of_aborted_scripts() {
+        let scripts = test_scripts("abort");
+        assert!(call_script(&scripts, "loop.rhai", "on_use", use_args()).is_empty());
+        assert!(call_script(&scripts, "missing.rhai", "on_use", use_args()).is_empty());
+    }
+}
@@ -0,0 +1,370 @@
+use crate::{
+    item::Item,
+    xml_io::{XmlNode, read_xml_file},
+};
+use std::collections::HashMap;
+
+/*
+*   <actions>
+*       <action itemid="1644" behavior="toggle" pair="1645"/>
+*       <action fromid="1811" toid="1814" behavior="text" text="Welcome to Thais."/>
+*       <action itemid="2772" behavior="toggle" pair="2773"/>
+*       <action actionid="2000" behavior="lever" x="100" y="100" z="7" wallid="1304"/>
+*       <action itemid="1948" behavior="teleport" relative="1" x="0" y="1" z="-1"/>
+*       <action uniqueid="6000" behavior="transform" transformto="1646"/>
+*       <action uniqueid="7000" behavior="chest" reward="3357"/>
+*       <action itemid="1642" behavior="lockeddoor" unlocked="1644"/>
+*       <action itemid="1646" behavior="leveldoor" open="1647"/>
+*       <tool itemid="3003" target="386" behavior="teleport" relative="1" x="0" y="1" z="-1"/>
+*       <tool itemid="3483" fromtarget="4597" totarget="4602" behavior="fishing"/>
+*   </actions>
+*
+*   what happens when a player uses an item, registered for one of
+*       uniqueid, actionid  set on a single item in the map editor
+*       itemid              the client id of every item of that kind
+*       fromid, toid        a range of client ids
+*   the unique id action of an item runs if there is one, its action id action otherwise and
+*   its item id action when it has neither, ids that are not numbers are skipped
+*
+*   behaviors and their attributes
+*       transform   transformto, the used item turns into it
+*       toggle      pair, the used item and its pair turn into each other, both are registered
+*       teleport    x, y, z, the player is moved there, with relative="1" they are offsets
+*                   from the used item
+*       text        text, opens a window showing it
+*       lever       x, y, z, wallid, flips the lever with the toggle of its item id and
+*                   removes the wall from the tile or puts it back
+*       script      script, runs on_use of the script in data/scripts
+*       chest       reward, storage, gives the reward item once per player, storage is the
+*                   storage key remembering it and defaults to the unique id of the chest
+*       lockeddoor  unlocked, the door says it is locked, a key with the same action id as
+*                   the door turns it into the unlocked door and the unlocked door back
+*       leveldoor   open, players with a level of at least the action id of the door minus
+*                   1000 walk into it as it turns into the open door
+*       questdoor   open, the same for players having a storage value for the action id
+*       fishing     the water splashes and the fishing skill of the player trains
+*   closing gated doors behind the player is left to a StepOut transform in movements.xml
+*
+*   items with an action are used even when they are containers, so quest chests never open
+*
+*   tools are registered for the item id of the tool and the target item they are used on, or
+*   a range of targets with fromtarget and totarget, their behavior runs as if the target was
+*   used, relative teleports start at the target
+*/
+
+#[derive(Clone, Copy)]
+pub enum DoorGate {
+    Level,
+    Quest,
+}
+
+#[derive(Clone)]
+pub enum ItemAction {
+    Transform {
+        to_item_id: u16,
+    },
+    Teleport {
+        destination: (i32, i32, i32),
+        relative: bool,
+    },
+    Text {
+        text: String,
+    },
+    Lever {
+        target_pos: (u16, u16, u8),
+        wall_id: u16,
+    },
+    Script {
+        script: String,
+    },
+    Chest {
+        reward_id: u16,
+        storage_key: u32,
+    },
+    LockedDoor {
+        unlocked_id: u16,
+    },
+    GatedDoor {
+        gate: DoorGate,
+        open_id: u16,
+    },
+    Fishing,
+}
+
+pub struct Actions {
+    pub item_ids: HashMap<u16, ItemAction>,
+    pub action_ids: HashMap<u16, ItemAction>,
+    pub unique_ids: HashMap<u16, ItemAction>,
+    // keyed by the tool and the item it is used on
+    pub tools: HashMap<(u16, u16), ItemAction>,
+}
+
+impl Actions {
+    pub fn find(&self, item: &Item) -> Option<&ItemAction> {
+        let by_unique_id = self
+            .unique_ids
+            .get(&item.unique_id)
+            .filter(|_| item.unique_id != 0);
+        let by_action_id = self
+            .action_ids
+            .get(&item.action_id)
+            .filter(|_| item.action_id != 0);
+        return by_unique_id
+            .or(by_action_id)
+            .or(self.item_ids.get(&item.client_id));
+    }
+
+    // the locked door an unlocked door turns into when it is locked with a key
+    pub fn locked_door_of(&self, unlocked_id: u16) -> Option<u16> {
+        return self
+            .item_ids
+            .iter()
+            .find(|(_, action)| {
+                matches!(action, ItemAction::LockedDoor { unlocked_id: id } if *id == unlocked_id)
+            })
+            .map(|(item_id, _)| *item_id);
+    }
+}
+
+pub fn read_actions(filepath: &str) -> Actions {
+    let root = read_xml_file(filepath);
+    let mut actions = Actions {
+        item_ids: HashMap::new(),
+        action_ids: HashMap::new(),
+        unique_ids: HashMap::new(),
+        tools: HashMap::new(),
+    };
+    for node in root.children_named("tool") {
+        let (Some(tool_id), Some(from_target)) = (
+            node.attr("itemid").and_then(|id| id.parse::<u16>().ok()),
+            node.attr("target")
+                .or(node.attr("fromtarget"))
+                .and_then(|id| id.parse::<u16>().ok()),
+        ) else {
+            println!("tool action without a tool or target");
+            continue;
+        };
+        let to_target: u16 = node.attr_or("totarget", from_target);
+        let Some(action) = parse_action(node) else {
+            println!("unknown tool action {:?}", node.attr("behavior"));
+            continue;
+        };
+        for target_id in from_target..=to_target {
+            actions.tools.insert((tool_id, target_id), action.clone());
+        }
+    }
+    for node in root.children_named("action") {
+        if node.attr("behavior") == Some("toggle") {
+            let item_id: u16 = node.attr_or("itemid", 0);
+            let pair: u16 = node.attr_or("pair", 0);
+            actions
+                .item_ids
+                .insert(item_id, ItemAction::Transform { to_item_id: pair });
+            actions.item_ids.insert(
+                pair,
+                ItemAction::Transform {
+                    to_item_id: item_id,
+                },
+            );
+            continue;
+        }
+        let Some(action) = parse_action(node) else {
+            println!("unknown item action {:?}", node.attr("behavior"));
+            continue;
+        };
+        if let Some(id) = node.attr("uniqueid") {
+            insert_action(&mut actions.unique_ids, "uniqueid", id, action);
+        } else if let Some(id) = node.attr("actionid") {
+            insert_action(&mut actions.action_ids, "actionid", id, action);
+        } else if let Some(id) = node.attr("itemid") {
+            insert_action(&mut actions.item_ids, "itemid", id, action);
+        } else if node.attr("fromid").is_some() {
+            let from_id: u16 = node.attr_or("fromid", 0);
+            let to_id: u16 = node.attr_or("toid", from_id);
+            for item_id in from_id..=to_id {
+                actions.item_ids.insert(item_id, action.clone());
+            }
+        } else {
+            println!("item action without an item");
+        }
+    }
+    return actions;
+}
+
+// registers the action for the id unless the id is not a number
+fn insert_action(ids: &mut HashMap<u16, ItemAction>, key: &str, id: &str, action: ItemAction) {
+    match id.parse::<u16>() {
+        Ok(id) => {
+            ids.insert(id, action);
+        }
+        Err(_) => println!("item action with the malformed {} {:?}", key, id),
+    }
+}
+
+fn parse_action(node: &XmlNode) -> Option<ItemAction> {
+    return match node.attr("behavior")? {
+        "transform" => Some(ItemAction::Transform {
+            to_item_id: node.attr_or("transformto", 0),
+        }),
+        "teleport" => Some(ItemAction::Teleport {
+            destination: (
+                node.attr_or("x", 0),
+                node.attr_or("y", 0),
+                node.attr_or("z", 0),
+            ),
+            relative: node.attr_or("relative", 0u8) == 1,
+        }),
+        "text" => Some(ItemAction::Text {
+            text: node.attr("text")?.to_string(),
+        }),
+        "lever" => Some(ItemAction::Lever {
+            target_pos: (
+                node.attr_or("x", 0),
+                node.attr_or("y", 0),
+                node.attr_or("z", 0),
+            ),
+            wall_id: node.attr_or("wallid", 0),
+        }),
+        "script" => Some(ItemAction::Script {
+            script: node.attr("script")?.to_string(),
+        }),
+        "chest" => Some(ItemAction::Chest {
+            reward_id: node.attr("reward")?.parse().ok()?,
+            storage_key: node.attr_or("storage", node.attr_or("uniqueid", 0)),
+        }),
+        "lockeddoor" => Some(ItemAction::LockedDoor {
+            unlocked_id: node.attr("unlocked")?.parse().ok()?,
+        }),
+        "leveldoor" => Some(ItemAction::GatedDoor {
+            gate: DoorGate::Level,
+            open_id: node.attr("open")?.parse().ok()?,
+        }),
+        "questdoor" => Some(ItemAction::GatedDoor {
+            gate: DoorGate::Quest,
+            open_id: node.attr("open")?.parse().ok()?,
+        }),
+        "fishing" => Some(ItemAction::Fishing),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml_io::test_file;
+
+    fn transform_target(action: Option<&ItemAction>) -> Option<u16> {
+        return match action {
+            Some(ItemAction::Transform { to_item_id }) => Some(*to_item_id),
+            _ => None,
+        };
+    }
+
+    #[test]
+    fn read_actions_registers_toggles_and_ranges() {
+        let filepath = test_file(
+            "actions.xml",
+            r#"<actions>
+                <action itemid="1644" behavior="toggle" pair="1645"/>
+                <action fromid="1811" toid="1814" behavior="text" text="Welcome to Thais."/>
+                <action actionid="x2000" behavior="transform" transformto="1646"/>
+                <action itemid="1700" behavior="dance"/>
+            </actions>"#,
+        );
+        let actions = read_actions(&filepath);
+        assert_eq!(transform_target(actions.item_ids.get(&1644)), Some(1645));
+        assert_eq!(transform_target(actions.item_ids.get(&1645)), Some(1644));
+        for item_id in 1811..=1814 {
+            assert!(matches!(
+                actions.item_ids.get(&item_id),
+                Some(ItemAction::Text { text }) if text == "Welcome to Thais."
+            ));
+        }
+        assert_eq!(actions.item_ids.len(), 6);
+        assert!(actions.action_ids.is_empty());
+    }
+
+    #[test]
+    fn find_prefers_unique_then_action_ids() {
+        let filepath = test_file(
+            "actions-find.xml",
+            r#"<actions>
+                <action itemid="1644" behavior="transform" transformto="1"/>
+                <action actionid="2000" behavior="transform" transformto="2"/>
+                <action uniqueid="6000" behavior="transform" transformto="3"/>
+            </actions>"#,
+        );
+        let actions = read_actions(&filepath);
+        let mut door = Item::new(1644);
+        assert_eq!(transform_target(actions.find(&door)), Some(1));
+        door.action_id = 2000;
+        assert_eq!(transform_target(actions.find(&door)), Some(2));
+        door.unique_id = 6000;
+        assert_eq!(transform_target(actions.find(&door)), Some(3));
+        // ids without an action fall back to the next kind of id
+        door.unique_id = 6001;
+        assert_eq!(transform_target(actions.find(&door)), Some(2));
+        assert!(actions.find(&Item::new(1645)).is_none());
+    }
+
+    #[test]
+    fn chest_storage_defaults_to_the_unique_id() {
+        let filepath = test_file(
+            "actions-chests.xml",
+            r#"<actions>
+                <action uniqueid="7000" behavior="chest" reward="3357"/>
+                <action uniqueid="7001" behavior="chest" reward="3079" storage="1500"/>
+                <action uniqueid="7002" behavior="chest"/>
+            </actions>"#,
+        );
+        let actions = read_actions(&filepath);
+        assert!(matches!(
+            actions.unique_ids.get(&7000),
+            Some(ItemAction::Chest {
+                reward_id: 3357,
+                storage_key: 7000
+            })
+        ));
+        assert!(matches!(
+            actions.unique_ids.get(&7001),
+            Some(ItemAction::Chest {
+                reward_id: 3079,
+                storage_key: 1500
+            })
+        ));
+        // a chest without a reward is skipped
+        assert!(!actions.unique_ids.contains_key(&7002));
+    }
+
+    #[test]
+    fn locked_door_of_finds_the_door_by_its_unlocked_id() {
+        let filepath = test_file(
+            "actions-doors.xml",
+            r#"<actions>
+                <action itemid="1642" behavior="lockeddoor" unlocked="1644"/>
+                <action itemid="1646" behavior="leveldoor" open="1647"/>
+                <action itemid="1648" behavior="questdoor" open="1649"/>
+                <action itemid="1650" behavior="leveldoor"/>
+            </actions>"#,
+        );
+        let actions = read_actions(&filepath);
+        assert_eq!(actions.locked_door_of(1644), Some(1642));
+        assert_eq!(actions.locked_door_of(1642), None);
+        assert!(matches!(
+            actions.item_ids.get(&1646),
+            Some(ItemAction::GatedDoor {
+                gate: DoorGate::Level,
+                open_id: 1647
+            })
+        ));
+        assert!(matches!(
+            actions.item_ids.get(&1648),
+            Some(ItemAction::GatedDoor {
+                gate: DoorGate::Quest,
+                open_id: 1649
+            })
+        ));
+        // gated doors need the door they open into
+        assert!(!actions.item_ids.contains_key(&1650));
+    }
+}
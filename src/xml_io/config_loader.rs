@@ -0,0 +1,68 @@
+use crate::xml_io::read_xml_file;
+
+/*
+*   <config>
+*       <death experienceloss="10" skillloss="10" itemdropchance="10" containerdropchance="100"
+*           corpse="3058"/>
+*       <houses rentdays="30"/>
+*       <players saveminutes="10"/>
+*   </config>
+*
+*   chances and losses are in percent, item ids are client ids
+*   house rent is taken from the depot of the owner every rentdays days
+*   online players are saved every saveminutes minutes besides when they log out
+*/
+
+pub struct DeathConfig {
+    pub experience_loss: u32,
+    pub skill_loss: u32,
+    pub item_drop_chance: u32,
+    pub container_drop_chance: u32,
+    pub player_corpse_id: u16,
+}
+
+pub struct HouseConfig {
+    pub rent_days: u32,
+}
+
+pub struct PlayerConfig {
+    pub save_minutes: u32,
+}
+
+pub struct Config {
+    pub death: DeathConfig,
+    pub houses: HouseConfig,
+    pub players: PlayerConfig,
+}
+
+pub fn read_config(filepath: &str) -> Config {
+    let root = read_xml_file(filepath);
+    let mut death = DeathConfig {
+        experience_loss: 10,
+        skill_loss: 10,
+        item_drop_chance: 10,
+        container_drop_chance: 100,
+        player_corpse_id: 3058,
+    };
+    if let Some(node) = root.child("death") {
+        death.experience_loss = node.attr_or("experienceloss", death.experience_loss);
+        death.skill_loss = node.attr_or("skillloss", death.skill_loss);
+        death.item_drop_chance = node.attr_or("itemdropchance", death.item_drop_chance);
+        death.container_drop_chance =
+            node.attr_or("containerdropchance", death.container_drop_chance);
+        death.player_corpse_id = node.attr_or("corpse", death.player_corpse_id);
+    }
+    let mut houses = HouseConfig { rent_days: 30 };
+    if let Some(node) = root.child("houses") {
+        houses.rent_days = node.attr_or("rentdays", houses.rent_days);
+    }
+    let mut players = PlayerConfig { save_minutes: 10 };
+    if let Some(node) = root.child("players") {
+        players.save_minutes = node.attr_or("saveminutes", players.save_minutes);
+    }
+    return Config {
+        death,
+        houses,
+        players,
+    };
+}
@@ -0,0 +1,289 @@
+use crate::{
+    item::Item,
+    xml_io::{
+        XmlNode,
+        player_loader::{item_node, read_item},
+        read_xml_file, write_xml_file,
+    },
+};
+use std::collections::HashMap;
+
+/*
+*   <houses>
+*       <house name="Market Street 1" houseid="1" entryx="1030" entryy="1020" entryz="7"
+*           rent="1000" townid="1" size="24"/>
+*   </houses>
+*
+*   the houses of the map, their tiles and doors are set in the map editor, rent is in gold
+*
+*   what changes while the server runs is saved to data/houses/<id>.xml
+*   <house id="1" owner="some character" paiduntil="1700000000">
+*       <subowner name="another character"/>
+*       <guest name="..."/>
+*       <door id="1">
+*           <access name="..."/>
+*       </door>
+*       <tile x="1031" y="1020" z="7">
+*           <item id="2854"/>
+*       </tile>
+*   </house>
+*
+*   names are kept in lower case, paiduntil is in seconds since the unix epoch
+*   only the moveable items of house tiles are saved, the rest comes from the map
+*/
+
+pub const HOUSES_DIRECTORY: &str = "./data/houses";
+
+// the moveable items of each house tile, top of the stack first
+pub type HouseTiles = Vec<((u16, u16, u8), Vec<Item>)>;
+
+pub enum HouseList {
+    Subowners,
+    Guests,
+    Door(u8),
+}
+
+impl HouseList {
+    // subowners, guests or door followed by the door id
+    pub fn from_name(name: &str) -> Option<HouseList> {
+        let name = name.trim().to_lowercase();
+        return match name.as_str() {
+            "subowners" => Some(HouseList::Subowners),
+            "guests" => Some(HouseList::Guests),
+            _ => {
+                let door_id = name.strip_prefix("door")?.trim().parse().ok()?;
+                Some(HouseList::Door(door_id))
+            }
+        };
+    }
+}
+
+#[derive(Clone)]
+pub struct House {
+    pub id: u32,
+    pub name: String,
+    pub entry: (u16, u16, u8),
+    pub rent: u32,
+    pub town_id: u32,
+    pub size: u32,
+    // empty when nobody owns the house
+    pub owner: String,
+    pub subowners: Vec<String>,
+    pub guests: Vec<String>,
+    pub doors: HashMap<u8, Vec<String>>,
+    pub paid_until: u64,
+}
+
+impl House {
+    pub fn is_owner(&self, name: &str) -> bool {
+        return !self.owner.is_empty() && self.owner == name.to_lowercase();
+    }
+
+    pub fn can_move_items(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        return self.is_owner(&name)
+            || self.subowners.contains(&name)
+            || self.guests.contains(&name);
+    }
+
+    // doors of houses nobody owns stay open for everyone to have a look
+    pub fn can_open_door(&self, name: &str, door_id: u8) -> bool {
+        let name = name.to_lowercase();
+        return self.owner.is_empty()
+            || self.is_owner(&name)
+            || self.subowners.contains(&name)
+            || self
+                .doors
+                .get(&door_id)
+                .is_some_and(|names| names.contains(&name));
+    }
+
+    pub fn list_mut(&mut self, list: &HouseList) -> &mut Vec<String> {
+        return match list {
+            HouseList::Subowners => &mut self.subowners,
+            HouseList::Guests => &mut self.guests,
+            HouseList::Door(door_id) => self.doors.entry(*door_id).or_default(),
+        };
+    }
+
+    pub fn set_owner(&mut self, owner: &str, paid_until: u64) {
+        self.owner = owner.to_lowercase();
+        self.subowners.clear();
+        self.guests.clear();
+        self.doors.clear();
+        self.paid_until = paid_until;
+    }
+}
+
+pub fn read_houses(filepath: &str) -> HashMap<u32, House> {
+    let root = read_xml_file(filepath);
+    let mut houses: HashMap<u32, House> = HashMap::new();
+    for node in root.children_named("house") {
+        let house = House {
+            id: node.attr_or("houseid", 0),
+            name: node.attr("name").unwrap_or("").to_string(),
+            entry: (
+                node.attr_or("entryx", 0),
+                node.attr_or("entryy", 0),
+                node.attr_or("entryz", 0),
+            ),
+            rent: node.attr_or("rent", 0),
+            town_id: node.attr_or("townid", 0),
+            size: node.attr_or("size", 0),
+            owner: String::new(),
+            subowners: vec![],
+            guests: vec![],
+            doors: HashMap::new(),
+            paid_until: 0,
+        };
+        houses.insert(house.id, house);
+    }
+    return houses;
+}
+
+fn house_file(directory: &str, house_id: u32) -> String {
+    return format!("{}/{}.xml", directory, house_id);
+}
+
+// fills in the owner and access lists, returns the saved tile items, None when never saved
+pub fn read_house_state(directory: &str, house: &mut House) -> Option<HouseTiles> {
+    let filepath = house_file(directory, house.id);
+    if std::fs::metadata(&filepath).is_err() {
+        return None;
+    }
+    let root = read_xml_file(&filepath);
+    house.owner = root.attr("owner").unwrap_or("").to_lowercase();
+    house.paid_until = root.attr_or("paiduntil", 0);
+    house.subowners = names(&root, "subowner");
+    house.guests = names(&root, "guest");
+    house.doors.clear();
+    for node in root.children_named("door") {
+        house
+            .doors
+            .insert(node.attr_or("id", 0), names(node, "access"));
+    }
+    let mut tiles: HouseTiles = Vec::new();
+    for node in root.children_named("tile") {
+        let pos = (
+            node.attr_or("x", 0),
+            node.attr_or("y", 0),
+            node.attr_or("z", 0),
+        );
+        tiles.push((pos, node.children_named("item").map(read_item).collect()));
+    }
+    return Some(tiles);
+}
+
+fn names(node: &XmlNode, child_name: &str) -> Vec<String> {
+    return node
+        .children_named(child_name)
+        .filter_map(|child| child.attr("name"))
+        .map(|name| name.to_lowercase())
+        .collect();
+}
+
+pub fn save_house(directory: &str, house: &House, tiles: &HouseTiles) {
+    if let Err(error) = std::fs::create_dir_all(directory) {
+        println!("failed to create {}: {}", directory, error);
+        return;
+    }
+    let mut root = XmlNode::new("house")
+        .with_attr("id", house.id)
+        .with_attr("owner", &house.owner)
+        .with_attr("paiduntil", house.paid_until);
+    for name in house.subowners.iter() {
+        root.children
+            .push(XmlNode::new("subowner").with_attr("name", name));
+    }
+    for name in house.guests.iter() {
+        root.children
+            .push(XmlNode::new("guest").with_attr("name", name));
+    }
+    let mut door_ids: Vec<&u8> = house.doors.keys().collect();
+    door_ids.sort();
+    for door_id in door_ids {
+        let mut node = XmlNode::new("door").with_attr("id", door_id);
+        for name in house.doors[door_id].iter() {
+            node.children
+                .push(XmlNode::new("access").with_attr("name", name));
+        }
+        root.children.push(node);
+    }
+    for (pos, items) in tiles.iter() {
+        let mut node = XmlNode::new("tile")
+            .with_attr("x", pos.0)
+            .with_attr("y", pos.1)
+            .with_attr("z", pos.2);
+        for item in items.iter() {
+            node.children.push(item_node(item));
+        }
+        root.children.push(node);
+    }
+    write_xml_file(&house_file(directory, house.id), &root);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml_io::test_file;
+
+    fn test_house() -> House {
+        let filepath = test_file(
+            "houses.xml",
+            r#"<houses>
+                <house name="Market Street 1" houseid="1" entryx="1030" entryy="1020" entryz="7"
+                    rent="1000" townid="1" size="24"/>
+            </houses>"#,
+        );
+        return read_houses(&filepath).remove(&1).unwrap();
+    }
+
+    #[test]
+    fn house_lists_grant_access() {
+        let mut house = test_house();
+        assert_eq!(house.entry, (1030, 1020, 7));
+        assert!(house.can_open_door("Anyone", 1));
+        assert!(!house.can_move_items("Anyone"));
+        house.set_owner("Some Character", 100);
+        assert!(house.is_owner("some character"));
+        assert!(!house.can_open_door("Anyone", 1));
+        let door = HouseList::from_name("Door 2").unwrap();
+        house.list_mut(&door).push("a friend".to_string());
+        house
+            .list_mut(&HouseList::from_name("guests").unwrap())
+            .push("a guest".to_string());
+        assert!(house.can_open_door("A Friend", 2));
+        assert!(!house.can_open_door("A Friend", 1));
+        assert!(!house.can_move_items("A Friend"));
+        assert!(house.can_move_items("A Guest"));
+        assert!(HouseList::from_name("doorway").is_none());
+        // a new owner starts with empty lists
+        house.set_owner("Another Character", 200);
+        assert!(!house.can_move_items("A Guest"));
+    }
+
+    #[test]
+    fn save_house_round_trips_through_read_house_state() {
+        let directory = std::env::temp_dir().join(format!("tibia-{}-houses", std::process::id()));
+        let directory = directory.to_string_lossy().to_string();
+        let mut saved = test_house();
+        saved.set_owner("Some Character", 1700000000);
+        saved.subowners.push("another character".to_string());
+        saved.doors.insert(1, vec!["a friend".to_string()]);
+        let tiles: HouseTiles = vec![((1031, 1020, 7), vec![Item::new(2854), Item::new(3031)])];
+        save_house(&directory, &saved, &tiles);
+
+        let mut house = test_house();
+        let read_tiles = read_house_state(&directory, &mut house).unwrap();
+        assert_eq!(house.owner, "some character");
+        assert_eq!(house.paid_until, 1700000000);
+        assert_eq!(house.subowners, saved.subowners);
+        assert_eq!(house.doors, saved.doors);
+        assert_eq!(read_tiles.len(), 1);
+        assert_eq!(read_tiles[0].0, (1031, 1020, 7));
+        let ids: Vec<u16> = read_tiles[0].1.iter().map(|item| item.client_id).collect();
+        assert_eq!(ids, vec![2854, 3031]);
+        house.id = 2;
+        assert!(read_house_state(&directory, &mut house).is_none());
+    }
+}
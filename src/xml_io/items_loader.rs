@@ -0,0 +1,206 @@
+use crate::{
+    condition::ConditionType,
+    creature::SKILL_COUNT,
+    fluid::FluidType,
+    otb_io::item_loader::{ItemData, SlotType},
+    xml_io::{XmlNode, read_xml_file},
+};
+use std::collections::HashMap;
+
+/*
+*   <items>
+*       <item id="2118" decayto="2119" duration="120">
+*           <field condition="burning" damage="20" tickdamage="10" ticks="7"/>
+*       </item>
+*       <item id="3357" name="plate armor" article="a" weight="12000" armor="10" slottype="body"/>
+*       <item id="3079" name="boots of haste" speed="40" slottype="feet"/>
+*       <item id="3052" name="life ring" lightlevel="2" lightcolor="215" skillsword="1"
+*           slottype="ring"/>
+*       <item id="3031" name="gold coin" plural="gold coins" weight="10"/>
+*       <item id="3264" name="sword" article="a" attack="14" defense="12" weapontype="sword"/>
+*       <item id="3277" name="spear" article="a" attack="25" weapontype="distance"
+*           shooteffect="0"/>
+*       <item id="4597" fluidsource="water"/>
+*       <item id="3505" maxtextlen="512"/>
+*       <item id="2854" name="backpack" containersize="20" slottype="backpack"/>
+*   </items>
+*
+*   adds what items.otb does not know to the already loaded item data, ids are client ids
+*   duration is in seconds, decayto="0" makes the item disappear when it decays
+*   weight is in hundredths of an ounce and replaces the items.otb weight
+*   name replaces the items.otb name, the article defaults to a or an and the plural to the
+*   name with an s, they are used when looking at the item
+*   empty fluid containers used on a fluid source are filled with its fluid
+*   maxtextlen is how much can be written on a writeable item
+*   containersize is how many items fit in a container and replaces the items.otb size
+*   slottype is one of hand, two-handed, head, necklace, backpack, body, legs, feet, ring or ammo,
+*   items without one only fit the hands and the ammo slot
+*   weapontype is one of club, sword, axe or distance, the skill fighting with the weapon
+*   trains, distance weapons show the 0 based distance effect shooteffect when they hit
+*   armor, speed, light and the skillfist, skillclub, skillsword, skillaxe, skilldist,
+*   skillshield and skillfish boosts count while the item is worn in its slot
+*   field damage hits on step in, the condition then deals tickdamage for the given ticks
+*/
+
+// the items.xml attributes of the skill boosts in skill order
+const SKILL_NAMES: [&str; SKILL_COUNT] = [
+    "skillfist",
+    "skillclub",
+    "skillsword",
+    "skillaxe",
+    "skilldist",
+    "skillshield",
+    "skillfish",
+];
+
+// the weapontype names in skill order, fists are no weapon type
+const WEAPON_TYPES: [&str; 5] = ["", "club", "sword", "axe", "distance"];
+
+#[derive(Clone)]
+pub struct FieldEffect {
+    pub condition: ConditionType,
+    pub damage: u16,
+    pub tick_damage: u16,
+    pub ticks: u32,
+}
+
+pub fn read_items(filepath: &str, item_data: &mut HashMap<u16, ItemData>) {
+    let root = read_xml_file(filepath);
+    for node in root.children_named("item") {
+        let client_id: u16 = node.attr_or("id", 0);
+        let Some(data) = item_data.get_mut(&client_id) else {
+            println!("items.xml references unknown item {}", client_id);
+            continue;
+        };
+        data.decay_to = node.attr_or("decayto", data.decay_to);
+        data.decay_time = node.attr_or("duration", data.decay_time);
+        data.weight = node.attr_or("weight", data.weight);
+        data.max_text_length = node.attr_or("maxtextlen", data.max_text_length);
+        data.container_size = node.attr_or("containersize", data.container_size);
+        if let Some(name) = node.attr("name") {
+            data.item_name = name.to_string();
+        }
+        data.article = node.attr("article").unwrap_or("").to_string();
+        data.plural = node.attr("plural").unwrap_or("").to_string();
+        data.attack = node.attr_or("attack", data.attack);
+        data.defense = node.attr_or("defense", data.defense);
+        data.armor = node.attr_or("armor", data.armor);
+        if let Some(weapon_type) = node.attr("weapontype") {
+            data.weapon_skill = WEAPON_TYPES.iter().position(|name| *name == weapon_type);
+        }
+        if let Some(effect) = node
+            .attr("shooteffect")
+            .and_then(|effect| effect.parse().ok())
+        {
+            data.shoot_effect = Some(effect);
+        }
+        if let Some(slot_type) = node.attr("slottype").and_then(SlotType::from_name) {
+            data.slot_type = Some(slot_type);
+        }
+        data.speed_bonus = node.attr_or("speed", data.speed_bonus);
+        data.light_level = node.attr_or("lightlevel", data.light_level);
+        data.light_color = node.attr_or("lightcolor", data.light_color);
+        for (skill, name) in SKILL_NAMES.iter().enumerate() {
+            data.skill_boosts[skill] = node.attr_or(name, data.skill_boosts[skill]);
+        }
+        if let Some(fluid) = node.attr("fluidsource").and_then(FluidType::from_name) {
+            data.fluid_source = fluid as u8;
+        }
+        if let Some(field_node) = node.child("field") {
+            data.field = parse_field(field_node);
+        }
+    }
+}
+
+fn parse_field(node: &XmlNode) -> Option<FieldEffect> {
+    return Some(FieldEffect {
+        condition: ConditionType::from_name(node.attr("condition")?)?,
+        damage: node.attr_or("damage", 0),
+        tick_damage: node.attr_or("tickdamage", 0),
+        ticks: node.attr_or("ticks", 0),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{otb_io::item_loader::ItemType, xml_io::test_file};
+
+    const FIRE_FIELD_ID: u16 = 2118;
+    const SMALL_FIRE_ID: u16 = 2119;
+    const BAG_ID: u16 = 2853;
+    const BACKPACK_ID: u16 = 2854;
+
+    fn test_item_data() -> HashMap<u16, ItemData> {
+        let mut item_data = HashMap::new();
+        for client_id in [FIRE_FIELD_ID, SMALL_FIRE_ID, BAG_ID, BACKPACK_ID] {
+            item_data.insert(client_id, ItemData::new(client_id, ItemType::Nothing, 0));
+        }
+        return item_data;
+    }
+
+    #[test]
+    fn read_items_reads_decay_and_fields() {
+        let filepath = test_file(
+            "items.xml",
+            r#"<items>
+                <item id="2118" decayto="2119" duration="120">
+                    <field condition="burning" damage="20" tickdamage="10" ticks="7"/>
+                </item>
+                <item id="2119" decayto="0" duration="60">
+                    <field condition="frozen" damage="5"/>
+                </item>
+                <item id="9999" decayto="1"/>
+            </items>"#,
+        );
+        let mut item_data = test_item_data();
+        read_items(&filepath, &mut item_data);
+        let fire_field = &item_data[&FIRE_FIELD_ID];
+        assert_eq!(
+            (fire_field.decay_to, fire_field.decay_time),
+            (SMALL_FIRE_ID, 120)
+        );
+        let field = fire_field.field.as_ref().unwrap();
+        assert_eq!(field.condition, ConditionType::Burning);
+        assert_eq!((field.damage, field.tick_damage, field.ticks), (20, 10, 7));
+        // unknown conditions leave the item without a field
+        let small_fire = &item_data[&SMALL_FIRE_ID];
+        assert_eq!((small_fire.decay_to, small_fire.decay_time), (0, 60));
+        assert!(small_fire.field.is_none());
+        assert!(!item_data.contains_key(&9999));
+    }
+
+    #[test]
+    fn read_items_reads_fluid_sources() {
+        let filepath = test_file(
+            "items-fluids.xml",
+            r#"<items>
+                <item id="2118" fluidsource="water"/>
+                <item id="2119" fluidsource="lava"/>
+            </items>"#,
+        );
+        let mut item_data = test_item_data();
+        read_items(&filepath, &mut item_data);
+        assert_eq!(
+            item_data[&FIRE_FIELD_ID].fluid_source,
+            FluidType::Water as u8
+        );
+        assert_eq!(item_data[&SMALL_FIRE_ID].fluid_source, 0);
+    }
+
+    #[test]
+    fn read_items_replaces_the_container_size() {
+        let filepath = test_file(
+            "items-containers.xml",
+            r#"<items>
+                <item id="2853" containersize="8"/>
+                <item id="2854" name="backpack"/>
+            </items>"#,
+        );
+        let mut item_data = test_item_data();
+        item_data.get_mut(&BACKPACK_ID).unwrap().container_size = 20;
+        read_items(&filepath, &mut item_data);
+        assert_eq!(item_data[&BAG_ID].container_size, 8);
+        assert_eq!(item_data[&BACKPACK_ID].container_size, 20);
+    }
+}
@@ -0,0 +1,286 @@
+pub mod action_loader;
+pub mod config_loader;
+pub mod house_loader;
+pub mod items_loader;
+pub mod monster_loader;
+pub mod movement_loader;
+pub mod player_loader;
+pub mod quest_loader;
+pub mod spell_loader;
+pub mod talkaction_loader;
+pub mod vocation_loader;
+
+use std::{collections::HashMap, str::FromStr};
+
+/*
+*   minimal xml reader for the data files (config, monsters, houses, spawns ...)
+*   supports elements, attributes in single or double quotes, self closing tags,
+*   text content, comments and the xml declaration. Doctypes and CDATA are not supported.
+*   the writer is used for the files the server saves itself, like players
+*/
+
+#[derive(Clone)]
+pub struct XmlNode {
+    pub name: String,
+    pub attributes: HashMap<String, String>,
+    pub children: Vec<XmlNode>,
+    pub text: String,
+}
+
+impl XmlNode {
+    pub fn new(name: &str) -> XmlNode {
+        return XmlNode {
+            name: name.to_string(),
+            attributes: HashMap::new(),
+            children: vec![],
+            text: String::new(),
+        };
+    }
+
+    pub fn attr(&self, key: &str) -> Option<&str> {
+        return self.attributes.get(key).map(|value| value.as_str());
+    }
+
+    pub fn attr_or<T: FromStr>(&self, key: &str, default: T) -> T {
+        if let Some(value) = self.attributes.get(key) {
+            if let Ok(parsed) = value.trim().parse::<T>() {
+                return parsed;
+            }
+        }
+        return default;
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlNode> {
+        return self.children.iter().filter(move |child| child.name == name);
+    }
+
+    pub fn child(&self, name: &str) -> Option<&XmlNode> {
+        return self.children.iter().find(|child| child.name == name);
+    }
+
+    pub fn with_attr(mut self, key: &str, value: impl ToString) -> XmlNode {
+        self.attributes.insert(key.to_string(), value.to_string());
+        return self;
+    }
+}
+
+pub fn read_xml_file(filepath: &str) -> XmlNode {
+    println!("parsing {}", filepath);
+    let text = std::fs::read_to_string(filepath).unwrap();
+    let root = parse_xml(&text);
+    println!("done parsing {}", filepath);
+    return root;
+}
+
+// writes a data file for a test to the temp directory and returns its path
+#[cfg(test)]
+pub fn test_file(name: &str, text: &str) -> String {
+    let path = std::env::temp_dir().join(format!("tibia-{}-{}", std::process::id(), name));
+    std::fs::write(&path, text).unwrap();
+    return path.to_string_lossy().to_string();
+}
+
+pub fn write_xml_file(filepath: &str, root: &XmlNode) {
+    let mut text = String::from("<?xml version=\"1.0\"?>\n");
+    write_node(&mut text, root, 0);
+    if let Err(error) = std::fs::write(filepath, text) {
+        println!("failed to write {}: {}", filepath, error);
+    }
+}
+
+fn write_node(text: &mut String, node: &XmlNode, depth: usize) {
+    let indent = "    ".repeat(depth);
+    text.push_str(&format!("{}<{}", indent, node.name));
+    // sorted so that saving the same data gives the same file
+    let mut keys: Vec<&String> = node.attributes.keys().collect();
+    keys.sort();
+    for key in keys {
+        text.push_str(&format!(" {}=\"{}\"", key, escape(&node.attributes[key])));
+    }
+    if node.children.is_empty() && node.text.is_empty() {
+        text.push_str("/>\n");
+        return;
+    }
+    if node.children.is_empty() {
+        text.push_str(&format!(">{}</{}>\n", escape(&node.text), node.name));
+        return;
+    }
+    text.push_str(">\n");
+    for child in node.children.iter() {
+        write_node(text, child, depth + 1);
+    }
+    text.push_str(&format!("{}</{}>\n", indent, node.name));
+}
+
+pub fn parse_xml(text: &str) -> XmlNode {
+    let bytes = text.as_bytes();
+    let mut idx: usize = 0;
+    // artificial root so that the document element can be pushed like any other child
+    let mut stack: Vec<XmlNode> = vec![XmlNode::new("")];
+    while idx < bytes.len() {
+        if bytes[idx] != b'<' {
+            let start = idx;
+            while idx < bytes.len() && bytes[idx] != b'<' {
+                idx += 1;
+            }
+            let content = unescape(text[start..idx].trim());
+            stack.last_mut().unwrap().text.push_str(&content);
+            continue;
+        }
+        if text[idx..].starts_with("<?") {
+            idx = skip_past(text, idx, "?>");
+            continue;
+        }
+        if text[idx..].starts_with("<!--") {
+            idx = skip_past(text, idx, "-->");
+            continue;
+        }
+        if text[idx..].starts_with("<!") {
+            idx = skip_past(text, idx, ">");
+            continue;
+        }
+        if text[idx..].starts_with("</") {
+            idx = skip_past(text, idx, ">");
+            if stack.len() > 1 {
+                let node = stack.pop().unwrap();
+                stack.last_mut().unwrap().children.push(node);
+            }
+            continue;
+        }
+        // opening tag
+        idx += 1;
+        let name = read_name(bytes, &mut idx);
+        let mut node = XmlNode::new(&name);
+        let mut self_closing = false;
+        loop {
+            skip_whitespace(bytes, &mut idx);
+            if idx >= bytes.len() {
+                break;
+            }
+            match bytes[idx] {
+                b'/' => {
+                    self_closing = true;
+                    idx += 1;
+                }
+                b'>' => {
+                    idx += 1;
+                    break;
+                }
+                _ => {
+                    let key = read_name(bytes, &mut idx);
+                    skip_whitespace(bytes, &mut idx);
+                    let mut value = String::new();
+                    if idx < bytes.len() && bytes[idx] == b'=' {
+                        idx += 1;
+                        skip_whitespace(bytes, &mut idx);
+                        // values must be quoted, anything else drops the attribute
+                        if idx >= bytes.len() || !matches!(bytes[idx], b'"' | b'\'') {
+                            println!("malformed value of the xml attribute {}", key);
+                            read_name(bytes, &mut idx);
+                            continue;
+                        }
+                        let quote = bytes[idx];
+                        idx += 1;
+                        let start = idx;
+                        while idx < bytes.len() && bytes[idx] != quote {
+                            idx += 1;
+                        }
+                        value = unescape(&text[start..idx]);
+                        idx += 1;
+                    } else if key.is_empty() {
+                        // malformed tag, skip a byte so we never loop forever
+                        idx += 1;
+                        continue;
+                    }
+                    node.attributes.insert(key, value);
+                }
+            }
+        }
+        if self_closing {
+            stack.last_mut().unwrap().children.push(node);
+        } else {
+            stack.push(node);
+        }
+    }
+    // close any element left open at the end of the file
+    while stack.len() > 1 {
+        let node = stack.pop().unwrap();
+        stack.last_mut().unwrap().children.push(node);
+    }
+    let mut root = stack.pop().unwrap();
+    if root.children.is_empty() {
+        return root;
+    }
+    return root.children.remove(0);
+}
+
+fn skip_past(text: &str, idx: usize, pattern: &str) -> usize {
+    return match text[idx..].find(pattern) {
+        Some(offset) => idx + offset + pattern.len(),
+        None => text.len(),
+    };
+}
+
+fn skip_whitespace(bytes: &[u8], idx: &mut usize) {
+    while *idx < bytes.len() && bytes[*idx].is_ascii_whitespace() {
+        *idx += 1;
+    }
+}
+
+fn read_name(bytes: &[u8], idx: &mut usize) -> String {
+    let mut name = String::new();
+    while *idx < bytes.len() {
+        let byte = bytes[*idx];
+        if byte.is_ascii_whitespace() || byte == b'=' || byte == b'>' || byte == b'/' {
+            break;
+        }
+        name.push(byte as char);
+        *idx += 1;
+    }
+    return name;
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_xml_reads_attributes_children_and_text() {
+        let root = parse_xml(
+            "<?xml version=\"1.0\"?>\n<!-- data -->\n<monsters>\n\
+             <monster name='Rat' corpse=\"2813\"/>\n<note>a &amp; b</note>\n</monsters>",
+        );
+        assert_eq!(root.name, "monsters");
+        let monster = root.child("monster").unwrap();
+        assert_eq!(monster.attr("name"), Some("Rat"));
+        assert_eq!(monster.attr_or("corpse", 0u16), 2813);
+        assert_eq!(root.child("note").unwrap().text, "a & b");
+    }
+
+    #[test]
+    fn parse_xml_drops_malformed_attributes() {
+        let root = parse_xml("<item id=");
+        assert_eq!(root.name, "item");
+        assert!(root.attr("id").is_none());
+        let root = parse_xml("<item id=3031 name=\"gold coin\" plural=\u{e9}x/>");
+        assert!(root.attr("id").is_none());
+        assert_eq!(root.attr("name"), Some("gold coin"));
+        assert!(root.attr("plural").is_none());
+    }
+}
@@ -0,0 +1,62 @@
+use crate::xml_io::read_xml_file;
+use std::collections::HashMap;
+
+/*
+*   <monsters>
+*       <monster name="Rat" corpse="2813" health="20" experience="5" speed="134" looktype="21">
+*           <loot>
+*               <item id="3031" chance="50000"/>
+*           </loot>
+*       </monster>
+*   </monsters>
+*
+*   loot chance is out of 100000, item ids are client ids
+*/
+
+pub const MAX_LOOT_CHANCE: u32 = 100000;
+
+#[derive(Clone)]
+pub struct LootItem {
+    pub client_id: u16,
+    pub chance: u32,
+}
+
+pub struct MonsterType {
+    pub name: String,
+    pub corpse_id: u16,
+    pub health: u16,
+    pub experience: u32,
+    pub speed: u16,
+    pub outfit_type: u16,
+    pub loot: Vec<LootItem>,
+}
+
+pub fn read_monsters(filepath: &str) -> HashMap<String, MonsterType> {
+    let root = read_xml_file(filepath);
+    let mut monster_types: HashMap<String, MonsterType> = HashMap::new();
+    for node in root.children_named("monster") {
+        let name = node.attr("name").unwrap_or_default().to_string();
+        let mut loot: Vec<LootItem> = Vec::new();
+        if let Some(loot_node) = node.child("loot") {
+            for item_node in loot_node.children_named("item") {
+                loot.push(LootItem {
+                    client_id: item_node.attr_or("id", 0),
+                    chance: item_node.attr_or("chance", MAX_LOOT_CHANCE),
+                });
+            }
+        }
+        monster_types.insert(
+            name.to_lowercase(),
+            MonsterType {
+                name,
+                corpse_id: node.attr_or("corpse", 0),
+                health: node.attr_or("health", 100),
+                experience: node.attr_or("experience", 0),
+                speed: node.attr_or("speed", 200),
+                outfit_type: node.attr_or("looktype", 0),
+                loot,
+            },
+        );
+    }
+    return monster_types;
+}
@@ -0,0 +1,352 @@
+use crate::{
+    condition::{Condition, ConditionType},
+    item::Item,
+    map::{TILE_FLAG_NO_LOGOUT, TILE_FLAG_NO_PVP, TILE_FLAG_PROTECTION_ZONE, TILE_FLAG_PVP_ZONE},
+    otb_io::item_loader::ItemData,
+    xml_io::{XmlNode, read_xml_file},
+};
+use std::{collections::HashMap, time::Duration};
+
+/*
+*   <movements>
+*       <movevent event="StepIn" itemid="426" action="transform" transformto="425"/>
+*       <movevent event="StepIn" actionid="1001" action="teleport" x="100" y="100" z="7"/>
+*       <movevent event="StepIn" uniqueid="5000" action="block" text="The door seems to be sealed."/>
+*       <movevent event="StepIn" tileflag="protectionzone" action="message" text="..."/>
+*       <movevent event="Equip" itemid="3049" action="condition" condition="invisible"/>
+*       <movevent event="DeEquip" itemid="3086" action="removecondition" condition="invisible"/>
+*   </movements>
+*
+*   events
+*       StepIn, StepOut     a creature walks onto or off a tile holding the item
+*       Equip, DeEquip      a player puts the item into or takes it out of an inventory slot
+*
+*   an event is registered for one of
+*       uniqueid, actionid  set on a single item in the map editor
+*       itemid              the client id of every item of that kind
+*       tileflag            protectionzone, nopvp, nologout or pvpzone, runs when the
+*                           creature crosses into or out of tiles having the flag
+*   an item with a unique id only runs the unique id events, one with an action id only the
+*   action id events if there are any, the item id events run otherwise
+*
+*   actions and their attributes
+*       transform       transformto, the item that triggered the event turns into it
+*       teleport        x, y, z, the creature is moved there instead
+*       block           text, the step is not made, only for StepIn and StepOut
+*       message         text, shown to the player
+*       condition       condition, speed, lightlevel, lightcolor, duration in seconds,
+*                       without a duration the condition lasts until it is removed
+*       removecondition condition
+*       script          script, runs on_step_in, on_step_out, on_equip or on_deequip of the
+*                       script in data/scripts
+*
+*   items with a <field> in items.xml get a StepIn field action on their own
+*/
+
+// how long conditions without a duration last, equipped items remove them on DeEquip
+const UNTIL_REMOVED: Duration = Duration::from_secs(60 * 60 * 24 * 365);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MoveEventType {
+    StepIn,
+    StepOut,
+    Equip,
+    DeEquip,
+}
+
+impl MoveEventType {
+    // the script function run for the event
+    pub fn script_function(&self) -> &'static str {
+        return match self {
+            MoveEventType::StepIn => "on_step_in",
+            MoveEventType::StepOut => "on_step_out",
+            MoveEventType::Equip => "on_equip",
+            MoveEventType::DeEquip => "on_deequip",
+        };
+    }
+
+    pub fn from_name(name: &str) -> Option<MoveEventType> {
+        return match name.to_lowercase().as_str() {
+            "stepin" => Some(MoveEventType::StepIn),
+            "stepout" => Some(MoveEventType::StepOut),
+            "equip" => Some(MoveEventType::Equip),
+            "deequip" => Some(MoveEventType::DeEquip),
+            _ => None,
+        };
+    }
+}
+
+#[derive(Clone)]
+pub struct ConditionSpec {
+    pub condition_type: ConditionType,
+    pub speed_change: i32,
+    pub light_level: u8,
+    pub light_color: u8,
+    pub duration: u32,
+}
+
+impl ConditionSpec {
+    pub fn create(&self) -> Condition {
+        let duration = match self.duration {
+            0 => UNTIL_REMOVED,
+            seconds => Duration::from_secs(seconds as u64),
+        };
+        let mut condition = Condition::timed(self.condition_type.clone(), duration);
+        condition.speed_change = self.speed_change;
+        condition.light_level = self.light_level;
+        condition.light_color = self.light_color;
+        return condition;
+    }
+}
+
+#[derive(Clone)]
+pub enum MoveAction {
+    Transform { to_item_id: u16 },
+    Teleport { destination: (u16, u16, u8) },
+    Block { text: String },
+    Message { text: String },
+    Field,
+    AddCondition(ConditionSpec),
+    RemoveCondition { condition_type: ConditionType },
+    Script { script: String },
+}
+
+pub struct Movements {
+    pub item_ids: HashMap<(MoveEventType, u16), Vec<MoveAction>>,
+    pub action_ids: HashMap<(MoveEventType, u16), Vec<MoveAction>>,
+    pub unique_ids: HashMap<(MoveEventType, u16), Vec<MoveAction>>,
+    pub tile_flags: Vec<(MoveEventType, u32, MoveAction)>,
+}
+
+impl Movements {
+    pub fn item_actions(&self, event_type: MoveEventType, item: &Item) -> &[MoveAction] {
+        if item.unique_id != 0 {
+            return match self.unique_ids.get(&(event_type, item.unique_id)) {
+                Some(actions) => actions,
+                None => &[],
+            };
+        }
+        if item.action_id != 0 {
+            if let Some(actions) = self.action_ids.get(&(event_type, item.action_id)) {
+                return actions;
+            }
+        }
+        return match self.item_ids.get(&(event_type, item.client_id)) {
+            Some(actions) => actions,
+            None => &[],
+        };
+    }
+
+    // actions of the flags in crossed, the flags one tile has and the other does not
+    pub fn flag_actions(&self, event_type: MoveEventType, crossed: u32) -> Vec<&MoveAction> {
+        return self
+            .tile_flags
+            .iter()
+            .filter(|(event, flag, _)| *event == event_type && crossed & flag != 0)
+            .map(|(_, _, action)| action)
+            .collect();
+    }
+}
+
+pub fn read_movements(filepath: &str, item_data: &HashMap<u16, ItemData>) -> Movements {
+    let root = read_xml_file(filepath);
+    let mut movements = Movements {
+        item_ids: HashMap::new(),
+        action_ids: HashMap::new(),
+        unique_ids: HashMap::new(),
+        tile_flags: vec![],
+    };
+    for node in root.children_named("movevent") {
+        let Some(event_type) = node.attr("event").and_then(MoveEventType::from_name) else {
+            println!("unknown movement event {:?}", node.attr("event"));
+            continue;
+        };
+        let Some(action) = parse_action(node) else {
+            println!("unknown movement action {:?}", node.attr("action"));
+            continue;
+        };
+        if let Some(id) = node.attr("uniqueid") {
+            let key = (event_type, id.parse::<u16>().unwrap_or(0));
+            movements.unique_ids.entry(key).or_default().push(action);
+        } else if let Some(id) = node.attr("actionid") {
+            let key = (event_type, id.parse::<u16>().unwrap_or(0));
+            movements.action_ids.entry(key).or_default().push(action);
+        } else if let Some(id) = node.attr("itemid") {
+            let key = (event_type, id.parse::<u16>().unwrap_or(0));
+            movements.item_ids.entry(key).or_default().push(action);
+        } else if let Some(flag) = node.attr("tileflag").and_then(tile_flag_from_name) {
+            movements.tile_flags.push((event_type, flag, action));
+        } else {
+            println!("movement event without an item or tile flag");
+        }
+    }
+    for (client_id, data) in item_data.iter() {
+        if data.field.is_some() {
+            movements
+                .item_ids
+                .entry((MoveEventType::StepIn, *client_id))
+                .or_default()
+                .push(MoveAction::Field);
+        }
+    }
+    return movements;
+}
+
+fn parse_action(node: &XmlNode) -> Option<MoveAction> {
+    return match node.attr("action")? {
+        "transform" => Some(MoveAction::Transform {
+            to_item_id: node.attr_or("transformto", 0),
+        }),
+        "teleport" => Some(MoveAction::Teleport {
+            destination: (
+                node.attr_or("x", 0),
+                node.attr_or("y", 0),
+                node.attr_or("z", 0),
+            ),
+        }),
+        "block" => Some(MoveAction::Block {
+            text: node
+                .attr("text")
+                .unwrap_or("Sorry, not possible.")
+                .to_string(),
+        }),
+        "message" => Some(MoveAction::Message {
+            text: node.attr("text")?.to_string(),
+        }),
+        "condition" => Some(MoveAction::AddCondition(ConditionSpec {
+            condition_type: ConditionType::from_name(node.attr("condition")?)?,
+            speed_change: node.attr_or("speed", 0),
+            light_level: node.attr_or("lightlevel", 0),
+            light_color: node.attr_or("lightcolor", 0xD7),
+            duration: node.attr_or("duration", 0),
+        })),
+        "removecondition" => Some(MoveAction::RemoveCondition {
+            condition_type: ConditionType::from_name(node.attr("condition")?)?,
+        }),
+        "script" => Some(MoveAction::Script {
+            script: node.attr("script")?.to_string(),
+        }),
+        _ => None,
+    };
+}
+
+fn tile_flag_from_name(name: &str) -> Option<u32> {
+    return match name {
+        "protectionzone" => Some(TILE_FLAG_PROTECTION_ZONE),
+        "nopvp" => Some(TILE_FLAG_NO_PVP),
+        "nologout" => Some(TILE_FLAG_NO_LOGOUT),
+        "pvpzone" => Some(TILE_FLAG_PVP_ZONE),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        otb_io::item_loader::ItemType,
+        xml_io::{items_loader::FieldEffect, test_file},
+    };
+
+    const TRAPDOOR_ID: u16 = 426;
+    const FIRE_FIELD_ID: u16 = 2118;
+
+    fn test_movements() -> Movements {
+        let filepath = test_file(
+            "movements.xml",
+            r#"<movements>
+                <movevent event="StepIn" itemid="426" action="transform" transformto="425"/>
+                <movevent event="StepIn" actionid="1001" action="teleport" x="100" y="101" z="7"/>
+                <movevent event="StepIn" uniqueid="5000" action="block"/>
+                <movevent event="StepIn" tileflag="protectionzone" action="message" text="Safe."/>
+                <movevent event="StepIn" itemid="427" action="explode"/>
+                <movevent event="Jump" itemid="427" action="block"/>
+            </movements>"#,
+        );
+        let mut fire_field = ItemData::new(FIRE_FIELD_ID, ItemType::Nothing, 0);
+        fire_field.field = Some(FieldEffect {
+            condition: ConditionType::Burning,
+            damage: 20,
+            tick_damage: 10,
+            ticks: 7,
+        });
+        let mut item_data = HashMap::new();
+        item_data.insert(FIRE_FIELD_ID, fire_field);
+        return read_movements(&filepath, &item_data);
+    }
+
+    #[test]
+    fn read_movements_registers_each_kind_of_id() {
+        let movements = test_movements();
+        assert_eq!(movements.item_ids.len(), 2);
+        assert_eq!(movements.action_ids.len(), 1);
+        assert_eq!(movements.unique_ids.len(), 1);
+        assert!(matches!(
+            movements.item_ids[&(MoveEventType::StepIn, FIRE_FIELD_ID)][..],
+            [MoveAction::Field]
+        ));
+        assert!(matches!(
+            movements.action_ids[&(MoveEventType::StepIn, 1001)][..],
+            [MoveAction::Teleport {
+                destination: (100, 101, 7)
+            }]
+        ));
+        match &movements.unique_ids[&(MoveEventType::StepIn, 5000)][..] {
+            [MoveAction::Block { text }] => assert_eq!(text, "Sorry, not possible."),
+            _ => panic!("expected a block action"),
+        }
+    }
+
+    #[test]
+    fn item_actions_prefer_unique_then_action_ids() {
+        let movements = test_movements();
+        let mut trapdoor = Item::new(TRAPDOOR_ID);
+        assert!(matches!(
+            movements.item_actions(MoveEventType::StepIn, &trapdoor),
+            [MoveAction::Transform { to_item_id: 425 }]
+        ));
+        assert!(
+            movements
+                .item_actions(MoveEventType::StepOut, &trapdoor)
+                .is_empty()
+        );
+        // an action id without events falls back to the item id
+        trapdoor.action_id = 1002;
+        assert!(matches!(
+            movements.item_actions(MoveEventType::StepIn, &trapdoor),
+            [MoveAction::Transform { .. }]
+        ));
+        trapdoor.action_id = 1001;
+        assert!(matches!(
+            movements.item_actions(MoveEventType::StepIn, &trapdoor),
+            [MoveAction::Teleport { .. }]
+        ));
+        // a unique id without events runs nothing
+        trapdoor.unique_id = 5001;
+        assert!(
+            movements
+                .item_actions(MoveEventType::StepIn, &trapdoor)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn flag_actions_run_for_crossed_flags() {
+        let movements = test_movements();
+        let crossed = TILE_FLAG_PROTECTION_ZONE | TILE_FLAG_NO_LOGOUT;
+        assert_eq!(
+            movements.flag_actions(MoveEventType::StepIn, crossed).len(),
+            1
+        );
+        assert!(
+            movements
+                .flag_actions(MoveEventType::StepIn, TILE_FLAG_NO_PVP)
+                .is_empty()
+        );
+        assert!(
+            movements
+                .flag_actions(MoveEventType::StepOut, crossed)
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use crate::{
+    creature::{Character, SKILL_COUNT},
+    item::Item,
+    xml_io::{XmlNode, read_xml_file, vocation_loader::Vocation, write_xml_file},
+};
+use std::collections::HashMap;
+
+/*
+*   <player name="Some Character" vocation="4" town="1" level="8" experience="4200"
+*       health="185" maxhealth="185" mana="35" maxmana="35" magiclevel="0" manaspent="0"
+*       soul="100" capacity="470" speed="220" x="1024" y="1024" z="7"
+*       looktype="128" head="80" body="80" legs="80" feet="80">
+*       <skill id="0" level="10" tries="0"/>
+*       <slot id="3">
+*           <item id="2854">
+*               <item id="3031" count="50"/>
+*               <item id="3198" charges="5" actionid="2000" uniqueid="6000"/>
+*               <item id="3505" text="Meet me at the depot." writer="Some Character" written="1700000000"/>
+*           </item>
+*       </slot>
+*       <storage key="1000" value="1"/>
+*       <depot town="1">
+*           <item id="3497"/>
+*       </depot>
+*   </player>
+*
+*   one file per player in data/players named after the player, written when the player
+*   logs out or loses the connection and every saveminutes minutes of config.xml while it is
+*   online. players without a file start as they are in create_characters
+*   skill ids are fist, club, sword, axe, distance, shielding, fishing
+*   a depot holds the locker of the player in that town
+*   characters of a vocation vocations.xml does not define get vocation 0
+*/
+
+pub const PLAYERS_DIRECTORY: &str = "./data/players";
+
+pub fn player_file(directory: &str, name: &str) -> String {
+    return format!("{}/{}.xml", directory, name.to_lowercase());
+}
+
+// overwrites the character with what was saved for it, returns false when nothing was saved
+pub fn read_player(
+    directory: &str,
+    character: &mut Character,
+    vocations: &HashMap<u8, Vocation>,
+) -> bool {
+    let found = read_player_file(directory, character);
+    if !vocations.contains_key(&character.vocation) {
+        println!(
+            "{} has the unknown vocation {}",
+            character.name, character.vocation
+        );
+        character.vocation = 0;
+    }
+    return found;
+}
+
+fn read_player_file(directory: &str, character: &mut Character) -> bool {
+    let filepath = player_file(directory, &character.name);
+    if std::fs::metadata(&filepath).is_err() {
+        return false;
+    }
+    let root = read_xml_file(&filepath);
+    character.vocation = root.attr_or("vocation", character.vocation);
+    character.town_id = root.attr_or("town", character.town_id);
+    character.level = root.attr_or("level", character.level);
+    character.experience = root.attr_or("experience", character.experience);
+    character.health = root.attr_or("health", character.health);
+    character.max_health = root.attr_or("maxhealth", character.max_health);
+    character.mana = root.attr_or("mana", character.mana);
+    character.max_mana = root.attr_or("maxmana", character.max_mana);
+    character.magic_level = root.attr_or("magiclevel", character.magic_level);
+    character.mana_spent = root.attr_or("manaspent", character.mana_spent);
+    character.soul = root.attr_or("soul", character.soul);
+    character.capacity = root.attr_or("capacity", character.capacity);
+    character.base_speed = root.attr_or("speed", character.base_speed);
+    character.speed = character.base_speed;
+    character.position = (
+        root.attr_or("x", character.position.0),
+        root.attr_or("y", character.position.1),
+        root.attr_or("z", character.position.2),
+    );
+    character.outfit_type = root.attr_or("looktype", character.outfit_type);
+    character.outfit = [
+        root.attr_or("head", character.outfit[0]),
+        root.attr_or("body", character.outfit[1]),
+        root.attr_or("legs", character.outfit[2]),
+        root.attr_or("feet", character.outfit[3]),
+    ];
+    for node in root.children_named("skill") {
+        let id: usize = node.attr_or("id", SKILL_COUNT);
+        if id >= SKILL_COUNT {
+            continue;
+        }
+        character.skills[id].level = node.attr_or("level", character.skills[id].level);
+        character.skills[id].tries = node.attr_or("tries", 0);
+    }
+    for node in root.children_named("slot") {
+        let slot: u16 = node.attr_or("id", 0);
+        character.inventory.remove_from_slot(slot);
+        if let Some(item) = node.child("item") {
+            character.inventory.equip(slot, read_item(item));
+        }
+    }
+    character.depots.clear();
+    for node in root.children_named("depot") {
+        if let Some(item) = node.child("item") {
+            character
+                .depots
+                .insert(node.attr_or("town", 0), read_item(item));
+        }
+    }
+    character.storage.clear();
+    for node in root.children_named("storage") {
+        character
+            .storage
+            .insert(node.attr_or("key", 0), node.attr_or("value", 0));
+    }
+    return true;
+}
+
+pub fn read_item(node: &XmlNode) -> Item {
+    let mut item = Item::new(node.attr_or("id", 0));
+    item.count = node.attr_or("count", 1);
+    item.subtype = node.attr_or("subtype", 0);
+    item.charges = node.attr_or("charges", 0);
+    item.action_id = node.attr_or("actionid", 0);
+    item.unique_id = node.attr_or("uniqueid", 0);
+    item.text = node.attr("text").unwrap_or("").to_string();
+    item.writer = node.attr("writer").unwrap_or("").to_string();
+    item.written_at = node.attr_or("written", 0);
+    for child in node.children_named("item") {
+        item.items.push(read_item(child));
+    }
+    return item;
+}
+
+pub fn save_player(directory: &str, character: &Character) {
+    if let Err(error) = std::fs::create_dir_all(directory) {
+        println!("failed to create {}: {}", directory, error);
+        return;
+    }
+    let mut root = XmlNode::new("player")
+        .with_attr("name", &character.name)
+        .with_attr("vocation", character.vocation)
+        .with_attr("town", character.town_id)
+        .with_attr("level", character.level)
+        .with_attr("experience", character.experience)
+        .with_attr("health", character.health)
+        .with_attr("maxhealth", character.max_health)
+        .with_attr("mana", character.mana)
+        .with_attr("maxmana", character.max_mana)
+        .with_attr("magiclevel", character.magic_level)
+        .with_attr("manaspent", character.mana_spent)
+        .with_attr("soul", character.soul)
+        .with_attr("capacity", character.capacity)
+        .with_attr("speed", character.base_speed)
+        .with_attr("x", character.position.0)
+        .with_attr("y", character.position.1)
+        .with_attr("z", character.position.2)
+        .with_attr("looktype", character.outfit_type)
+        .with_attr("head", character.outfit[0])
+        .with_attr("body", character.outfit[1])
+        .with_attr("legs", character.outfit[2])
+        .with_attr("feet", character.outfit[3]);
+    for (id, skill) in character.skills.iter().enumerate() {
+        root.children.push(
+            XmlNode::new("skill")
+                .with_attr("id", id)
+                .with_attr("level", skill.level)
+                .with_attr("tries", skill.tries),
+        );
+    }
+    for slot in 1..=10 {
+        if let Some(item) = character.inventory.clone().get_from_slot(slot) {
+            let mut node = XmlNode::new("slot").with_attr("id", slot);
+            node.children.push(item_node(&item));
+            root.children.push(node);
+        }
+    }
+    let mut keys: Vec<&u32> = character.storage.keys().collect();
+    keys.sort();
+    for key in keys {
+        root.children.push(
+            XmlNode::new("storage")
+                .with_attr("key", key)
+                .with_attr("value", character.storage[key]),
+        );
+    }
+    let mut towns: Vec<&u32> = character.depots.keys().collect();
+    towns.sort();
+    for town in towns {
+        let mut node = XmlNode::new("depot").with_attr("town", town);
+        node.children.push(item_node(&character.depots[town]));
+        root.children.push(node);
+    }
+    write_xml_file(&player_file(directory, &character.name), &root);
+}
+
+pub fn item_node(item: &Item) -> XmlNode {
+    let mut node = XmlNode::new("item").with_attr("id", item.client_id);
+    if item.count != 1 {
+        node = node.with_attr("count", item.count);
+    }
+    if item.subtype != 0 {
+        node = node.with_attr("subtype", item.subtype);
+    }
+    if item.charges != 0 {
+        node = node.with_attr("charges", item.charges);
+    }
+    if item.action_id != 0 {
+        node = node.with_attr("actionid", item.action_id);
+    }
+    if item.unique_id != 0 {
+        node = node.with_attr("uniqueid", item.unique_id);
+    }
+    if !item.text.is_empty() {
+        node = node
+            .with_attr("text", &item.text)
+            .with_attr("writer", &item.writer)
+            .with_attr("written", item.written_at);
+    }
+    for inner_item in item.items.iter() {
+        node.children.push(item_node(inner_item));
+    }
+    return node;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        creature::{SKILL_FIST, create_characters},
+        xml_io::{test_file, vocation_loader::read_vocations},
+    };
+
+    fn test_vocations() -> HashMap<u8, Vocation> {
+        let filepath = test_file(
+            "player-vocations.xml",
+            r#"<vocations>
+                <vocation id="4" name="Knight" description="a knight"/>
+            </vocations>"#,
+        );
+        return read_vocations(&filepath);
+    }
+
+    fn test_directory(test: &str) -> String {
+        let directory = std::env::temp_dir().join(format!("tibia-{}-{}", std::process::id(), test));
+        return directory.to_string_lossy().to_string();
+    }
+
+    #[test]
+    fn save_player_round_trips_through_read_player() {
+        let directory = test_directory("players");
+        let mut saved = create_characters().remove(0);
+        saved.level = 20;
+        saved.position = (1000, 1001, 6);
+        saved.skills[SKILL_FIST].tries = 42;
+        saved.storage.insert(1000, 3);
+        saved.storage.insert(1001, -5);
+        let mut backpack = Item::new(2854);
+        let mut coins = Item::new(3031);
+        coins.count = 50;
+        backpack.items.push(coins);
+        saved.inventory.equip(3, backpack);
+        save_player(&directory, &saved);
+
+        let mut character = create_characters().remove(0);
+        assert!(read_player(&directory, &mut character, &test_vocations()));
+        assert_eq!(character.level, 20);
+        assert_eq!(character.position, (1000, 1001, 6));
+        assert_eq!(character.skills[SKILL_FIST].tries, 42);
+        assert_eq!(character.storage, saved.storage);
+        let backpack = character.inventory.get_from_slot(3).unwrap();
+        assert_eq!(backpack.client_id, 2854);
+        assert_eq!(
+            (backpack.items[0].client_id, backpack.items[0].count),
+            (3031, 50)
+        );
+    }
+
+    #[test]
+    fn read_player_falls_back_to_vocation_0() {
+        let directory = test_directory("players-unsaved");
+        let mut character = create_characters().remove(1);
+        assert!(!read_player(&directory, &mut character, &test_vocations()));
+        assert_eq!(character.vocation, 0);
+        let mut knight = create_characters().remove(0);
+        read_player(&directory, &mut knight, &test_vocations());
+        assert_eq!(knight.vocation, 4);
+    }
+
+    #[test]
+    fn depots_round_trip_by_town() {
+        let directory = test_directory("players-depots");
+        let mut saved = create_characters().remove(0);
+        saved.locker(1).add_item(Item::new(3357));
+        saved.locker(2);
+        save_player(&directory, &saved);
+
+        let mut character = create_characters().remove(0);
+        character.locker(3);
+        read_player(&directory, &mut character, &test_vocations());
+        let mut towns: Vec<&u32> = character.depots.keys().collect();
+        towns.sort();
+        assert_eq!(towns, vec![&1, &2]);
+        assert_eq!(character.depots[&1].items[0].client_id, 3357);
+        assert!(character.depots[&2].items.is_empty());
+    }
+
+    #[test]
+    fn item_node_keeps_the_text_and_its_writer() {
+        let mut letter = Item::new(3505);
+        letter.text = "Meet me at the \"depot\" & bring <gold>.".to_string();
+        letter.writer = "Some Character".to_string();
+        letter.written_at = 1700000000;
+        let mut bag = Item::new(2854);
+        bag.items.push(letter);
+        bag.items.push(Item::new(3507));
+        let read = read_item(&item_node(&bag));
+        assert_eq!(read.items.len(), 2);
+        let letter = &read.items[0];
+        assert_eq!(letter.text, "Meet me at the \"depot\" & bring <gold>.");
+        assert_eq!(letter.writer, "Some Character");
+        assert_eq!(letter.written_at, 1700000000);
+        // items without a text save no writer either
+        let blank = &read.items[1];
+        assert!(blank.text.is_empty() && blank.writer.is_empty());
+        assert!(item_node(&bag.items[1]).attr("writer").is_none());
+    }
+}
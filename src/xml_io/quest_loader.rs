@@ -0,0 +1,143 @@
+use crate::xml_io::read_xml_file;
+use std::collections::HashMap;
+
+/*
+*   <quests>
+*       <quest name="The Rat Cellar" startstorageid="1000" startstoragevalue="1">
+*           <mission name="Rats in the Cellar" storageid="1000" startvalue="1" endvalue="3">
+*               <missionstate id="1" description="Kill the rats below the tavern."/>
+*               <missionstate id="3" description="The cellar is free of rats."/>
+*           </mission>
+*       </quest>
+*   </quests>
+*
+*   quests are shown in the quest log once the start storage of the player has reached the
+*   start value, missions once their storage has reached the start value. a quest is done when
+*   all of its missions reached their end value. a mission shows the description of the highest
+*   state its storage reached. quest ids are their place in the file counting from 1
+*/
+
+pub struct Mission {
+    pub name: String,
+    pub storage_key: u32,
+    pub start_value: i32,
+    pub end_value: i32,
+    pub states: HashMap<i32, String>,
+}
+
+impl Mission {
+    pub fn is_started(&self, storage: &HashMap<u32, i32>) -> bool {
+        return storage_value(storage, self.storage_key) >= self.start_value;
+    }
+
+    pub fn is_completed(&self, storage: &HashMap<u32, i32>) -> bool {
+        return storage_value(storage, self.storage_key) >= self.end_value;
+    }
+
+    pub fn description(&self, storage: &HashMap<u32, i32>) -> String {
+        let value = storage_value(storage, self.storage_key);
+        return self
+            .states
+            .iter()
+            .filter(|(state, _)| **state <= value)
+            .max_by_key(|(state, _)| **state)
+            .map(|(_, description)| description.clone())
+            .unwrap_or_default();
+    }
+}
+
+pub struct Quest {
+    pub id: u16,
+    pub name: String,
+    pub start_key: u32,
+    pub start_value: i32,
+    pub missions: Vec<Mission>,
+}
+
+impl Quest {
+    pub fn is_started(&self, storage: &HashMap<u32, i32>) -> bool {
+        return storage_value(storage, self.start_key) >= self.start_value;
+    }
+
+    pub fn is_completed(&self, storage: &HashMap<u32, i32>) -> bool {
+        return self
+            .missions
+            .iter()
+            .all(|mission| mission.is_completed(storage));
+    }
+}
+
+// unset storage values read as -1
+pub fn storage_value(storage: &HashMap<u32, i32>, key: u32) -> i32 {
+    return *storage.get(&key).unwrap_or(&-1);
+}
+
+pub fn read_quests(filepath: &str) -> Vec<Quest> {
+    let root = read_xml_file(filepath);
+    let mut quests: Vec<Quest> = Vec::new();
+    for node in root.children_named("quest") {
+        let mut quest = Quest {
+            id: quests.len() as u16 + 1,
+            name: node.attr("name").unwrap_or("").to_string(),
+            start_key: node.attr_or("startstorageid", 0),
+            start_value: node.attr_or("startstoragevalue", 0),
+            missions: vec![],
+        };
+        for mission_node in node.children_named("mission") {
+            let mut mission = Mission {
+                name: mission_node.attr("name").unwrap_or("").to_string(),
+                storage_key: mission_node.attr_or("storageid", 0),
+                start_value: mission_node.attr_or("startvalue", 0),
+                end_value: mission_node.attr_or("endvalue", 0),
+                states: HashMap::new(),
+            };
+            for state_node in mission_node.children_named("missionstate") {
+                mission.states.insert(
+                    state_node.attr_or("id", 0),
+                    state_node.attr("description").unwrap_or("").to_string(),
+                );
+            }
+            quest.missions.push(mission);
+        }
+        quests.push(quest);
+    }
+    return quests;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml_io::test_file;
+
+    #[test]
+    fn quests_follow_the_storage_values() {
+        let filepath = test_file(
+            "quests.xml",
+            r#"<quests>
+                <quest name="The Rat Cellar" startstorageid="1000" startstoragevalue="1">
+                    <mission name="Rats in the Cellar" storageid="1000" startvalue="1" endvalue="3">
+                        <missionstate id="1" description="Kill the rats below the tavern."/>
+                        <missionstate id="3" description="The cellar is free of rats."/>
+                    </mission>
+                </quest>
+            </quests>"#,
+        );
+        let quests = read_quests(&filepath);
+        let quest = &quests[0];
+        let mission = &quest.missions[0];
+        assert_eq!(quest.id, 1);
+        let mut storage: HashMap<u32, i32> = HashMap::new();
+        assert_eq!(storage_value(&storage, 1000), -1);
+        assert!(!quest.is_started(&storage));
+        storage.insert(1000, 2);
+        assert!(quest.is_started(&storage) && mission.is_started(&storage));
+        assert!(!quest.is_completed(&storage));
+        assert_eq!(
+            mission.description(&storage),
+            "Kill the rats below the tavern."
+        );
+        storage.insert(1000, 3);
+        assert!(quest.is_completed(&storage));
+        assert_eq!(mission.description(&storage), "The cellar is free of rats.");
+    }
+}
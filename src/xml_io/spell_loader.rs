@@ -0,0 +1,361 @@
+use crate::{
+    condition::ConditionType,
+    xml_io::{XmlNode, read_xml_file},
+};
+use std::collections::HashMap;
+
+/*
+*   <spells>
+*       <instant name="Light Healing" words="exura" level="9" magiclevel="1" mana="20"
+*           cooldown="1000" vocations="1;2;3" magiceffect="12" effect="heal" min="20" max="40"/>
+*       <instant name="Berserk" words="exori" ... effect="areadamage" min="30" max="60">
+*           <area>
+*               <row>1 1 1</row>
+*               <row>1 2 1</row>
+*               <row>1 1 1</row>
+*           </area>
+*       </instant>
+*       <rune name="Sudden Death" id="3155" level="45" magiclevel="15" charges="1" range="7"
+*           needtarget="1" shooteffect="10" magiceffect="17" effect="damage" min="150" max="300"/>
+*   </spells>
+*
+*   effects and their attributes
+*       heal        min, max
+*       damage      min, max, instant spells hit the tile in front of the caster
+*       areadamage  min, max, area rows (1 - hit, 2 - caster, 3 - caster and hit)
+*       haste       speed, duration
+*       paralyze    speed (taken away), duration
+*       light       lightlevel, lightcolor, duration
+*       invisible   duration
+*       cure        condition (poison, burning, electrified, paralyze, drunk ...)
+*       field       fieldid, optional area, instant spells place it in front of the caster
+*       summon      the monster name is the spell parameter: utevo res "rat
+*
+*   cooldown is in milliseconds, duration in seconds, magiceffect is the 0 based effect id
+*   vocations are allowed in addition to the ones listing the spell in vocations.xml
+*   rune areas are centered on the target tile, shooteffect is the 0 based distance effect
+*   needtarget runes can only be used on a creature
+*/
+
+#[derive(Clone)]
+pub enum SpellEffect {
+    Heal {
+        min: u16,
+        max: u16,
+    },
+    Damage {
+        min: u16,
+        max: u16,
+    },
+    AreaDamage {
+        min: u16,
+        max: u16,
+        area: Vec<(i16, i16)>,
+    },
+    Haste {
+        speed: u16,
+        duration: u32,
+    },
+    Paralyze {
+        speed: u16,
+        duration: u32,
+    },
+    Light {
+        level: u8,
+        color: u8,
+        duration: u32,
+    },
+    Invisible {
+        duration: u32,
+    },
+    Cure {
+        condition: ConditionType,
+    },
+    Field {
+        item_id: u16,
+        area: Vec<(i16, i16)>,
+    },
+    Summon,
+}
+
+#[derive(Clone)]
+pub struct Spell {
+    pub name: String,
+    pub words: String,
+    pub level: u16,
+    pub magic_level: u8,
+    pub mana: u16,
+    pub cooldown: u32,
+    pub vocations: Vec<u8>,
+    pub magic_effect: u8,
+    pub effect: SpellEffect,
+}
+
+#[derive(Clone)]
+pub struct Rune {
+    pub name: String,
+    pub client_id: u16,
+    pub level: u16,
+    pub magic_level: u8,
+    pub charges: u8,
+    pub range: u16,
+    pub need_target: bool,
+    pub shoot_effect: Option<u8>,
+    pub magic_effect: u8,
+    pub effect: SpellEffect,
+}
+
+pub fn read_spells(filepath: &str) -> Vec<Spell> {
+    let root = read_xml_file(filepath);
+    let mut spells: Vec<Spell> = Vec::new();
+    for node in root.children_named("instant") {
+        let effect = match parse_effect(node) {
+            Some(effect) => effect,
+            None => {
+                println!("unknown spell effect for {:?}", node.attr("name"));
+                continue;
+            }
+        };
+        let vocations: Vec<u8> = node
+            .attr("vocations")
+            .unwrap_or_default()
+            .split(';')
+            .filter_map(|id| id.trim().parse::<u8>().ok())
+            .collect();
+        spells.push(Spell {
+            name: node.attr("name").unwrap_or_default().to_string(),
+            words: node.attr("words").unwrap_or_default().to_lowercase(),
+            level: node.attr_or("level", 0),
+            magic_level: node.attr_or("magiclevel", 0),
+            mana: node.attr_or("mana", 0),
+            cooldown: node.attr_or("cooldown", 1000),
+            vocations,
+            magic_effect: node.attr_or("magiceffect", 0),
+            effect,
+        });
+    }
+    return spells;
+}
+
+// runes keyed by their client item id
+pub fn read_runes(filepath: &str) -> HashMap<u16, Rune> {
+    let root = read_xml_file(filepath);
+    let mut runes: HashMap<u16, Rune> = HashMap::new();
+    for node in root.children_named("rune") {
+        let effect = match parse_effect(node) {
+            Some(effect) => effect,
+            None => {
+                println!("unknown rune effect for {:?}", node.attr("name"));
+                continue;
+            }
+        };
+        let client_id: u16 = node.attr_or("id", 0);
+        runes.insert(
+            client_id,
+            Rune {
+                name: node.attr("name").unwrap_or_default().to_string(),
+                client_id,
+                level: node.attr_or("level", 0),
+                magic_level: node.attr_or("magiclevel", 0),
+                charges: node.attr_or("charges", 1),
+                range: node.attr_or("range", 7),
+                need_target: node.attr_or("needtarget", 0u8) == 1,
+                shoot_effect: node
+                    .attr("shooteffect")
+                    .and_then(|id| id.parse::<u8>().ok()),
+                magic_effect: node.attr_or("magiceffect", 0),
+                effect,
+            },
+        );
+    }
+    return runes;
+}
+
+fn parse_effect(node: &XmlNode) -> Option<SpellEffect> {
+    return match node.attr("effect")? {
+        "heal" => Some(SpellEffect::Heal {
+            min: node.attr_or("min", 0),
+            max: node.attr_or("max", 0),
+        }),
+        "damage" => Some(SpellEffect::Damage {
+            min: node.attr_or("min", 0),
+            max: node.attr_or("max", 0),
+        }),
+        "areadamage" => Some(SpellEffect::AreaDamage {
+            min: node.attr_or("min", 0),
+            max: node.attr_or("max", 0),
+            area: parse_area(node.child("area")?),
+        }),
+        "haste" => Some(SpellEffect::Haste {
+            speed: node.attr_or("speed", 0),
+            duration: node.attr_or("duration", 0),
+        }),
+        "paralyze" => Some(SpellEffect::Paralyze {
+            speed: node.attr_or("speed", 0),
+            duration: node.attr_or("duration", 0),
+        }),
+        "invisible" => Some(SpellEffect::Invisible {
+            duration: node.attr_or("duration", 0),
+        }),
+        "field" => Some(SpellEffect::Field {
+            item_id: node.attr_or("fieldid", 0),
+            area: match node.child("area") {
+                Some(area_node) => parse_area(area_node),
+                None => vec![(0, 0)],
+            },
+        }),
+        "cure" => Some(SpellEffect::Cure {
+            condition: ConditionType::from_name(node.attr("condition")?)?,
+        }),
+        "light" => Some(SpellEffect::Light {
+            level: node.attr_or("lightlevel", 0),
+            color: node.attr_or("lightcolor", 0xD7),
+            duration: node.attr_or("duration", 0),
+        }),
+        "summon" => Some(SpellEffect::Summon),
+        _ => None,
+    };
+}
+
+// offsets of the hit tiles relative to the caster, or the target tile for runes
+pub fn parse_area(area_node: &XmlNode) -> Vec<(i16, i16)> {
+    let rows: Vec<Vec<u8>> = area_node
+        .children_named("row")
+        .map(|row| {
+            row.text
+                .split_whitespace()
+                .filter_map(|cell| cell.parse::<u8>().ok())
+                .collect()
+        })
+        .collect();
+    let mut center: (i16, i16) = (0, 0);
+    for (y, row) in rows.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if *cell == 2 || *cell == 3 {
+                center = (x as i16, y as i16);
+            }
+        }
+    }
+    let mut area: Vec<(i16, i16)> = Vec::new();
+    for (y, row) in rows.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if *cell == 1 || *cell == 3 {
+                area.push((x as i16 - center.0, y as i16 - center.1));
+            }
+        }
+    }
+    return area;
+}
+
+// matches the start of a sentence against the spell words, returns the spell and its parameter
+pub fn find_spell<'a>(spells: &'a [Spell], text: &str) -> Option<(&'a Spell, String)> {
+    let text = text.trim().to_lowercase();
+    let mut found: Option<(&Spell, String)> = None;
+    for spell in spells.iter() {
+        if !text.starts_with(&spell.words) {
+            continue;
+        }
+        let rest = text[spell.words.len()..].trim();
+        if !rest.is_empty() && !rest.starts_with('"') {
+            continue;
+        }
+        // longest words win, "utevo lux" over "utevo"
+        if let Some((current, _)) = found {
+            if current.words.len() >= spell.words.len() {
+                continue;
+            }
+        }
+        found = Some((spell, rest.trim_matches('"').trim().to_string()));
+    }
+    return found;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml_io::{parse_xml, test_file};
+
+    #[test]
+    fn read_spells_skips_unknown_effects() {
+        let filepath = test_file(
+            "spells.xml",
+            r#"<spells>
+                <instant name="Light" words="Utevo Lux" mana="20" vocations="1; 2" effect="light"
+                    lightlevel="6" duration="360"/>
+                <instant name="Creature Illusion" words="utevo res ina" effect="illusion"/>
+            </spells>"#,
+        );
+        let spells = read_spells(&filepath);
+        assert_eq!(spells.len(), 1);
+        assert_eq!(spells[0].words, "utevo lux");
+        assert_eq!(spells[0].vocations, vec![1, 2]);
+        assert_eq!(spells[0].cooldown, 1000);
+        assert!(matches!(
+            spells[0].effect,
+            SpellEffect::Light {
+                level: 6,
+                color: 0xD7,
+                duration: 360
+            }
+        ));
+    }
+
+    #[test]
+    fn find_spell_takes_the_longest_words_and_a_quoted_parameter() {
+        let filepath = test_file(
+            "find_spells.xml",
+            r#"<spells>
+                <instant name="Light" words="utevo lux" effect="light"/>
+                <instant name="Summon Creature" words="utevo res" effect="summon"/>
+                <instant name="Great Light" words="utevo gran lux" effect="light"/>
+            </spells>"#,
+        );
+        let spells = read_spells(&filepath);
+        let (spell, param) = find_spell(&spells, " Utevo Gran Lux ").unwrap();
+        assert_eq!((spell.name.as_str(), param.as_str()), ("Great Light", ""));
+        let (spell, param) = find_spell(&spells, "utevo res \"rat\"").unwrap();
+        assert_eq!(
+            (spell.name.as_str(), param.as_str()),
+            ("Summon Creature", "rat")
+        );
+        // words followed by anything but a parameter are just talk
+        assert!(find_spell(&spells, "utevo luxury").is_none());
+        assert!(find_spell(&spells, "hello").is_none());
+    }
+
+    #[test]
+    fn parse_area_is_relative_to_the_caster() {
+        let area = parse_area(&parse_xml(
+            "<area><row>0 1 0</row><row>1 2 1</row><row>0 1 0</row></area>",
+        ));
+        assert_eq!(area, vec![(0, -1), (-1, 0), (1, 0), (0, 1)]);
+        // 3 hits the tile of the caster too
+        let area = parse_area(&parse_xml("<area><row>1 3</row></area>"));
+        assert_eq!(area, vec![(-1, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn read_runes_fills_in_default_charges_and_range() {
+        let filepath = test_file(
+            "runes.xml",
+            r#"<runes>
+                <rune name="Sudden Death" id="3155" level="45" magiclevel="15" needtarget="1"
+                    shooteffect="31" effect="damage" min="150" max="300"/>
+                <rune name="Ultimate Healing" id="3160" charges="3" range="1" effect="heal"/>
+                <rune name="Broken" id="3161" effect="nothing"/>
+            </runes>"#,
+        );
+        let runes = read_runes(&filepath);
+        assert_eq!(runes.len(), 2);
+        let sudden_death = &runes[&3155];
+        assert_eq!(sudden_death.charges, 1);
+        assert_eq!(sudden_death.range, 7);
+        assert!(sudden_death.need_target);
+        assert_eq!(sudden_death.shoot_effect, Some(31));
+        let healing = &runes[&3160];
+        assert_eq!(healing.charges, 3);
+        assert_eq!(healing.range, 1);
+        assert!(!healing.need_target);
+        assert_eq!(healing.shoot_effect, None);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::xml_io::read_xml_file;
+
+/*
+*   <talkactions>
+*       <talkaction words="!pos" script="commands.rhai"/>
+*   </talkactions>
+*
+*   a player saying the words, optionally followed by a parameter, runs on_say of the script
+*   instead of being heard
+*/
+
+#[derive(Clone)]
+pub struct TalkAction {
+    pub words: String,
+    pub script: String,
+}
+
+pub fn read_talkactions(filepath: &str) -> Vec<TalkAction> {
+    let root = read_xml_file(filepath);
+    let mut talkactions: Vec<TalkAction> = Vec::new();
+    for node in root.children_named("talkaction") {
+        let (Some(words), Some(script)) = (node.attr("words"), node.attr("script")) else {
+            println!("talkaction without words or script");
+            continue;
+        };
+        talkactions.push(TalkAction {
+            words: words.to_lowercase(),
+            script: script.to_string(),
+        });
+    }
+    return talkactions;
+}
+
+// the talk action a sentence starts with and the parameter following its words
+pub fn find_talkaction<'a>(
+    talkactions: &'a [TalkAction],
+    text: &str,
+) -> Option<(&'a TalkAction, String)> {
+    let text = text.trim();
+    for talkaction in talkactions.iter() {
+        if !text.to_lowercase().starts_with(&talkaction.words) {
+            continue;
+        }
+        let rest = text.get(talkaction.words.len()..).unwrap_or_default();
+        if rest.is_empty() || rest.starts_with(' ') {
+            return Some((talkaction, rest.trim().trim_matches('"').to_string()));
+        }
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn talkaction(words: &str) -> TalkAction {
+        return TalkAction {
+            words: words.to_string(),
+            script: "commands.rhai".to_string(),
+        };
+    }
+
+    #[test]
+    fn find_talkaction_splits_off_the_parameter() {
+        let talkactions = vec![talkaction("!pos"), talkaction("!buyhouse")];
+        let (found, param) = find_talkaction(&talkactions, "  !POS").unwrap();
+        assert_eq!((found.words.as_str(), param.as_str()), ("!pos", ""));
+        let (found, param) = find_talkaction(&talkactions, "!buyhouse \"Thais 1\"").unwrap();
+        assert_eq!(
+            (found.words.as_str(), param.as_str()),
+            ("!buyhouse", "Thais 1")
+        );
+        // the words have to end where the parameter starts
+        assert!(find_talkaction(&talkactions, "!position").is_none());
+        assert!(find_talkaction(&talkactions, "hello").is_none());
+    }
+}
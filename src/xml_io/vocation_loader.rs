@@ -0,0 +1,220 @@
+use crate::{
+    creature::{MAGIC_LEVEL_BASE_MANA, MIN_SKILL_LEVEL, SKILL_BASE_TRIES, SKILL_COUNT},
+    xml_io::{XmlNode, read_xml_file},
+};
+use std::collections::{HashMap, hash_map::Entry};
+
+/*
+*   <vocations>
+*       <vocation id="1" name="Sorcerer" description="a sorcerer" gainhp="5" gainmana="30"
+*           gaincap="10" gainhpticks="6" gainhpamount="1" gainmanaticks="3" gainmanaamount="2"
+*           manamultiplier="1.1" attackspeed="2000" fromvoc="1">
+*           <skill fist="1.5" club="2.0" sword="2.0" axe="2.0" distance="2.0" shielding="1.5"
+*               fishing="1.1"/>
+*           <spell name="Light Healing"/>
+*       </vocation>
+*   </vocations>
+*
+*   fromvoc is the vocation this one is promoted from, base vocations point at themselves
+*   regeneration ticks are in seconds, attack speed in milliseconds
+*/
+
+const SKILL_NAMES: [&str; SKILL_COUNT] = [
+    "fist",
+    "club",
+    "sword",
+    "axe",
+    "distance",
+    "shielding",
+    "fishing",
+];
+
+pub struct Vocation {
+    pub id: u8,
+    pub description: String,
+    pub from_vocation: u8,
+    pub gain_health: u16,
+    pub gain_mana: u16,
+    pub gain_capacity: u16,
+    pub gain_health_ticks: u32,
+    pub gain_health_amount: u16,
+    pub gain_mana_ticks: u32,
+    pub gain_mana_amount: u16,
+    pub mana_multiplier: f64,
+    pub skill_multipliers: [f64; SKILL_COUNT],
+    pub attack_speed: u32,
+    pub spells: Vec<String>,
+}
+
+impl Vocation {
+    // tries needed to advance from `level` to `level + 1`
+    pub fn skill_tries_for_level(&self, skill: usize, level: u16) -> u32 {
+        let exponent = level as i32 - MIN_SKILL_LEVEL as i32;
+        return (SKILL_BASE_TRIES[skill] as f64 * self.skill_multipliers[skill].powi(exponent))
+            as u32;
+    }
+
+    // mana that has to be spent to advance from `magic_level` to `magic_level + 1`
+    pub fn mana_for_magic_level(&self, magic_level: u8) -> u32 {
+        return (MAGIC_LEVEL_BASE_MANA as f64 * self.mana_multiplier.powi(magic_level as i32))
+            as u32;
+    }
+
+    pub fn can_cast(&self, spell_name: &str) -> bool {
+        return self
+            .spells
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(spell_name));
+    }
+}
+
+pub fn read_vocations(filepath: &str) -> HashMap<u8, Vocation> {
+    let root = read_xml_file(filepath);
+    let mut vocations: HashMap<u8, Vocation> = HashMap::new();
+    for node in root.children_named("vocation") {
+        let vocation = parse_vocation(node);
+        vocations.insert(vocation.id, vocation);
+    }
+    if let Entry::Vacant(entry) = vocations.entry(0) {
+        println!(
+            "{} defines no vocation 0, it is added with the defaults",
+            filepath
+        );
+        entry.insert(parse_vocation(&XmlNode::new("vocation")));
+    }
+    return vocations;
+}
+
+fn parse_vocation(node: &XmlNode) -> Vocation {
+    let id: u8 = node.attr_or("id", 0);
+    let mut skill_multipliers = [1.0; SKILL_COUNT];
+    if let Some(skill_node) = node.child("skill") {
+        for (skill, name) in SKILL_NAMES.iter().enumerate() {
+            skill_multipliers[skill] = skill_node.attr_or(name, 1.0);
+        }
+    }
+    let spells: Vec<String> = node
+        .children_named("spell")
+        .filter_map(|spell| spell.attr("name"))
+        .map(|name| name.to_string())
+        .collect();
+    return Vocation {
+        id,
+        description: node.attr("description").unwrap_or_default().to_string(),
+        from_vocation: node.attr_or("fromvoc", id),
+        gain_health: node.attr_or("gainhp", 5),
+        gain_mana: node.attr_or("gainmana", 5),
+        gain_capacity: node.attr_or("gaincap", 10),
+        gain_health_ticks: node.attr_or("gainhpticks", 6),
+        gain_health_amount: node.attr_or("gainhpamount", 1),
+        gain_mana_ticks: node.attr_or("gainmanaticks", 6),
+        gain_mana_amount: node.attr_or("gainmanaamount", 1),
+        mana_multiplier: node.attr_or("manamultiplier", 1.0),
+        skill_multipliers,
+        attack_speed: node.attr_or("attackspeed", 2000),
+        spells,
+    };
+}
+
+// the vocation of a character, characters of unknown vocations fall back to vocation 0 that
+// read_vocations always defines
+pub fn vocation_of(vocations: &HashMap<u8, Vocation>, vocation_id: u8) -> &Vocation {
+    return vocations
+        .get(&vocation_id)
+        .unwrap_or_else(|| &vocations[&0]);
+}
+
+// the vocation promoted from `vocation_id`, if the data defines one
+pub fn promoted_vocation(vocations: &HashMap<u8, Vocation>, vocation_id: u8) -> Option<u8> {
+    return vocations
+        .values()
+        .find(|vocation| vocation.from_vocation == vocation_id && vocation.id != vocation_id)
+        .map(|vocation| vocation.id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        creature::{
+            SKILL_DISTANCE, SKILL_FISHING, SKILL_FIST, create_characters, experience_for_level,
+        },
+        xml_io::{parse_xml, test_file},
+    };
+
+    fn sorcerer_and_knight() -> (Vocation, Vocation) {
+        let root = parse_xml(
+            r#"<vocations>
+                <vocation id="1" gainhp="5" gainmana="30" gaincap="10" manamultiplier="1.1">
+                    <skill fist="2.0" distance="2.0"/>
+                </vocation>
+                <vocation id="4" gainhp="15" gainmana="5" gaincap="25" manamultiplier="3.0">
+                    <skill fist="1.1" distance="1.4"/>
+                </vocation>
+            </vocations>"#,
+        );
+        let mut vocations = root.children_named("vocation").map(parse_vocation);
+        return (vocations.next().unwrap(), vocations.next().unwrap());
+    }
+
+    #[test]
+    fn skill_tries_grow_with_the_vocation_multiplier() {
+        let (sorcerer, knight) = sorcerer_and_knight();
+        assert_eq!(sorcerer.skill_tries_for_level(SKILL_FIST, 10), 50);
+        assert_eq!(sorcerer.skill_tries_for_level(SKILL_FIST, 12), 200);
+        assert_eq!(knight.skill_tries_for_level(SKILL_FIST, 10), 50);
+        assert_eq!(knight.skill_tries_for_level(SKILL_FIST, 12), 60);
+        assert_eq!(knight.skill_tries_for_level(SKILL_DISTANCE, 11), 42);
+        // skills missing from the data train like fist fighting at 1.0
+        assert_eq!(knight.skill_tries_for_level(SKILL_FISHING, 13), 20);
+    }
+
+    #[test]
+    fn mana_for_magic_level_grows_with_the_mana_multiplier() {
+        let (sorcerer, knight) = sorcerer_and_knight();
+        assert_eq!(sorcerer.mana_for_magic_level(0), 1600);
+        assert_eq!(sorcerer.mana_for_magic_level(1), 1760);
+        assert_eq!(knight.mana_for_magic_level(1), 4800);
+    }
+
+    #[test]
+    fn skill_tries_carry_over_to_the_next_level() {
+        let (sorcerer, _) = sorcerer_and_knight();
+        let mut character = create_characters().remove(0);
+        assert!(!character.add_skill_tries(SKILL_FIST, 49, &sorcerer));
+        assert!(character.add_skill_tries(SKILL_FIST, 160, &sorcerer));
+        assert_eq!(character.skills[SKILL_FIST].level, 12);
+        assert_eq!(character.skills[SKILL_FIST].tries, 59);
+    }
+
+    #[test]
+    fn levels_gained_add_the_vocation_gains() {
+        let (_, knight) = sorcerer_and_knight();
+        let mut character = create_characters().remove(0);
+        let (max_health, capacity) = (character.max_health, character.capacity);
+        let experience = experience_for_level(10) - character.experience;
+        assert!(character.add_experience(experience, &knight));
+        assert_eq!(character.level, 10);
+        assert_eq!(character.max_health, max_health + 30);
+        assert_eq!(character.capacity, capacity + 50);
+    }
+
+    #[test]
+    fn read_vocations_adds_vocation_0_and_links_promotions() {
+        let filepath = test_file(
+            "vocations.xml",
+            r#"<vocations>
+                <vocation id="4" description="a knight" fromvoc="4"/>
+                <vocation id="8" description="an elite knight" fromvoc="4"/>
+            </vocations>"#,
+        );
+        let vocations = read_vocations(&filepath);
+        assert_eq!(vocations.len(), 3);
+        assert_eq!(vocations[&0].attack_speed, 2000);
+        assert_eq!(promoted_vocation(&vocations, 4), Some(8));
+        assert_eq!(promoted_vocation(&vocations, 8), None);
+        assert_eq!(vocation_of(&vocations, 8).description, "an elite knight");
+        // unknown vocations fall back to vocation 0
+        assert_eq!(vocation_of(&vocations, 3).id, 0);
+    }
+}